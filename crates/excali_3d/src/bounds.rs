@@ -0,0 +1,123 @@
+use nalgebra::{Matrix4, Point3, Vector3, Vector4};
+
+use crate::Vertex;
+
+/// An axis aligned bounding box
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Aabb {
+    pub min: Point3<f32>,
+    pub max: Point3<f32>,
+}
+
+impl Aabb {
+    /// the box around every vertex, a single point at the origin if there are none
+    pub fn from_vertices(vertices: &[Vertex]) -> Self {
+        let mut points = vertices.iter().map(|vertex| Point3::from(vertex.position));
+        let Some(first) = points.next() else {
+            return Self {
+                min: Point3::origin(),
+                max: Point3::origin(),
+            };
+        };
+        let mut bounds = Self {
+            min: first,
+            max: first,
+        };
+        for point in points {
+            bounds.extend(&point);
+        }
+        bounds
+    }
+
+    /// grows the box to hold the point
+    pub fn extend(&mut self, point: &Point3<f32>) {
+        self.min = self.min.inf(point);
+        self.max = self.max.sup(point);
+    }
+
+    /// grows the box to hold the other box
+    pub fn merge(&mut self, other: &Aabb) {
+        self.extend(&other.min);
+        self.extend(&other.max);
+    }
+
+    pub fn corners(&self) -> [Point3<f32>; 8] {
+        let [min, max] = [self.min, self.max];
+        [
+            Point3::new(min.x, min.y, min.z),
+            Point3::new(max.x, min.y, min.z),
+            Point3::new(min.x, max.y, min.z),
+            Point3::new(max.x, max.y, min.z),
+            Point3::new(min.x, min.y, max.z),
+            Point3::new(max.x, min.y, max.z),
+            Point3::new(min.x, max.y, max.z),
+            Point3::new(max.x, max.y, max.z),
+        ]
+    }
+
+    /// the box around this one after it's been moved by the matrix
+    pub fn transformed(&self, matrix: &Matrix4<f32>) -> Self {
+        let mut corners = self
+            .corners()
+            .into_iter()
+            .map(|corner| matrix.transform_point(&corner));
+        let first = corners.next().unwrap();
+        let mut bounds = Self {
+            min: first,
+            max: first,
+        };
+        for corner in corners {
+            bounds.extend(&corner);
+        }
+        bounds
+    }
+}
+
+/// The six planes of what a camera can see, their normals face inward
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Frustum {
+    planes: [Vector4<f32>; 6],
+}
+
+impl Frustum {
+    /// extracts the planes from a camera's projection matrix, like
+    /// [crate::Camera::projection_matrix]
+    pub fn new(projection: &Matrix4<f32>) -> Self {
+        let row = |i: usize| projection.row(i).transpose();
+        let [x, y, z, w] = [row(0), row(1), row(2), row(3)];
+        Self {
+            planes: [w + x, w - x, w + y, w - y, w + z, w - z],
+        }
+    }
+
+    /// false only if the box is entirely behind one of the planes, boxes near the frustum's
+    /// corners can be kept though they're offscreen
+    pub fn intersects(&self, bounds: &Aabb) -> bool {
+        self.planes.iter().all(|plane| {
+            let normal = plane.xyz();
+            let [min, max] = [bounds.min.coords, bounds.max.coords];
+            // the corner furthest along the plane's normal
+            let furthest = Vector3::from_fn(|i, _| if normal[i] > 0.0 { max[i] } else { min[i] });
+            normal.dot(&furthest) + plane.w >= 0.0
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn transformed_box_holds_its_corners() {
+        let bounds = Aabb::from_vertices(&[
+            Vertex::new([-1.0, 0.0, 0.0], [1.0; 3]),
+            Vertex::new([1.0, 2.0, 0.5], [1.0; 3]),
+        ]);
+        assert_eq!(bounds.min, Point3::new(-1.0, 0.0, 0.0));
+        assert_eq!(bounds.max, Point3::new(1.0, 2.0, 0.5));
+
+        let moved = bounds.transformed(&Matrix4::new_translation(&Vector3::new(0.0, 0.0, 10.0)));
+        assert_eq!(moved.min, Point3::new(-1.0, 0.0, 10.0));
+        assert_eq!(moved.max, Point3::new(1.0, 2.0, 10.5));
+    }
+}
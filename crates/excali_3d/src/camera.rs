@@ -8,6 +8,8 @@ pub trait CameraEye {
 pub struct FPSEye {
     pub pitch: f32,
     pub yaw: f32,
+    /// moving the mouse up looks down
+    pub invert_y: bool,
 }
 
 impl Default for FPSEye {
@@ -15,6 +17,7 @@ impl Default for FPSEye {
         Self {
             pitch: 0.0,
             yaw: PI / 2.0,
+            invert_y: false,
         }
     }
 }
@@ -43,6 +46,45 @@ impl CameraEye for LookAtEye {
     }
 }
 
+/// Circles a fixed target, the camera's position follows from the yaw, pitch and distance
+pub struct OrbitEye {
+    pub target: Point3<f32>,
+    pub yaw: f32,
+    pub pitch: f32,
+    /// how far the camera is from the target, always positive
+    pub distance: f32,
+}
+
+impl Default for OrbitEye {
+    fn default() -> Self {
+        Self {
+            target: Point3::origin(),
+            yaw: -PI / 2.0,
+            pitch: 0.5,
+            distance: 10.0,
+        }
+    }
+}
+
+impl OrbitEye {
+    const PITCH_LIMIT: f32 = 1.5;
+    const MIN_DISTANCE: f32 = 0.1;
+
+    /// where the camera sits around the target
+    pub fn position(&self) -> Point3<f32> {
+        let (sin_pitch, cos_pitch) = self.pitch.sin_cos();
+        let (sin_yaw, cos_yaw) = self.yaw.sin_cos();
+        self.target
+            + Vector3::new(cos_pitch * cos_yaw, sin_pitch, cos_pitch * sin_yaw) * self.distance
+    }
+}
+
+impl CameraEye for OrbitEye {
+    fn target(&self, _position: &Point3<f32>) -> Point3<f32> {
+        self.target
+    }
+}
+
 pub struct Camera<T: CameraEye> {
     pub eye: T,
     pub up: Vector3<f32>,
@@ -67,29 +109,144 @@ impl<T: CameraEye + Default> Default for Camera<T> {
     }
 }
 
+/// Eases a camera's fovy toward a target, narrowing it reads as zooming in
+pub struct FovAnimator {
+    /// radians, clamped to [FovAnimator::MIN_FOVY] and [FovAnimator::MAX_FOVY]
+    pub target: f32,
+    /// how quickly the fovy closes the gap, higher is faster
+    pub speed: f32,
+}
+
+impl FovAnimator {
+    pub const MIN_FOVY: f32 = 10.0 * PI / 180.0;
+    pub const MAX_FOVY: f32 = 100.0 * PI / 180.0;
+
+    /// nudges the camera's fovy toward the target, returns if it has arrived
+    pub fn update<T: CameraEye>(&self, camera: &mut Camera<T>, dt: f32) -> bool {
+        const SNAP: f32 = 1e-4;
+        let target = self.target.clamp(Self::MIN_FOVY, Self::MAX_FOVY);
+        let difference = target - camera.fovy;
+        if difference.abs() < SNAP {
+            if difference != 0.0 {
+                camera.fovy = target;
+            }
+            return true;
+        }
+        camera.fovy += difference * (1.0 - (-self.speed * dt).exp());
+        false
+    }
+}
+
+/// A camera with parallel rays, looking from position to target
+pub struct OrthographicCamera {
+    pub position: Point3<f32>,
+    pub target: Point3<f32>,
+    pub up: Vector3<f32>,
+    /// world units visible from the bottom to the top of the screen
+    pub height: f32,
+    pub aspect: f32,
+    pub znear: f32,
+    pub zfar: f32,
+}
+
+impl OrthographicCamera {
+    /// looks straight down onto center from above
+    pub fn top_down(center: Point3<f32>, height: f32, aspect: f32) -> Self {
+        const DISTANCE: f32 = 100.0;
+        Self {
+            position: center + Vector3::y() * DISTANCE,
+            target: center,
+            up: Vector3::z(),
+            height,
+            aspect,
+            znear: 0.1,
+            zfar: DISTANCE * 2.0,
+        }
+    }
+
+    pub fn projection_matrix(&self) -> Matrix4<f32> {
+        let half_height = self.height / 2.0;
+        let half_width = half_height * self.aspect;
+        // wgpu keeps depths from 0 to 1 rather than OpenGL's -1 to 1, which would clip
+        // everything nearer than halfway to zfar
+        #[rustfmt::skip]
+        let depth_to_wgpu = Matrix4::new(
+            1.0, 0.0, 0.0, 0.0,
+            0.0, 1.0, 0.0, 0.0,
+            0.0, 0.0, 0.5, 0.5,
+            0.0, 0.0, 0.0, 1.0,
+        );
+        depth_to_wgpu
+            * Matrix4::new_orthographic(
+                -half_width,
+                half_width,
+                -half_height,
+                half_height,
+                self.znear,
+                self.zfar,
+            )
+            * Matrix4::look_at_rh(&self.position, &self.target, &self.up)
+    }
+}
+
 impl Camera<FPSEye> {
     pub fn rotate(&mut self, mouse_delta: &Vector2<f32>, sensitivity: f32) {
         self.eye.yaw += mouse_delta.x * sensitivity;
         const PITCH_LIMIT: f32 = 1.3;
+        let pitch_delta = match self.eye.invert_y {
+            true => mouse_delta.y,
+            false => -mouse_delta.y,
+        };
         self.eye.pitch =
-            (self.eye.pitch - mouse_delta.y * sensitivity).clamp(-PITCH_LIMIT, PITCH_LIMIT);
+            (self.eye.pitch + pitch_delta * sensitivity).clamp(-PITCH_LIMIT, PITCH_LIMIT);
+    }
+
+    /// x and z follow where the camera looks while y stays along the world's up
+    pub fn fly_direction(&self, direction: &Vector3<f32>) -> Vector3<f32> {
+        let horizontal = Vector3::new(direction.x, 0.0, direction.z);
+        self.point_to_world_space(&horizontal) - self.position.coords + self.up * direction.y
     }
 
     pub fn free_fly(
         &mut self,
-        mut direction: Vector3<f32>,
+        direction: Vector3<f32>,
         mouse_delta: &Vector2<f32>,
         distance: f32,
         sensitivity: f32,
     ) {
         self.rotate(mouse_delta, sensitivity);
         if direction.magnitude_squared() > 0.1 {
-            direction = direction.normalize() * distance;
-            self.position = self.point_to_world_space(&direction).into();
+            self.position += self.fly_direction(&(direction.normalize() * distance));
         }
     }
 }
 
+impl Camera<OrbitEye> {
+    /// camera with its position already placed around the eye's target
+    pub fn orbiting(eye: OrbitEye) -> Self {
+        Self {
+            position: eye.position(),
+            eye,
+            ..Self::default()
+        }
+    }
+
+    /// dragging sideways circles the target, dragging up and down tilts over it without
+    /// flipping past straight up or down
+    pub fn orbit(&mut self, mouse_delta: &Vector2<f32>, sensitivity: f32) {
+        self.eye.yaw += mouse_delta.x * sensitivity;
+        self.eye.pitch = (self.eye.pitch + mouse_delta.y * sensitivity)
+            .clamp(-OrbitEye::PITCH_LIMIT, OrbitEye::PITCH_LIMIT);
+        self.position = self.eye.position();
+    }
+
+    /// moves toward the target by scroll world units, never reaching it
+    pub fn zoom(&mut self, scroll: f32) {
+        self.eye.distance = (self.eye.distance - scroll).max(OrbitEye::MIN_DISTANCE);
+        self.position = self.eye.position();
+    }
+}
+
 impl<T: CameraEye> Camera<T> {
     pub fn point_to_world_space(&self, position: &Vector3<f32>) -> Vector3<f32> {
         (self.model_matrix() * Vector4::new(position.x, position.y, position.z, 1.0)).xyz()
@@ -99,6 +256,17 @@ impl<T: CameraEye> Camera<T> {
         Matrix4::<f32>::new_perspective(self.aspect, self.fovy, self.znear, self.zfar) * self.view()
     }
 
+    /// the world position at the clip space mouse position (-1.0 -> 1.0) and the depth buffer's
+    /// value there
+    pub fn unproject(&self, mouse_clip: Vector2<f32>, depth: f32) -> Point3<f32> {
+        let inverse = self
+            .projection_matrix()
+            .try_inverse()
+            .unwrap_or_else(Matrix4::identity);
+        let world = inverse * Vector4::new(mouse_clip.x, mouse_clip.y, depth, 1.0);
+        (world.xyz() / world.w).into()
+    }
+
     pub fn view(&self) -> Matrix4<f32> {
         Matrix4::look_at_rh(&self.position, &self.eye.target(&self.position), &self.up)
     }
@@ -137,3 +305,89 @@ impl<T: CameraEye> Camera<T> {
         parry3d::query::Ray::new(start.into(), direction)
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn fov_converges_to_target() {
+        let mut camera = Camera::<FPSEye> {
+            fovy: 1.2,
+            ..Default::default()
+        };
+        let animator = FovAnimator {
+            target: 0.8,
+            speed: 5.0,
+        };
+        let mut arrived = false;
+        for _ in 0..600 {
+            arrived = animator.update(&mut camera, 1.0 / 60.0);
+        }
+        assert!(arrived);
+        assert_eq!(camera.fovy, 0.8);
+        assert!(animator.update(&mut camera, 1.0 / 60.0));
+        assert_eq!(camera.fovy, 0.8);
+    }
+
+    #[test]
+    fn fov_target_is_clamped() {
+        let mut camera = Camera::<FPSEye> {
+            fovy: 1.0,
+            ..Default::default()
+        };
+        let animator = FovAnimator {
+            target: 0.0,
+            speed: 1000.0,
+        };
+        while !animator.update(&mut camera, 1.0) {}
+        assert_eq!(camera.fovy, FovAnimator::MIN_FOVY);
+    }
+
+    #[test]
+    fn fly_follows_yaw() {
+        let mut camera = Camera::<FPSEye>::default();
+        let forward = Vector3::z();
+        let right = camera.fly_direction(&Vector3::new(-1.0, 0.0, 0.0));
+        camera.rotate(&Vector2::new(PI / 2.0, 0.0), 1.0);
+        assert!((camera.fly_direction(&forward) - right).magnitude() < 1e-5);
+    }
+
+    #[test]
+    fn fly_vertical_stays_world_up() {
+        let mut camera = Camera::<FPSEye>::default();
+        camera.rotate(&Vector2::new(0.3, -1.0), 1.0);
+        assert!((camera.fly_direction(&Vector3::y()) - Vector3::y()).magnitude() < 1e-5);
+    }
+
+    #[test]
+    fn half_orbit_is_opposite_the_target() {
+        let target = Point3::new(1.0, 2.0, 3.0);
+        let mut camera = Camera::orbiting(OrbitEye {
+            target,
+            pitch: 0.0,
+            ..Default::default()
+        });
+        let before = camera.position - target;
+        camera.orbit(&Vector2::new(PI, 0.0), 1.0);
+        let after = camera.position - target;
+        assert!((after + before).magnitude() < 1e-4, "{before} {after}");
+
+        camera.orbit(&Vector2::new(0.0, 100.0), 1.0);
+        assert_eq!(camera.eye.pitch, OrbitEye::PITCH_LIMIT);
+        camera.zoom(100.0);
+        assert!(camera.eye.distance > 0.0);
+        assert!(camera.position != target);
+    }
+
+    #[test]
+    fn invert_y_flips_pitch() {
+        let mut camera = Camera::<FPSEye>::default();
+        let mut inverted = Camera::<FPSEye>::default();
+        inverted.eye.invert_y = true;
+        camera.rotate(&Vector2::new(0.0, 0.5), 1.0);
+        inverted.rotate(&Vector2::new(0.0, 0.5), 1.0);
+        assert!(camera.eye.pitch < 0.0);
+        assert_eq!(inverted.eye.pitch, -camera.eye.pitch);
+    }
+}
@@ -0,0 +1,152 @@
+use nalgebra::{Point3, Vector2, Vector3, Vector4};
+
+use crate::{Camera, CameraEye, Vertex};
+
+/// A world axis a gizmo handle moves along
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Axis {
+    X,
+    Y,
+    Z,
+}
+
+impl Axis {
+    pub const ALL: [Axis; 3] = [Axis::X, Axis::Y, Axis::Z];
+
+    pub fn direction(&self) -> Vector3<f32> {
+        match self {
+            Axis::X => Vector3::x(),
+            Axis::Y => Vector3::y(),
+            Axis::Z => Vector3::z(),
+        }
+    }
+
+    pub fn color(&self) -> [f32; 3] {
+        match self {
+            Axis::X => [1.0, 0.2, 0.2],
+            Axis::Y => [0.2, 1.0, 0.2],
+            Axis::Z => [0.2, 0.4, 1.0],
+        }
+    }
+}
+
+/// Three axis lines with grabbable ends for moving something through the world
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TranslateGizmo {
+    pub origin: Point3<f32>,
+    /// how far each handle is from the origin
+    pub length: f32,
+    /// how close a ray must pass to a handle's end to grab it
+    pub handle_radius: f32,
+}
+
+impl TranslateGizmo {
+    pub fn new(origin: Point3<f32>) -> Self {
+        Self {
+            origin,
+            length: 1.5,
+            handle_radius: 0.25,
+        }
+    }
+
+    pub fn handle(&self, axis: Axis) -> Point3<f32> {
+        self.origin + axis.direction() * self.length
+    }
+
+    /// line pairs for [crate::LineRenderer], the highlighted axis is drawn white
+    pub fn vertices(&self, highlighted: Option<Axis>) -> Vec<Vertex> {
+        let mut vertices = Vec::new();
+        for axis in Axis::ALL {
+            let color = match highlighted == Some(axis) {
+                true => [1.0; 3],
+                false => axis.color(),
+            };
+            vertices.push(Vertex::new(self.origin.into(), color));
+            vertices.push(Vertex::new(self.handle(axis).into(), color));
+        }
+        vertices
+    }
+
+    /// returns the axis of the nearest handle end the ray passes through
+    pub fn hit(&self, origin: &Point3<f32>, direction: &Vector3<f32>) -> Option<Axis> {
+        let direction = direction.try_normalize(f32::EPSILON)?;
+        Axis::ALL
+            .into_iter()
+            .filter_map(|axis| {
+                let to_handle = self.handle(axis) - origin;
+                let along = to_handle.dot(&direction);
+                let distance = (to_handle - direction * along).magnitude();
+                (along > 0.0 && distance <= self.handle_radius).then_some((axis, along))
+            })
+            .min_by(|(_, a), (_, b)| a.total_cmp(b))
+            .map(|(axis, _)| axis)
+    }
+
+    /// the distance along the axis a drag across the screen moves, the drag is in clip space
+    /// (-1.0 -> 1.0) so it follows the handle's direction on screen
+    pub fn drag_distance<T: CameraEye>(
+        &self,
+        camera: &Camera<T>,
+        axis: Axis,
+        drag: Vector2<f32>,
+    ) -> f32 {
+        let projection = camera.projection_matrix();
+        let project = |point: Point3<f32>| {
+            let clip = projection * Vector4::new(point.x, point.y, point.z, 1.0);
+            Vector2::new(clip.x, clip.y) / clip.w
+        };
+        let screen_axis = project(self.origin + axis.direction()) - project(self.origin);
+        let length_squared = screen_axis.magnitude_squared();
+        if length_squared < f32::EPSILON {
+            return 0.0;
+        }
+        drag.dot(&screen_axis) / length_squared
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::FPSEye;
+
+    fn camera() -> Camera<FPSEye> {
+        // looks along +z so +x is on the left of the screen
+        Camera {
+            position: Point3::new(0.0, 0.0, -10.0),
+            fovy: 1.0,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn x_drag_moves_along_x() {
+        let camera = camera();
+        let gizmo = TranslateGizmo::new(Point3::origin());
+        let focal = 1.0 / (camera.fovy / 2.0).tan();
+        // a point 2 units along x seen from 10 units away
+        let screen_x = -2.0 * focal / (10.0 * camera.aspect);
+
+        let distance = gizmo.drag_distance(&camera, Axis::X, Vector2::new(screen_x, 0.0));
+        assert!((distance - 2.0).abs() < 1e-3, "moved {distance}");
+        let across = gizmo.drag_distance(&camera, Axis::X, Vector2::new(0.0, 0.3));
+        assert!(across.abs() < 1e-5);
+        // the z axis points straight into the screen
+        assert_eq!(
+            gizmo.drag_distance(&camera, Axis::Z, Vector2::new(0.3, 0.3)),
+            0.0
+        );
+    }
+
+    #[test]
+    fn ray_grabs_nearest_handle() {
+        let gizmo = TranslateGizmo::new(Point3::origin());
+        let handle = gizmo.handle(Axis::Y);
+        let origin = Point3::new(handle.x, handle.y, -10.0);
+        assert_eq!(gizmo.hit(&origin, &Vector3::z()), Some(Axis::Y));
+        assert_eq!(gizmo.hit(&origin, &-Vector3::z()), None);
+        assert_eq!(
+            gizmo.hit(&Point3::new(0.7, 0.7, -10.0), &Vector3::z()),
+            None
+        );
+    }
+}
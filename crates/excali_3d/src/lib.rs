@@ -1,11 +1,21 @@
 #[cfg(feature = "parry3d")]
 pub use parry3d;
 
+mod bounds;
 mod camera;
+mod gizmo;
 mod line_renderer;
+mod marching_squares;
+#[cfg(feature = "tobj")]
+mod obj;
 mod renderer;
 mod transform;
+pub use bounds::*;
 pub use camera::*;
+pub use gizmo::*;
 pub use line_renderer::*;
+pub use marching_squares::*;
+#[cfg(feature = "tobj")]
+pub use obj::*;
 pub use renderer::*;
 pub use transform::*;
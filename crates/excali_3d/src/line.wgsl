@@ -9,7 +9,9 @@ var<uniform> camera: CameraUniform;
 
 struct VertexInput {
     @location(0) position: vec3<f32>,
-    @location(1) color: vec3<f32>,
+    @location(1) other: vec3<f32>,
+    @location(2) color: vec3<f32>,
+    @location(3) offset: f32,
 }
 
 struct VertexOutput {
@@ -21,9 +23,40 @@ struct VertexOutput {
 fn vs_main(
     model: VertexInput,
 ) -> VertexOutput {
+    let m = camera.matrix;
+    let position = m * vec4<f32>(model.position, 1.0);
+    let end = m * vec4<f32>(model.other, 1.0);
     var out: VertexOutput;
-    out.clip_position = camera.matrix * vec4<f32>(model.position.xyz, 1.0);
-	out.color = model.color;
+    out.color = model.color;
+    let near = 0.0001;
+    if position.w < near && end.w < near {
+        // the whole line is behind the camera, w below 0 puts every corner outside the clip volume
+        out.clip_position = vec4<f32>(0.0, 0.0, 0.0, -1.0);
+        return out;
+    }
+    // an end behind the camera is moved along the line to just in front of it
+    let clip = select(
+        position,
+        mix(position, end, (near - position.w) / (end.w - position.w)),
+        position.w < near,
+    );
+    let other = select(
+        end,
+        mix(end, position, (near - end.w) / (position.w - end.w)),
+        end.w < near,
+    );
+    // how far a world unit moves in clip space across and up the screen at a depth of 1
+    let scale = vec2<f32>(
+        length(vec3<f32>(m[0][0], m[1][0], m[2][0])),
+        length(vec3<f32>(m[0][1], m[1][1], m[2][1])),
+    );
+    // undoing the scale keeps the direction's angle true to the screen's aspect ratio
+    let delta = other.xy / other.w / scale - clip.xy / clip.w / scale;
+    // ends on the same point on screen have no direction, any will do for a line seen end on
+    let along = select(vec2<f32>(1.0, 0.0), normalize(delta), length(delta) > 0.000001);
+    let across = vec2<f32>(-along.y, along.x) * scale * model.offset;
+    // not divided by w, so the line thins with distance like something in the world
+    out.clip_position = clip + vec4<f32>(across, 0.0, 0.0);
     return out;
 }
 
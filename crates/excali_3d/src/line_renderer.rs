@@ -1,20 +1,75 @@
 use excali_render::wgpu::util::DeviceExt;
 use excali_render::wgpu::{
-    include_wgsl, CommandEncoderDescriptor, FragmentState, FrontFace, LoadOp, MultisampleState,
-    Operations, PolygonMode, PrimitiveState, PrimitiveTopology, RenderPassColorAttachment,
-    RenderPassDescriptor, RenderPipelineDescriptor, TextureView, VertexState,
+    include_wgsl, BufferAddress, CommandEncoderDescriptor, FragmentState, FrontFace, LoadOp,
+    MultisampleState, Operations, PolygonMode, PrimitiveState, PrimitiveTopology,
+    RenderPassColorAttachment, RenderPassDescriptor, RenderPipelineDescriptor, TextureView,
+    VertexAttribute, VertexBufferLayout, VertexState, VertexStepMode,
 };
 use excali_render::{wgpu, Renderer};
 
 use crate::renderer::{Renderer3D, Vertex};
 
+/// One corner of a line's quad, the vertex shader pushes it out from its end of the line
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct LineVertex {
+    position: [f32; 3],
+    /// the line's other end, to find which way is across the line on screen
+    other: [f32; 3],
+    color: [f32; 3],
+    /// how far across the line the corner is pushed, half the thickness either way
+    offset: f32,
+}
+
+impl LineVertex {
+    fn descriptor<'a>() -> VertexBufferLayout<'a> {
+        const ATTRIBUTES: [VertexAttribute; 4] = wgpu::vertex_attr_array![
+            0 => Float32x3,
+            1 => Float32x3,
+            2 => Float32x3,
+            3 => Float32,
+        ];
+        VertexBufferLayout {
+            array_stride: std::mem::size_of::<LineVertex>() as BufferAddress,
+            step_mode: VertexStepMode::Vertex,
+            attributes: &ATTRIBUTES,
+        }
+    }
+}
+
+/// a quad for each pair of vertices, the ends' corners are in the same order across the line
+fn line_quads(vertices: &[Vertex], thickness: f32) -> (Vec<LineVertex>, Vec<u32>) {
+    let half = thickness / 2.0;
+    let mut quads = Vec::with_capacity(vertices.len() * 2);
+    let mut indices = Vec::with_capacity(vertices.len() * 3);
+    for line in vertices.chunks_exact(2) {
+        let first = quads.len() as u32;
+        let [a, b] = [line[0], line[1]];
+        // across is flipped at the far end since it looks back along the line
+        for (end, other, offset) in [(a, b, half), (a, b, -half), (b, a, -half), (b, a, half)] {
+            quads.push(LineVertex {
+                position: end.position,
+                other: other.position,
+                color: end.color,
+                offset,
+            });
+        }
+        indices.extend([0, 1, 2, 2, 1, 3].map(|index| first + index));
+    }
+    (quads, indices)
+}
+
 pub struct LineRenderer {
     render_pipeline: wgpu::RenderPipeline,
     vertices: usize,
     vertex_buffer: wgpu::Buffer,
+    indices: usize,
+    index_buffer: wgpu::Buffer,
+    /// the width of lines in world units
+    thickness: f32,
 }
 
-fn create_vertex_buffer(vertices: &[Vertex], renderer: &Renderer) -> wgpu::Buffer {
+fn create_vertex_buffer(vertices: &[LineVertex], renderer: &Renderer) -> wgpu::Buffer {
     renderer
         .device
         .create_buffer_init(&wgpu::util::BufferInitDescriptor {
@@ -24,14 +79,22 @@ fn create_vertex_buffer(vertices: &[Vertex], renderer: &Renderer) -> wgpu::Buffe
         })
 }
 
+fn create_index_buffer(indices: &[u32], renderer: &Renderer) -> wgpu::Buffer {
+    renderer
+        .device
+        .create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Line Renderer Index Buffer"),
+            contents: bytemuck::cast_slice(indices),
+            usage: wgpu::BufferUsages::INDEX | wgpu::BufferUsages::COPY_DST,
+        })
+}
+
 impl LineRenderer {
     /// lines must be greater than 0
     pub fn new(renderer: &Renderer, renderer_3d: &Renderer3D, lines: usize) -> Self {
-        let mut vertices = Vec::<Vertex>::new();
-        for _ in 0..lines * 2 {
-            vertices.push(Vertex::new([0.0, 0.0, 0.0], [0.0, 0.0, 0.0]));
-            vertices.push(Vertex::new([0.0, 0.0, 0.0], [0.0, 0.0, 0.0]));
-        }
+        let thickness = 0.05;
+        let (vertices, indices) =
+            line_quads(&vec![Vertex::new([0.0; 3], [0.0; 3]); lines * 2], thickness);
 
         let shader = renderer
             .device
@@ -51,7 +114,7 @@ impl LineRenderer {
                 vertex: VertexState {
                     module: &shader,
                     entry_point: "vs_main",
-                    buffers: &[Vertex::descriptor()],
+                    buffers: &[LineVertex::descriptor()],
                 },
                 fragment: Some(FragmentState {
                     module: &shader,
@@ -59,11 +122,11 @@ impl LineRenderer {
                     targets: &renderer_3d.targets,
                 }),
                 primitive: PrimitiveState {
-                    topology: PrimitiveTopology::LineList,
+                    topology: PrimitiveTopology::TriangleList,
                     strip_index_format: None,
                     front_face: FrontFace::Ccw,
                     cull_mode: None,
-                    polygon_mode: PolygonMode::Line,
+                    polygon_mode: PolygonMode::Fill,
                     unclipped_depth: false,
                     conservative: false,
                 },
@@ -73,11 +136,20 @@ impl LineRenderer {
             });
         Self {
             render_pipeline,
-            vertices: lines * 2,
+            vertices: vertices.len(),
             vertex_buffer: create_vertex_buffer(&vertices, renderer),
+            indices: indices.len(),
+            index_buffer: create_index_buffer(&indices, renderer),
+            thickness,
         }
     }
 
+    /// the width of lines drawn from now on, in world units
+    pub fn set_thickness(&mut self, thickness: f32) {
+        self.thickness = thickness;
+    }
+
+    /// draws a line between each pair of vertices
     pub fn draw(
         &mut self,
         vertices: Vec<Vertex>,
@@ -90,13 +162,23 @@ impl LineRenderer {
             .create_command_encoder(&CommandEncoderDescriptor {
                 label: Some("Line Command Encoder"),
             });
+        let (vertices, indices) = line_quads(&vertices, self.thickness);
         if vertices.len() > self.vertices {
+            self.vertices = vertices.len();
             self.vertex_buffer = create_vertex_buffer(&vertices, renderer);
         } else {
             renderer
                 .queue
                 .write_buffer(&self.vertex_buffer, 0, bytemuck::cast_slice(&vertices));
         }
+        if indices.len() > self.indices {
+            self.indices = indices.len();
+            self.index_buffer = create_index_buffer(&indices, renderer);
+        } else {
+            renderer
+                .queue
+                .write_buffer(&self.index_buffer, 0, bytemuck::cast_slice(&indices));
+        }
 
         let mut render_pass = encoder.begin_render_pass(&RenderPassDescriptor {
             label: Some("Line Render Pass"),
@@ -114,8 +196,29 @@ impl LineRenderer {
         render_pass.set_pipeline(&self.render_pipeline);
         render_pass.set_bind_group(0, &renderer_3d.camera_bind_group, &[]);
         render_pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
-        render_pass.draw(0..vertices.len() as u32, 0..1);
+        render_pass.set_index_buffer(self.index_buffer.slice(..), wgpu::IndexFormat::Uint32);
+        render_pass.draw_indexed(0..indices.len() as u32, 0, 0..1);
         drop(render_pass);
         encoder.finish()
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn each_line_is_a_quad() {
+        let vertices: Vec<Vertex> = (0..6)
+            .map(|i| Vertex::new([i as f32, 0.0, 0.0], [1.0; 3]))
+            .collect();
+        let (quads, indices) = line_quads(&vertices, 0.5);
+        assert_eq!(quads.len(), 3 * 4);
+        assert_eq!(indices.len(), 3 * 6);
+        assert_eq!(indices[6..12], [4, 5, 6, 6, 5, 7]);
+        // both ends push the same side of the line out the same way
+        assert_eq!(quads[0].offset, 0.25);
+        assert_eq!(quads[2].offset, -0.25);
+        assert_eq!(quads[2].other, quads[0].position);
+    }
+}
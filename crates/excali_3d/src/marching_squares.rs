@@ -0,0 +1,284 @@
+use nalgebra::Vector3;
+
+use crate::Vertex;
+
+/// every cell gets the same number of slots so one can be rewritten without moving the others
+pub const CELL_VERTICES: usize = 16;
+pub const CELL_INDICES: usize = 30;
+
+/// corners then edge midpoints of a marching cell, in (x, z) going around the cell
+const CELL_POINTS: [[f32; 2]; 8] = [
+    [0.0, 0.0],
+    [1.0, 0.0],
+    [1.0, 1.0],
+    [0.0, 1.0],
+    [0.5, 0.0],
+    [1.0, 0.5],
+    [0.5, 1.0],
+    [0.0, 0.5],
+];
+/// the perimeter of a cell, each corner followed by the midpoint of the edge after it
+const PERIMETER: [usize; 8] = [0, 4, 1, 5, 2, 6, 3, 7];
+
+/// accumulates each triangle's normal into its vertices' normals weighted by the triangle's area
+fn smooth_normals(vertices: &mut [Vertex], indices: &[u16]) {
+    let mut normals = vec![Vector3::<f32>::zeros(); vertices.len()];
+    for triangle in indices.chunks_exact(3) {
+        let [a, b, c] = [0, 1, 2].map(|i| Vector3::from(vertices[triangle[i] as usize].position));
+        // the cross product's length is twice the triangle's area
+        let normal = (b - a).cross(&(c - a));
+        for index in triangle {
+            normals[*index as usize] += normal;
+        }
+    }
+    for (vertex, normal) in vertices.iter_mut().zip(normals) {
+        if let Some(normal) = normal.try_normalize(f32::EPSILON) {
+            vertex.normal = normal.into();
+        }
+    }
+}
+
+/// the geometry of the marching cell whose lowest corner is at (x, z), with indices local to the cell,
+/// corners are ordered (x, z), (x + 1, z), (x + 1, z + 1), (x, z + 1)
+/// and unused index slots are degenerate triangles. Normals are smoothed within the cell, the
+/// tops of walls have their own vertices so cliffs are shaded apart from the raised side
+pub fn marching_cell(
+    heights: [u16; 4],
+    x: usize,
+    z: usize,
+    color: [f32; 3],
+) -> ([Vertex; CELL_VERTICES], [u16; CELL_INDICES]) {
+    let mut vertices = [Vertex::new([0.0; 3], [0.0; 3]); CELL_VERTICES];
+    let mut indices = [0u16; CELL_INDICES];
+    let mut vertex_count = 0;
+    let mut index_count = 0;
+
+    let base = *heights.iter().min().unwrap();
+    let high = heights.map(|height| height > base);
+    let saddle = high == [true, false, true, false] || high == [false, true, false, true];
+
+    // the height of the raised side at a point
+    let top = |point: usize| match point {
+        0..=3 => heights[point],
+        _ => heights[point - 4].max(heights[(point - 3) % 4]),
+    };
+    // a midpoint belongs to both sides when its edge crosses the contour
+    let on_side = |point: usize, side: bool| match point {
+        0..=3 => high[point] == side,
+        _ => high[point - 4] != high[(point - 3) % 4],
+    };
+
+    // fans a convex polygon given counter clockwise in (x, z) so it faces up,
+    // returns each point's vertex so walls can share them
+    let mut polygon = |points: &[usize], raised: bool| {
+        let mut point_vertices = [0u16; 8];
+        let start = vertex_count as u16;
+        for point in points {
+            let [px, pz] = CELL_POINTS[*point];
+            let height = if raised { top(*point) } else { base };
+            point_vertices[*point] = vertex_count as u16;
+            vertices[vertex_count] =
+                Vertex::new([x as f32 + px, height as f32, z as f32 + pz], color);
+            vertex_count += 1;
+        }
+        for i in 1..points.len() as u16 - 1 {
+            indices[index_count..index_count + 3].copy_from_slice(&[
+                start,
+                start + i + 1,
+                start + i,
+            ]);
+            index_count += 3;
+        }
+        point_vertices
+    };
+
+    if high.iter().all(|high| !high) {
+        polygon(&[0, 1, 2, 3], false);
+        smooth_normals(&mut vertices[..vertex_count], &indices[..index_count]);
+        return (vertices, indices);
+    }
+
+    let lower: Vec<usize> = PERIMETER
+        .into_iter()
+        .filter(|point| on_side(*point, false))
+        .collect();
+    let lower = polygon(&lower, false);
+
+    // each run of raised corners is cut off by one contour, saddles have two
+    let mut contours = Vec::<([usize; 2], [u16; 8])>::new();
+    if saddle {
+        for corner in (0..4).filter(|corner| high[*corner]) {
+            let before = 4 + (corner + 3) % 4;
+            let after = 4 + corner;
+            contours.push(([before, after], polygon(&[before, corner, after], true)));
+        }
+    } else {
+        // the corners and the contour's ends, rotated to start just after the lowered side
+        let points: Vec<usize> = PERIMETER
+            .into_iter()
+            .filter(|point| on_side(*point, true) || on_side(*point, false))
+            .collect();
+        let start = (0..points.len())
+            .find(|i| {
+                on_side(points[*i], true)
+                    && !on_side(points[(*i + points.len() - 1) % points.len()], true)
+            })
+            .unwrap();
+        let upper: Vec<usize> = (0..points.len())
+            .map(|i| points[(start + i) % points.len()])
+            .take_while(|point| on_side(*point, true))
+            .collect();
+        contours.push(([upper[0], upper[upper.len() - 1]], polygon(&upper, true)));
+    }
+
+    // walls drop from the raised side down to the base, facing away from it
+    for ([a, b], upper) in contours {
+        let top = [a, b].map(|point| {
+            vertices[vertex_count] = vertices[upper[point] as usize];
+            vertex_count += 1;
+            (vertex_count - 1) as u16
+        });
+        indices[index_count..index_count + 6]
+            .copy_from_slice(&[lower[a], lower[b], top[1], lower[a], top[1], top[0]]);
+        index_count += 6;
+    }
+    smooth_normals(&mut vertices[..vertex_count], &indices[..index_count]);
+    (vertices, indices)
+}
+
+/// meshes a grid of width by height samples, one unit apart with heights of one unit per step,
+/// cell (x, z) fills the slots starting at cell x * (height - 1) + z,
+/// the cells times [CELL_VERTICES] must fit in u16 indices
+pub fn marching_squares(
+    sampler: &dyn Fn(usize, usize) -> u16,
+    width: usize,
+    height: usize,
+    color: [f32; 3],
+) -> (Vec<Vertex>, Vec<u16>) {
+    let cells = width.saturating_sub(1) * height.saturating_sub(1);
+    debug_assert!(cells * CELL_VERTICES <= u16::MAX as usize + 1);
+    let mut vertices = Vec::with_capacity(cells * CELL_VERTICES);
+    let mut indices = Vec::with_capacity(cells * CELL_INDICES);
+    for x in 0..width.saturating_sub(1) {
+        for z in 0..height.saturating_sub(1) {
+            let first_vertex = vertices.len() as u16;
+            let (cell_vertices, cell_indices) = marching_cell(
+                [
+                    sampler(x, z),
+                    sampler(x + 1, z),
+                    sampler(x + 1, z + 1),
+                    sampler(x, z + 1),
+                ],
+                x,
+                z,
+                color,
+            );
+            vertices.extend(cell_vertices);
+            indices.extend(cell_indices.map(|index| first_vertex + index));
+        }
+    }
+    (vertices, indices)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn triangles(case: usize) -> Vec<[Vector3<f32>; 3]> {
+        let heights = [0, 1, 2, 3].map(|corner| (case >> corner & 1) as u16);
+        let (vertices, indices) = marching_cell(heights, 0, 0, [1.0; 3]);
+        indices
+            .chunks(3)
+            .filter(|triangle| triangle[0] != triangle[1] || triangle[1] != triangle[2])
+            .map(|triangle| {
+                [0, 1, 2].map(|i| Vector3::from(vertices[triangle[i] as usize].position))
+            })
+            .collect()
+    }
+
+    #[test]
+    fn cases_have_expected_triangles() {
+        let expected = [2, 6, 6, 6, 6, 10, 6, 6, 6, 6, 10, 6, 6, 6, 6, 2];
+        for (case, count) in expected.into_iter().enumerate() {
+            assert_eq!(triangles(case).len(), count, "case {case}");
+        }
+    }
+
+    #[test]
+    fn cases_face_up_and_out() {
+        for case in 0..16 {
+            let raised: Vec<Vector3<f32>> = (0..4)
+                .filter(|corner| case >> corner & 1 == 1 && case != 15)
+                .map(|corner| {
+                    let [x, z] = CELL_POINTS[corner];
+                    Vector3::new(x, 0.0, z)
+                })
+                .collect();
+            for [a, b, c] in triangles(case) {
+                let normal = (b - a).cross(&(c - a));
+                if normal.y != 0.0 {
+                    assert!(normal.y > 0.0, "case {case} has a floor facing down");
+                    continue;
+                }
+                // walls face away from the nearest raised corner
+                let center = (a + b + c) / 3.0;
+                let center = Vector3::new(center.x, 0.0, center.z);
+                let corner = raised
+                    .iter()
+                    .min_by(|p, q| {
+                        (center - *p)
+                            .magnitude()
+                            .total_cmp(&(center - *q).magnitude())
+                    })
+                    .unwrap();
+                assert!(
+                    normal.dot(&(center - corner)) > 0.0,
+                    "case {case} has a wall facing in"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn flat_top_faces_up() {
+        let (vertices, indices) = marching_squares(&|_, _| 3, 2, 2, [1.0; 3]);
+        let used: Vec<&Vertex> = indices
+            .iter()
+            .map(|index| &vertices[*index as usize])
+            .collect();
+        assert!(used.iter().all(|vertex| vertex.normal == [0.0, 1.0, 0.0]));
+    }
+
+    #[test]
+    fn wall_tops_face_out() {
+        // the corner at (1, 1) is raised
+        let (vertices, indices) = marching_cell([0, 0, 1, 0], 0, 0, [1.0; 3]);
+        let raised = indices
+            .iter()
+            .map(|index| &vertices[*index as usize])
+            .filter(|vertex| vertex.position[1] == 1.0);
+        let (walls, tops): (Vec<&Vertex>, Vec<&Vertex>) =
+            raised.partition(|vertex| vertex.normal[1].abs() < 1e-6);
+        assert!(!walls.is_empty());
+        assert!(tops.iter().all(|vertex| vertex.normal == [0.0, 1.0, 0.0]));
+        for wall in walls {
+            // away from the raised corner
+            let normal = Vector3::from(wall.normal);
+            assert!(normal.dot(&Vector3::new(-1.0, 0.0, -1.0)) > 0.0);
+        }
+    }
+
+    #[test]
+    fn grid_is_laid_out_in_cell_slots() {
+        let (vertices, indices) =
+            marching_squares(&|x, z| (x == 1 && z == 1) as u16, 3, 4, [1.0; 3]);
+        assert_eq!(vertices.len(), 6 * CELL_VERTICES);
+        assert_eq!(indices.len(), 6 * CELL_INDICES);
+        // x * (height - 1) + z
+        let cell = 4;
+        let range = cell * CELL_INDICES..(cell + 1) * CELL_INDICES;
+        assert!(indices[range]
+            .iter()
+            .all(|index| (*index as usize) / CELL_VERTICES == cell));
+    }
+}
@@ -0,0 +1,154 @@
+use excali_render::wgpu::Device;
+
+use crate::{Model, Vertex};
+
+const DEFAULT_COLOR: [f32; 3] = [1.0; 3];
+
+/// Why an OBJ couldn't be turned into a model
+#[derive(Debug)]
+pub enum ObjError {
+    Parse(tobj::LoadError),
+    /// more vertices than u16 indices can reach
+    TooManyVertices(usize),
+}
+
+impl std::fmt::Display for ObjError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Parse(err) => write!(f, "can't parse obj: {err}"),
+            Self::TooManyVertices(count) => write!(
+                f,
+                "obj has {count} vertices but only {} can be indexed",
+                u16::MAX as usize + 1
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ObjError {}
+
+/// every mesh in the OBJ joined together, vertices are colored by the OBJ's vertex colors,
+/// otherwise their material's diffuse color from the MTL, otherwise white. Texture coordinates
+/// are kept for [Model::textured]
+pub fn obj_mesh(obj: &[u8], mtl: Option<&[u8]>) -> Result<(Vec<Vertex>, Vec<u16>), ObjError> {
+    let options = tobj::LoadOptions {
+        single_index: true,
+        triangulate: true,
+        ..Default::default()
+    };
+    let (models, materials) = tobj::load_obj_buf(&mut &obj[..], &options, |_| match mtl {
+        Some(mtl) => tobj::load_mtl_buf(&mut &mtl[..]),
+        None => Err(tobj::LoadError::OpenFileFailed),
+    })
+    .map_err(ObjError::Parse)?;
+    let materials = materials.unwrap_or_default();
+
+    let count: usize = models
+        .iter()
+        .map(|model| model.mesh.positions.len() / 3)
+        .sum();
+    if count > u16::MAX as usize + 1 {
+        return Err(ObjError::TooManyVertices(count));
+    }
+
+    let mut vertices = Vec::with_capacity(count);
+    let mut indices = Vec::new();
+    for model in models {
+        let mesh = model.mesh;
+        let material_color = mesh
+            .material_id
+            .and_then(|id| materials.get(id))
+            .and_then(|material| material.diffuse)
+            .unwrap_or(DEFAULT_COLOR);
+        let first_vertex = vertices.len() as u16;
+        for (i, position) in mesh.positions.chunks_exact(3).enumerate() {
+            let color = match mesh.vertex_color.get(i * 3..i * 3 + 3) {
+                Some(color) => [color[0], color[1], color[2]],
+                None => material_color,
+            };
+            let vertex = Vertex::new([position[0], position[1], position[2]], color);
+            vertices.push(match mesh.texcoords.get(i * 2..i * 2 + 2) {
+                // obj's v goes up from the bottom of the image
+                Some(uv) => vertex.with_tex_coords([uv[0], 1.0 - uv[1]]),
+                None => vertex,
+            });
+        }
+        indices.extend(
+            mesh.indices
+                .iter()
+                .map(|index| first_vertex + *index as u16),
+        );
+    }
+    Ok((vertices, indices))
+}
+
+impl Model {
+    pub fn from_obj(device: &Device, bytes: &[u8], name: String) -> Result<Self, ObjError> {
+        Self::from_obj_with_materials(device, bytes, None, name)
+    }
+
+    /// colors faces with the diffuse colors of the OBJ's MTL file
+    pub fn from_obj_with_materials(
+        device: &Device,
+        obj: &[u8],
+        mtl: Option<&[u8]>,
+        name: String,
+    ) -> Result<Self, ObjError> {
+        let (vertices, indices) = obj_mesh(obj, mtl)?;
+        Ok(Self::new(device, vertices, indices, name))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const CUBE: &str = "
+v 0 0 0
+v 1 0 0
+v 1 1 0
+v 0 1 0
+v 0 0 1
+v 1 0 1
+v 1 1 1
+v 0 1 1
+f 1 2 3 4
+f 5 8 7 6
+f 1 5 6 2
+f 2 6 7 3
+f 3 7 8 4
+f 5 1 4 8
+";
+
+    #[test]
+    fn cube_has_quads_triangulated() {
+        let (vertices, indices) = obj_mesh(CUBE.as_bytes(), None).unwrap();
+        assert_eq!(vertices.len(), 8);
+        assert_eq!(indices.len(), 6 * 2 * 3);
+        assert!(vertices.iter().all(|vertex| vertex.color == DEFAULT_COLOR));
+    }
+
+    #[test]
+    fn meshes_are_joined_with_materials() {
+        let obj = "
+mtllib cube.mtl
+o first
+v 0 0 0
+v 1 0 0
+v 0 1 0
+f 1 2 3
+o second
+v 0 0 1
+v 1 0 1
+v 0 1 1
+usemtl red
+f 4 5 6
+";
+        let mtl = "newmtl red\nKd 1 0 0\n";
+        let (vertices, indices) = obj_mesh(obj.as_bytes(), Some(mtl.as_bytes())).unwrap();
+        assert_eq!(vertices.len(), 6);
+        assert_eq!(indices, vec![0, 1, 2, 3, 4, 5]);
+        assert_eq!(vertices[0].color, DEFAULT_COLOR);
+        assert_eq!(vertices[3].color, [1.0, 0.0, 0.0]);
+    }
+}
@@ -1,9 +1,11 @@
-use crate::camera::Camera;
-use crate::{CameraEye, FPSEye};
+use crate::camera::{Camera, OrthographicCamera};
+use crate::{Aabb, CameraEye, FPSEye, Frustum};
 use excali_render::wgpu::util::DeviceExt;
 use excali_render::wgpu::*;
-use excali_render::{wgpu, Renderer};
+use excali_render::{aspect_ratio, wgpu, Renderer, Viewport};
+use log::warn;
 use nalgebra::{Matrix4, Point3, Vector3};
+use std::time::Instant;
 
 #[repr(C)]
 #[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
@@ -26,15 +28,117 @@ impl<T: CameraEye> From<&Camera<T>> for CameraUniform {
     }
 }
 
+/// A directional light, shading with the Lambert term [lambert]
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct LightUniform {
+    /// the way the light travels, normalized
+    pub direction: [f32; 3],
+    // vec3 has an alignment of 16 bytes
+    _padding: u32,
+    pub color: [f32; 3],
+    // vec3 has an alignment of 16 bytes
+    _padding2: u32,
+}
+
+impl LightUniform {
+    pub fn new(direction: Vector3<f32>, color: [f32; 3]) -> Self {
+        Self {
+            direction: direction.normalize().into(),
+            _padding: 0,
+            color,
+            _padding2: 0,
+        }
+    }
+}
+
+impl Default for LightUniform {
+    /// straight down, so the default up facing normal is fully lit
+    fn default() -> Self {
+        Self::new(-Vector3::y(), [1.0; 3])
+    }
+}
+
+/// how lit a surface is by light travelling in the direction, mirrors shader.wgsl
+pub fn lambert(normal: &Vector3<f32>, direction: &Vector3<f32>) -> f32 {
+    normal.normalize().dot(&-direction.normalize()).max(0.0)
+}
+
+/// rows copied out of textures must be aligned to [COPY_BYTES_PER_ROW_ALIGNMENT]
+fn padded_bytes_per_row(width: u32, bytes_per_pixel: u32) -> u32 {
+    let alignment = COPY_BYTES_PER_ROW_ALIGNMENT;
+    (width * bytes_per_pixel).div_ceil(alignment) * alignment
+}
+
+impl From<&OrthographicCamera> for CameraUniform {
+    fn from(value: &OrthographicCamera) -> Self {
+        CameraUniform {
+            matrix: value.projection_matrix().into(),
+        }
+    }
+}
+
+/// How the depth buffer is stored, cleared and compared
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct DepthConfig {
+    pub format: wgpu::TextureFormat,
+    /// a fragment is drawn when its depth compares true against the stored depth
+    pub compare: wgpu::CompareFunction,
+    /// the depth before anything is drawn
+    pub clear: f32,
+}
+
+impl Default for DepthConfig {
+    fn default() -> Self {
+        Self {
+            format: wgpu::TextureFormat::Depth32Float,
+            compare: wgpu::CompareFunction::Less,
+            clear: 1.0,
+        }
+    }
+}
+
+impl DepthConfig {
+    /// keeps more precision far away, the projection must map near to 1 and far to 0
+    pub fn reverse_z() -> Self {
+        Self {
+            compare: wgpu::CompareFunction::Greater,
+            clear: 0.0,
+            ..Default::default()
+        }
+    }
+
+    /// checks the format holds depth and the device has its features
+    pub fn validate(&self, features: wgpu::Features) -> Result<(), String> {
+        let info = self.format.describe();
+        if info.sample_type != wgpu::TextureSampleType::Depth {
+            return Err(format!("{:?} isn't a depth format", self.format));
+        }
+        if !features.contains(info.required_features) {
+            return Err(format!(
+                "{:?} needs the features {:?}",
+                self.format, info.required_features
+            ));
+        }
+        Ok(())
+    }
+}
+
 struct DepthTexture {
+    texture: wgpu::Texture,
     view: wgpu::TextureView,
     size: [u32; 2],
+    format: wgpu::TextureFormat,
 }
 
 impl DepthTexture {
-    pub const DEPTH_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Depth32Float;
-
-    fn new(device: &wgpu::Device, config: &wgpu::SurfaceConfiguration, label: &str) -> Self {
+    fn new(
+        device: &wgpu::Device,
+        config: &wgpu::SurfaceConfiguration,
+        format: wgpu::TextureFormat,
+        sample_count: u32,
+        label: &str,
+    ) -> Self {
         let size = wgpu::Extent3d {
             width: config.width,
             height: config.height,
@@ -42,86 +146,359 @@ impl DepthTexture {
         };
         let desc = wgpu::TextureDescriptor {
             label: Some(label),
-            view_formats: &[Self::DEPTH_FORMAT],
+            view_formats: &[format],
             size,
             mip_level_count: 1,
-            sample_count: 1,
+            sample_count,
             dimension: wgpu::TextureDimension::D2,
-            format: Self::DEPTH_FORMAT,
-            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+            format,
+            // only some depth formats can be copied out for reading back, multisampled ones
+            // can't be at all and GL doesn't draw to them as textures
+            usage: match sample_count {
+                1 => {
+                    (wgpu::TextureUsages::RENDER_ATTACHMENT
+                        | wgpu::TextureUsages::TEXTURE_BINDING
+                        | wgpu::TextureUsages::COPY_SRC)
+                        & format.describe().guaranteed_format_features.allowed_usages
+                }
+                _ => wgpu::TextureUsages::RENDER_ATTACHMENT,
+            },
         };
         let texture = device.create_texture(&desc);
 
         let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
         Self {
+            texture,
             view,
             size: [config.width, config.height],
+            format,
+        }
+    }
+}
+
+/// the color target drawn to before it's resolved to the view, none with a single sample
+fn multisampled_view(
+    device: &wgpu::Device,
+    config: &wgpu::SurfaceConfiguration,
+    sample_count: u32,
+) -> Option<wgpu::TextureView> {
+    if sample_count == 1 {
+        return None;
+    }
+    let texture = device.create_texture(&wgpu::TextureDescriptor {
+        label: Some("3D Multisampled Texture"),
+        size: wgpu::Extent3d {
+            width: config.width,
+            height: config.height,
+            depth_or_array_layers: 1,
+        },
+        mip_level_count: 1,
+        sample_count,
+        dimension: wgpu::TextureDimension::D2,
+        format: config.format,
+        usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+        view_formats: &[],
+    });
+    Some(texture.create_view(&wgpu::TextureViewDescriptor::default()))
+}
+
+/// the requested count if the adapter can multisample every format that many times, otherwise 1
+fn supported_sample_count(
+    adapter: &wgpu::Adapter,
+    requested: u32,
+    formats: &[wgpu::TextureFormat],
+) -> u32 {
+    let supported = formats.iter().all(|format| {
+        adapter
+            .get_texture_format_features(*format)
+            .flags
+            .sample_count_supported(requested)
+    });
+    if supported {
+        return requested;
+    }
+    warn!("{requested} samples aren't supported by {formats:?}, falling back to 1");
+    1
+}
+
+/// what drawing needs from the [Renderer], so it can be done without a window
+struct Gpu<'a> {
+    device: &'a Device,
+    queue: &'a Queue,
+    config: &'a SurfaceConfiguration,
+    /// when the frame being drawn started
+    frame: Instant,
+}
+
+impl<'a> From<&'a Renderer> for Gpu<'a> {
+    fn from(renderer: &'a Renderer) -> Self {
+        Self {
+            device: &renderer.device,
+            queue: &renderer.queue,
+            config: &renderer.config,
+            frame: renderer.clock.last_frame,
         }
     }
 }
 
 pub struct Renderer3D {
     camera_buffer: wgpu::Buffer,
+    light_buffer: wgpu::Buffer,
     pub camera_bind_group: wgpu::BindGroup,
     pub camera_bind_group_layout: wgpu::BindGroupLayout,
+    pub texture_bind_group_layout: wgpu::BindGroupLayout,
     render_pipeline: wgpu::RenderPipeline,
+    textured_render_pipeline: wgpu::RenderPipeline,
     pub targets: [Option<wgpu::ColorTargetState>; 1],
     instance_buffer: wgpu::Buffer,
     instances: usize,
     debug_render_pipeline: wgpu::RenderPipeline,
     depth_texture: DepthTexture,
+    depth: DepthConfig,
+    pub multisample: MultisampleState,
+    multisampled_view: Option<wgpu::TextureView>,
+    /// the frame the multisampled view was last cleared in, later draws that frame load it so
+    /// resolving keeps what they drew, like another viewport
+    multisampled_frame: Option<Instant>,
+    /// what's drawn behind the models when multisampling, since resolving replaces everything
+    /// already drawn to the view
+    pub clear_color: wgpu::Color,
+    /// the camera's matrix from the last update, for culling
+    projection: Matrix4<f32>,
 }
 
 impl Renderer3D {
-    pub fn update_camera<T: CameraEye>(&self, camera: &Camera<T>, renderer: &Renderer) {
-        renderer.queue.write_buffer(
+    pub fn update_camera<T: CameraEye>(&mut self, camera: &Camera<T>, renderer: &Renderer) {
+        self.write_projection(&renderer.queue, camera.projection_matrix());
+    }
+
+    fn write_projection(&mut self, queue: &Queue, projection: Matrix4<f32>) {
+        self.projection = projection;
+        queue.write_buffer(
             &self.camera_buffer,
             0,
-            bytemuck::cast_slice(camera.projection_matrix().as_slice()),
+            bytemuck::cast_slice(self.projection.as_slice()),
+        );
+    }
+
+    /// lights everything drawn from the direction the light travels in
+    pub fn set_light(&self, renderer: &Renderer, direction: Vector3<f32>, color: [f32; 3]) {
+        renderer.queue.write_buffer(
+            &self.light_buffer,
+            0,
+            bytemuck::cast_slice(&[LightUniform::new(direction, color)]),
+        );
+    }
+
+    /// reads the depth under the pixel from the last non debug draw, blocking until the GPU is
+    /// done, the depth can be turned into a world position with [Camera::unproject],
+    /// only a Depth32Float depth buffer that isn't multisampled can be read, on adapters which
+    /// can copy depth textures unlike WebGL
+    pub fn read_depth(&self, renderer: &Renderer, mouse_px: [u32; 2]) -> Option<f32> {
+        if !renderer
+            .downlevel_flags()
+            .contains(DownlevelFlags::DEPTH_TEXTURE_AND_BUFFER_COPIES)
+        {
+            return None;
+        }
+        self.read_depth_with(&renderer.into(), mouse_px)
+    }
+
+    fn read_depth_with(&self, gpu: &Gpu, mouse_px: [u32; 2]) -> Option<f32> {
+        // multisampled textures can't be copied
+        if self.multisample.count > 1
+            || mouse_px[0] >= self.depth_texture.size[0]
+            || mouse_px[1] >= self.depth_texture.size[1]
+            || self.depth_texture.format != wgpu::TextureFormat::Depth32Float
+        {
+            return None;
+        }
+        const DEPTH_SIZE: u32 = std::mem::size_of::<f32>() as u32;
+        let [width, height] = self.depth_texture.size;
+        let bytes_per_row = padded_bytes_per_row(width, DEPTH_SIZE);
+        let buffer = gpu.device.create_buffer(&BufferDescriptor {
+            label: Some("Depth Readback Buffer"),
+            size: (bytes_per_row * height) as BufferAddress,
+            usage: BufferUsages::COPY_DST | BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        });
+
+        let mut encoder = gpu
+            .device
+            .create_command_encoder(&CommandEncoderDescriptor {
+                label: Some("Depth Readback Encoder"),
+            });
+        // depth textures can only be copied whole
+        encoder.copy_texture_to_buffer(
+            ImageCopyTexture {
+                texture: &self.depth_texture.texture,
+                mip_level: 0,
+                origin: Origin3d::ZERO,
+                aspect: TextureAspect::DepthOnly,
+            },
+            ImageCopyBuffer {
+                buffer: &buffer,
+                layout: ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: std::num::NonZeroU32::new(bytes_per_row),
+                    rows_per_image: None,
+                },
+            },
+            Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
         );
+        gpu.queue.submit(Some(encoder.finish()));
+
+        let slice = buffer.slice(..);
+        let (tx, rx) = std::sync::mpsc::channel();
+        slice.map_async(MapMode::Read, move |result| {
+            tx.send(result).ok();
+        });
+        gpu.device.poll(Maintain::Wait);
+        rx.recv().ok()?.ok()?;
+
+        let offset = (mouse_px[1] * bytes_per_row + mouse_px[0] * DEPTH_SIZE) as usize;
+        let depth = bytemuck::pod_read_unaligned::<f32>(
+            &slice.get_mapped_range()[offset..offset + DEPTH_SIZE as usize],
+        );
+        buffer.unmap();
+        Some(depth)
+    }
+
+    /// binds a texture, like one from [Renderer::load_texture], for [Model::textured]
+    pub fn create_texture_bind_group(
+        &self,
+        device: &Device,
+        sampler: &Sampler,
+        texture: &excali_render::Texture,
+    ) -> BindGroup {
+        device.create_bind_group(&BindGroupDescriptor {
+            layout: &self.texture_bind_group_layout,
+            entries: &[
+                BindGroupEntry {
+                    binding: 0,
+                    resource: BindingResource::TextureView(&texture.view),
+                },
+                BindGroupEntry {
+                    binding: 1,
+                    resource: BindingResource::Sampler(sampler),
+                },
+            ],
+            label: Some(&format!("{}_bind_group", texture.name)),
+        })
     }
 
+    /// draws a wireframe through an orthographic camera, replacing the camera so
+    /// [Renderer3D::update_camera] must be called before the next perspective draw
+    pub fn draw_blueprint(
+        &mut self,
+        renderer: &Renderer,
+        view: &TextureView,
+        batches: &[ModelBatch],
+        camera: &OrthographicCamera,
+        viewport: Viewport,
+    ) -> (CommandBuffer, usize) {
+        self.draw_blueprint_with(&renderer.into(), view, batches, camera, viewport)
+    }
+
+    fn draw_blueprint_with(
+        &mut self,
+        gpu: &Gpu,
+        view: &TextureView,
+        batches: &[ModelBatch],
+        camera: &OrthographicCamera,
+        viewport: Viewport,
+    ) -> (CommandBuffer, usize) {
+        self.write_projection(gpu.queue, camera.projection_matrix());
+        self.draw_with(gpu, view, batches, true, viewport)
+    }
+
+    /// skips instances outside the camera's view, returns how many draws were issued
     pub fn draw(
         &mut self,
         renderer: &Renderer,
         view: &TextureView,
         batches: &[ModelBatch],
         debug: bool,
-    ) -> CommandBuffer {
-        let mut encoder = renderer
+        viewport: Viewport,
+    ) -> (CommandBuffer, usize) {
+        self.draw_with(&renderer.into(), view, batches, debug, viewport)
+    }
+
+    fn draw_with(
+        &mut self,
+        gpu: &Gpu,
+        view: &TextureView,
+        batches: &[ModelBatch],
+        debug: bool,
+        viewport: Viewport,
+    ) -> (CommandBuffer, usize) {
+        let mut encoder = gpu
             .device
             .create_command_encoder(&CommandEncoderDescriptor {
                 label: Some("3D Command Encoder"),
             });
-        if [renderer.config.width, renderer.config.height] != self.depth_texture.size {
-            self.depth_texture =
-                DepthTexture::new(&renderer.device, &renderer.config, "3D Depth Texture");
+        if [gpu.config.width, gpu.config.height] != self.depth_texture.size {
+            self.depth_texture = DepthTexture::new(
+                gpu.device,
+                gpu.config,
+                self.depth.format,
+                self.multisample.count,
+                "3D Depth Texture",
+            );
+            self.multisampled_view =
+                multisampled_view(gpu.device, gpu.config, self.multisample.count);
+            self.multisampled_frame = None;
         }
+        let multisampled_load = match self.multisampled_frame.replace(gpu.frame) {
+            Some(frame) if frame == gpu.frame => LoadOp::Load,
+            _ => LoadOp::Clear(self.clear_color),
+        };
 
+        let frustum = Frustum::new(&self.projection);
         let mut instances = Vec::<InstanceRaw>::new();
+        let mut draws = Vec::new();
         for batch in batches {
-            for matrix in batch.matrices.iter() {
-                instances.push((*matrix).into());
+            let start = instances.len() as u32;
+            instances.extend(
+                visible_matrices(&frustum, &batch.model.bounds, &batch.matrices)
+                    .map(InstanceRaw::from),
+            );
+            let end = instances.len() as u32;
+            if end > start {
+                draws.push((batch.model, start..end));
             }
         }
 
         if instances.len() > self.instances {
             self.instances = instances.len();
-            self.instance_buffer = create_instance_buffer(&renderer.device, instances);
+            self.instance_buffer = create_instance_buffer(gpu.device, instances);
         } else {
-            renderer
-                .queue
+            gpu.queue
                 .write_buffer(&self.instance_buffer, 0, bytemuck::cast_slice(&instances));
         }
 
         let mut render_pass = encoder.begin_render_pass(&RenderPassDescriptor {
             label: Some("Map Render Pass"),
-            color_attachments: &[Some(RenderPassColorAttachment {
-                view,
-                resolve_target: None,
-                ops: Operations {
-                    load: LoadOp::Load,
-                    store: true,
+            color_attachments: &[Some(match &self.multisampled_view {
+                Some(multisampled_view) => RenderPassColorAttachment {
+                    view: multisampled_view,
+                    resolve_target: Some(view),
+                    ops: Operations {
+                        load: multisampled_load,
+                        store: true,
+                    },
+                },
+                None => RenderPassColorAttachment {
+                    view,
+                    resolve_target: None,
+                    ops: Operations {
+                        load: LoadOp::Load,
+                        store: true,
+                    },
                 },
             })],
             depth_stencil_attachment: if debug {
@@ -130,7 +507,7 @@ impl Renderer3D {
                 Some(wgpu::RenderPassDepthStencilAttachment {
                     view: &self.depth_texture.view,
                     depth_ops: Some(wgpu::Operations {
-                        load: wgpu::LoadOp::Clear(1.0),
+                        load: wgpu::LoadOp::Clear(self.depth.clear),
                         store: true,
                     }),
                     stencil_ops: None,
@@ -138,35 +515,46 @@ impl Renderer3D {
             },
         });
 
-        render_pass.set_pipeline(match debug {
-            false => &self.render_pipeline,
-            true => &self.debug_render_pipeline,
-        });
+        viewport.apply(&mut render_pass);
         render_pass.set_bind_group(0, &self.camera_bind_group, &[]);
         render_pass.set_vertex_buffer(1, self.instance_buffer.slice(..));
 
-        let mut instance_index = 0u32;
-        for batch in batches {
-            let end_instances = batch.matrices.len() as u32 + instance_index;
-            if end_instances == instance_index {
-                continue;
+        for (model, instances) in draws.iter() {
+            match (debug, &model.texture) {
+                (true, _) => render_pass.set_pipeline(&self.debug_render_pipeline),
+                (false, None) => render_pass.set_pipeline(&self.render_pipeline),
+                (false, Some(texture)) => {
+                    render_pass.set_pipeline(&self.textured_render_pipeline);
+                    render_pass.set_bind_group(1, texture, &[]);
+                }
             }
-
-            render_pass.set_vertex_buffer(0, batch.model.vertex_buffer.slice(..));
-            render_pass.set_index_buffer(
-                batch.model.index_buffer.slice(..),
-                wgpu::IndexFormat::Uint16,
-            );
-            render_pass.draw_indexed(0..batch.model.indices, 0, instance_index..end_instances);
-            instance_index = end_instances;
+            render_pass.set_vertex_buffer(0, model.vertex_buffer.slice(..));
+            render_pass.set_index_buffer(model.index_buffer.slice(..), wgpu::IndexFormat::Uint16);
+            render_pass.draw_indexed(0..model.indices, 0, instances.clone());
         }
 
         drop(render_pass);
-        encoder.finish()
+        (encoder.finish(), draws.len())
     }
 
-    /// instances must be greater than 0
-    pub fn new(config: &SurfaceConfiguration, device: &Device, instances: usize) -> Self {
+    /// instances must be greater than 0, panics if the depth config isn't supported by the device.
+    /// Edges are smoothed with sample_count samples per pixel, falling back to 1 if the adapter
+    /// can't multisample the surface or depth format that many times
+    pub fn new(
+        config: &SurfaceConfiguration,
+        adapter: &Adapter,
+        device: &Device,
+        instances: usize,
+        depth: DepthConfig,
+        sample_count: u32,
+    ) -> Self {
+        if let Err(err) = depth.validate(device.features()) {
+            panic!("Unsupported depth config: {err}");
+        }
+        let multisample = MultisampleState {
+            count: supported_sample_count(adapter, sample_count, &[config.format, depth.format]),
+            ..Default::default()
+        };
         let mut instance_data = Vec::<InstanceRaw>::new();
         for _ in 0..instances {
             instance_data.push(InstanceRaw {
@@ -180,7 +568,7 @@ impl Renderer3D {
             position: Point3::new(2.0, 3.0, -1.0),
             eye: Default::default(),
             up: Vector3::new(0.0, 1.0, 0.0),
-            aspect: 1.0,
+            aspect: aspect_ratio(config.width as f32, config.height as f32),
             fovy: 45.0,
             znear: 0.1,
             zfar: 100.0,
@@ -192,35 +580,80 @@ impl Renderer3D {
             usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
         });
 
+        let light_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("3D Light Buffer"),
+            contents: bytemuck::cast_slice(&[LightUniform::default()]),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+
+        let uniform = |binding, visibility| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility,
+            ty: wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Uniform,
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None,
+        };
         let camera_bind_group_layout =
             device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
-                entries: &[wgpu::BindGroupLayoutEntry {
-                    binding: 0,
-                    visibility: wgpu::ShaderStages::VERTEX,
-                    ty: wgpu::BindingType::Buffer {
-                        ty: wgpu::BufferBindingType::Uniform,
-                        has_dynamic_offset: false,
-                        min_binding_size: None,
-                    },
-                    count: None,
-                }],
+                entries: &[
+                    uniform(0, wgpu::ShaderStages::VERTEX),
+                    uniform(1, wgpu::ShaderStages::FRAGMENT),
+                ],
                 label: Some("map_camera_bind_group_layout"),
             });
 
         let camera_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
             layout: &camera_bind_group_layout,
-            entries: &[wgpu::BindGroupEntry {
-                binding: 0,
-                resource: camera_buffer.as_entire_binding(),
-            }],
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: camera_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: light_buffer.as_entire_binding(),
+                },
+            ],
             label: Some("web_camera_bind_group"),
         });
 
+        let texture_bind_group_layout =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                entries: &[
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 0,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Texture {
+                            multisampled: false,
+                            view_dimension: wgpu::TextureViewDimension::D2,
+                            sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                        },
+                        count: None,
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 1,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                        count: None,
+                    },
+                ],
+                label: Some("map_texture_bind_group_layout"),
+            });
+
         let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
             label: Some("3D Render Pipeline Layout"),
             bind_group_layouts: &[&camera_bind_group_layout],
             push_constant_ranges: &[],
         });
+        let textured_pipeline_layout =
+            device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some("3D Textured Render Pipeline Layout"),
+                bind_group_layouts: &[&camera_bind_group_layout, &texture_bind_group_layout],
+                push_constant_ranges: &[],
+            });
 
         let shader = device.create_shader_module(include_wgsl!("shader.wgsl"));
 
@@ -246,16 +679,16 @@ impl Renderer3D {
             }),
             write_mask: ColorWrites::ALL,
         })];
-        let fragment = Some(FragmentState {
+        let fragment = FragmentState {
             module: &shader,
             entry_point: "fs_main",
             targets: &targets,
-        });
+        };
         let debug_render_pipeline = device.create_render_pipeline(&RenderPipelineDescriptor {
             label: Some("3D Debug Render Pipeline"),
             layout,
             vertex: vertex.clone(),
-            fragment: fragment.clone(),
+            fragment: Some(fragment.clone()),
             primitive: PrimitiveState {
                 topology: PrimitiveTopology::TriangleList,
                 strip_index_format: None,
@@ -268,47 +701,74 @@ impl Renderer3D {
                 conservative: false,
             },
             depth_stencil: None,
-            multisample: MultisampleState::default(),
+            multisample,
             multiview: None,
         });
-        let render_pipeline = device.create_render_pipeline(&RenderPipelineDescriptor {
-            label: Some("3D Render Pipeline"),
-            layout,
-            vertex,
-            fragment,
-            primitive: PrimitiveState {
-                topology: PrimitiveTopology::TriangleList,
-                strip_index_format: None,
-                front_face: FrontFace::Ccw,
-                cull_mode: Some(Face::Back),
-                polygon_mode: PolygonMode::Fill,
-                // Requires Features::DEPTH_CLIP_CONTROL
-                unclipped_depth: false,
-                // Requires Features::CONSERVATIVE_RASTERIZATION
-                conservative: false,
+        let filled_pipeline = |label, layout, fragment| {
+            device.create_render_pipeline(&RenderPipelineDescriptor {
+                label: Some(label),
+                layout: Some(layout),
+                vertex: vertex.clone(),
+                fragment: Some(fragment),
+                primitive: PrimitiveState {
+                    topology: PrimitiveTopology::TriangleList,
+                    strip_index_format: None,
+                    front_face: FrontFace::Ccw,
+                    cull_mode: Some(Face::Back),
+                    polygon_mode: PolygonMode::Fill,
+                    // Requires Features::DEPTH_CLIP_CONTROL
+                    unclipped_depth: false,
+                    // Requires Features::CONSERVATIVE_RASTERIZATION
+                    conservative: false,
+                },
+                depth_stencil: Some(wgpu::DepthStencilState {
+                    format: depth.format,
+                    depth_write_enabled: true,
+                    depth_compare: depth.compare,
+                    stencil: wgpu::StencilState::default(),
+                    bias: wgpu::DepthBiasState::default(),
+                }),
+                multisample,
+                multiview: None,
+            })
+        };
+        let render_pipeline = filled_pipeline("3D Render Pipeline", &pipeline_layout, fragment);
+        let textured_render_pipeline = filled_pipeline(
+            "3D Textured Render Pipeline",
+            &textured_pipeline_layout,
+            FragmentState {
+                module: &shader,
+                entry_point: "fs_textured",
+                targets: &targets,
             },
-            depth_stencil: Some(wgpu::DepthStencilState {
-                format: DepthTexture::DEPTH_FORMAT,
-                depth_write_enabled: true,
-                depth_compare: wgpu::CompareFunction::Less,
-                stencil: wgpu::StencilState::default(),
-                bias: wgpu::DepthBiasState::default(),
-            }),
-            multisample: MultisampleState::default(),
-            multiview: None,
-        });
-        let depth_texture = DepthTexture::new(device, config, "3D Depth Texture");
+        );
+        let depth_texture = DepthTexture::new(
+            device,
+            config,
+            depth.format,
+            multisample.count,
+            "3D Depth Texture",
+        );
 
         Self {
             camera_bind_group_layout,
+            texture_bind_group_layout,
             targets,
             render_pipeline,
+            textured_render_pipeline,
             debug_render_pipeline,
             instances,
             instance_buffer,
             camera_buffer,
+            light_buffer,
             camera_bind_group,
             depth_texture,
+            depth,
+            multisample,
+            multisampled_view: multisampled_view(device, config, multisample.count),
+            multisampled_frame: None,
+            clear_color: wgpu::Color::BLACK,
+            projection: camera.projection_matrix(),
         }
     }
 }
@@ -322,17 +782,36 @@ pub struct Vertex {
     pub color: [f32; 3],
     // vec3 has an alignment of 16 bytes
     _padding2: u32,
+    pub normal: [f32; 3],
+    // vec3 has an alignment of 16 bytes
+    _padding3: u32,
+    /// where the vertex samples a textured model's texture
+    pub tex_coords: [f32; 2],
 }
 
 impl Vertex {
+    /// facing up
     pub fn new(position: [f32; 3], color: [f32; 3]) -> Self {
+        Self::new_with_normal(position, color, [0.0, 1.0, 0.0])
+    }
+
+    pub fn new_with_normal(position: [f32; 3], color: [f32; 3], normal: [f32; 3]) -> Self {
         Self {
             position,
             _padding: 0,
             color,
             _padding2: 0,
+            normal,
+            _padding3: 0,
+            tex_coords: [0.0; 2],
         }
     }
+
+    pub fn with_tex_coords(mut self, tex_coords: [f32; 2]) -> Self {
+        self.tex_coords = tex_coords;
+        self
+    }
+
     pub fn descriptor<'a>() -> VertexBufferLayout<'a> {
         VertexBufferLayout {
             array_stride: std::mem::size_of::<Vertex>() as BufferAddress,
@@ -348,6 +827,16 @@ impl Vertex {
                     shader_location: 1,
                     format: VertexFormat::Float32x3,
                 },
+                VertexAttribute {
+                    offset: (std::mem::size_of::<f32>() * 8) as BufferAddress,
+                    shader_location: 2,
+                    format: VertexFormat::Float32x3,
+                },
+                VertexAttribute {
+                    offset: (std::mem::size_of::<f32>() * 12) as BufferAddress,
+                    shader_location: 3,
+                    format: VertexFormat::Float32x2,
+                },
             ],
         }
     }
@@ -407,6 +896,18 @@ impl InstanceRaw {
     }
 }
 
+/// the matrices placing the model's bounds at least partly inside the frustum
+fn visible_matrices<'a>(
+    frustum: &'a Frustum,
+    bounds: &'a Aabb,
+    matrices: &'a [Matrix4<f32>],
+) -> impl Iterator<Item = Matrix4<f32>> + 'a {
+    matrices
+        .iter()
+        .filter(|matrix| frustum.intersects(&bounds.transformed(matrix)))
+        .copied()
+}
+
 fn create_instance_buffer(device: &Device, instances: Vec<InstanceRaw>) -> wgpu::Buffer {
     device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
         label: Some("3D Instance Buffer"),
@@ -418,6 +919,10 @@ pub struct Model {
     pub vertex_buffer: wgpu::Buffer,
     pub index_buffer: wgpu::Buffer,
     pub indices: u32,
+    /// holds every vertex, instances outside the camera's view are culled by it
+    pub bounds: Aabb,
+    /// from [Renderer3D::create_texture_bind_group], sampled at the vertices' tex_coords
+    pub texture: Option<BindGroup>,
 }
 
 impl Model {
@@ -425,13 +930,13 @@ impl Model {
         let vertex_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
             label: Some((name.clone() + " Vertex Buffer").as_str()),
             contents: bytemuck::cast_slice(vertices.as_slice()),
-            usage: wgpu::BufferUsages::VERTEX,
+            usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
         });
 
         let index_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
             label: Some((name + " Index Buffer").as_str()),
             contents: bytemuck::cast_slice(indices.as_slice()),
-            usage: wgpu::BufferUsages::INDEX,
+            usage: wgpu::BufferUsages::INDEX | wgpu::BufferUsages::COPY_DST,
         });
         let indices = indices.len() as u32;
 
@@ -439,37 +944,499 @@ impl Model {
             vertex_buffer,
             index_buffer,
             indices,
+            bounds: Aabb::from_vertices(&vertices),
+            texture: None,
         }
     }
 
-    /// creates a 3D cube model with a center origin
-    pub fn cube(device: &Device, name: String, mut size: Vector3<f32>, color: [f32; 3]) -> Self {
-        size /= 2.0;
+    /// a model drawn with its texture multiplied by the vertex colors
+    pub fn textured(
+        device: &Device,
+        vertices: Vec<Vertex>,
+        indices: Vec<u16>,
+        texture: BindGroup,
+        name: String,
+    ) -> Self {
+        Self {
+            texture: Some(texture),
+            ..Self::new(device, vertices, indices, name)
+        }
+    }
 
-        let vertices = vec![
-            Vertex::new([-size.x, -size.y, -size.z], color),
-            Vertex::new([size.x, -size.y, -size.z], color),
-            Vertex::new([-size.x, size.y, -size.z], color),
-            Vertex::new([-size.x, -size.y, size.z], color),
-            Vertex::new([size.x, size.y, -size.z], color),
-            Vertex::new([-size.x, size.y, size.z], color),
-            Vertex::new([size.x, -size.y, size.z], color),
-            Vertex::new([size.x, size.y, size.z], color),
-        ];
+    /// overwrites part of the model in place, the buffers don't grow so the ranges must fit
+    /// and the indices' byte offset and length must be multiples of 4. The bounds only grow
+    pub fn write(
+        &mut self,
+        queue: &Queue,
+        first_vertex: usize,
+        vertices: &[Vertex],
+        first_index: usize,
+        indices: &[u16],
+    ) {
+        if !vertices.is_empty() {
+            self.bounds.merge(&Aabb::from_vertices(vertices));
+        }
+        queue.write_buffer(
+            &self.vertex_buffer,
+            (first_vertex * std::mem::size_of::<Vertex>()) as BufferAddress,
+            bytemuck::cast_slice(vertices),
+        );
+        queue.write_buffer(
+            &self.index_buffer,
+            (first_index * std::mem::size_of::<u16>()) as BufferAddress,
+            bytemuck::cast_slice(indices),
+        );
+    }
 
-        let indices = vec![
-            0, 2, 1, 1, 2, 4, // Back
-            3, 6, 5, 6, 7, 5, // Front
-            0, 1, 3, 1, 6, 3, // Bottom
-            0, 3, 5, 0, 5, 2, // Left
-            1, 4, 7, 1, 7, 6, // Right
-            5, 7, 4, 5, 4, 2, // Top
-        ];
+    /// creates a 3D cube model with a center origin
+    pub fn cube(device: &Device, name: String, size: Vector3<f32>, color: [f32; 3]) -> Self {
+        let (vertices, indices) = box_mesh(&(-size / 2.0), &(size / 2.0), color);
         Self::new(device, vertices, indices, name)
     }
+
+    /// a box from min to max, drawn as a wireframe by [Renderer3D::draw_blueprint] like for
+    /// debugging bounds
+    pub fn wire_box(
+        device: &Device,
+        min: &Vector3<f32>,
+        max: &Vector3<f32>,
+        color: [f32; 3],
+        name: String,
+    ) -> Self {
+        let (vertices, indices) = box_mesh(min, max, color);
+        Self::new(device, vertices, indices, name)
+    }
+
+    /// see [Model::wire_box], like for a collider's bounds
+    #[cfg(feature = "parry3d")]
+    pub fn from_aabb(
+        device: &Device,
+        aabb: &parry3d::bounding_volume::Aabb,
+        color: [f32; 3],
+        name: String,
+    ) -> Self {
+        Self::wire_box(device, &aabb.mins.coords, &aabb.maxs.coords, color, name)
+    }
+}
+
+/// the corners of each box face's triangles, see [box_mesh] for the corners
+const FACES_VERTEX_IDS: [u16; 36] = [
+    0, 2, 1, 1, 2, 4, // Back
+    3, 6, 5, 6, 7, 5, // Front
+    0, 1, 3, 1, 6, 3, // Bottom
+    0, 3, 5, 0, 5, 2, // Left
+    1, 4, 7, 1, 7, 6, // Right
+    5, 7, 4, 5, 4, 2, // Top
+];
+
+fn box_mesh(min: &Vector3<f32>, max: &Vector3<f32>, color: [f32; 3]) -> (Vec<Vertex>, Vec<u16>) {
+    let vertices = [
+        [min.x, min.y, min.z],
+        [max.x, min.y, min.z],
+        [min.x, max.y, min.z],
+        [min.x, min.y, max.z],
+        [max.x, max.y, min.z],
+        [min.x, max.y, max.z],
+        [max.x, min.y, max.z],
+        [max.x, max.y, max.z],
+    ]
+    .map(|position| Vertex::new(position, color));
+    (vertices.to_vec(), FACES_VERTEX_IDS.to_vec())
 }
 
 pub struct ModelBatch<'a> {
     pub model: &'a Model,
     pub matrices: Vec<Matrix4<f32>>,
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use excali_render::RenderTarget;
+    use nalgebra::{Vector2, Vector4};
+
+    /// a device without a window, the debug pipeline draws lines
+    fn headless_adapter() -> Option<(Adapter, Device, Queue)> {
+        excali_render::test_util::headless_adapter(
+            Features::POLYGON_MODE_LINE,
+            DownlevelFlags::empty(),
+        )
+    }
+
+    fn test_config() -> SurfaceConfiguration {
+        SurfaceConfiguration {
+            usage: TextureUsages::RENDER_ATTACHMENT,
+            format: TextureFormat::Rgba8UnormSrgb,
+            width: 16,
+            height: 16,
+            present_mode: PresentMode::Fifo,
+            alpha_mode: CompositeAlphaMode::Auto,
+            view_formats: vec![],
+        }
+    }
+
+    #[test]
+    fn box_has_a_corner_for_each_vertex() {
+        let (min, max) = (Vector3::new(-1.0, 0.0, 2.0), Vector3::new(3.0, 1.0, 5.0));
+        let (vertices, indices) = box_mesh(&min, &max, [1.0; 3]);
+        assert_eq!(vertices.len(), 8);
+        assert_eq!(indices.len(), 36);
+        let bounds = Aabb::from_vertices(&vertices);
+        assert_eq!(bounds.min.coords, min);
+        assert_eq!(bounds.max.coords, max);
+        // every corner is part of a face
+        assert!((0..8).all(|corner| indices.contains(&corner)));
+    }
+
+    #[test]
+    fn textured_model_has_bind_group() {
+        let Some((adapter, device, _queue)) = headless_adapter() else {
+            return;
+        };
+        let renderer_3d = Renderer3D::new(
+            &test_config(),
+            &adapter,
+            &device,
+            1,
+            DepthConfig::default(),
+            1,
+        );
+        let image = device.create_texture(&TextureDescriptor {
+            label: Some("test_texture"),
+            size: Extent3d {
+                width: 1,
+                height: 1,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: TextureDimension::D2,
+            format: TextureFormat::Rgba8UnormSrgb,
+            usage: TextureUsages::TEXTURE_BINDING,
+            view_formats: &[],
+        });
+        let texture = excali_render::Texture {
+            view: image.create_view(&TextureViewDescriptor::default()),
+            width: 1,
+            height: 1,
+            name: "test_texture".to_string(),
+            premultiplied: false,
+            mip_level_count: 1,
+        };
+        let sampler = device.create_sampler(&SamplerDescriptor::default());
+        let bind_group = renderer_3d.create_texture_bind_group(&device, &sampler, &texture);
+
+        let vertices = vec![
+            Vertex::new([0.0; 3], [1.0; 3]).with_tex_coords([0.0, 0.0]),
+            Vertex::new([1.0, 0.0, 0.0], [1.0; 3]).with_tex_coords([1.0, 0.0]),
+            Vertex::new([0.0, 1.0, 0.0], [1.0; 3]).with_tex_coords([0.0, 1.0]),
+        ];
+        let model = Model::textured(
+            &device,
+            vertices,
+            vec![0, 1, 2, 0],
+            bind_group,
+            "Test".into(),
+        );
+        assert!(model.texture.is_some());
+        assert_eq!(model.indices, 4);
+    }
+
+    #[test]
+    fn multisampling_is_stored() {
+        let Some((adapter, device, _queue)) = headless_adapter() else {
+            return;
+        };
+        let config = test_config();
+        let formats = [config.format, DepthConfig::default().format];
+        // no format can be multisampled 3 times
+        assert_eq!(supported_sample_count(&adapter, 3, &formats), 1);
+
+        let renderer_3d = Renderer3D::new(&config, &adapter, &device, 1, DepthConfig::default(), 4);
+        let count = supported_sample_count(&adapter, 4, &formats);
+        assert_eq!(renderer_3d.multisample.count, count);
+        assert_eq!(renderer_3d.multisampled_view.is_some(), count == 4);
+    }
+
+    #[test]
+    fn facing_the_light_is_brighter() {
+        let light = LightUniform::new(Vector3::new(0.0, -2.0, 1.0), [1.0; 3]);
+        let direction = Vector3::from(light.direction);
+        assert!((direction.magnitude() - 1.0).abs() < 1e-6);
+        let facing = lambert(&-direction, &direction);
+        let away = lambert(&direction, &direction);
+        assert!((facing - 1.0).abs() < 1e-6);
+        assert_eq!(away, 0.0);
+        assert!(lambert(&Vector3::y(), &direction) > away);
+        assert_eq!(lambert(&Vector3::y(), &-Vector3::y()), 1.0);
+    }
+
+    #[test]
+    fn model_behind_camera_is_culled() {
+        let camera = Camera::<FPSEye> {
+            position: Point3::new(0.0, 0.0, -10.0),
+            ..Default::default()
+        };
+        let frustum = Frustum::new(&camera.projection_matrix());
+        let bounds = Aabb {
+            min: Point3::new(-0.5, -0.5, -0.5),
+            max: Point3::new(0.5, 0.5, 0.5),
+        };
+        let in_front = Matrix4::identity();
+        let behind = Matrix4::new_translation(&Vector3::new(0.0, 0.0, -20.0));
+        let visible: Vec<_> = visible_matrices(&frustum, &bounds, &[behind, in_front]).collect();
+        assert_eq!(visible, vec![in_front]);
+        assert_eq!(visible_matrices(&frustum, &bounds, &[behind]).count(), 0);
+    }
+
+    #[test]
+    fn rows_are_padded() {
+        assert_eq!(padded_bytes_per_row(1, 4), 256);
+        assert_eq!(padded_bytes_per_row(64, 4), 256);
+        assert_eq!(padded_bytes_per_row(65, 4), 512);
+    }
+
+    #[test]
+    fn depth_unprojects_to_world() {
+        let Some((adapter, device, queue)) = excali_render::test_util::headless_adapter(
+            Features::POLYGON_MODE_LINE,
+            DownlevelFlags::DEPTH_TEXTURE_AND_BUFFER_COPIES,
+        ) else {
+            return;
+        };
+        let config = test_config();
+        let gpu = Gpu {
+            device: &device,
+            queue: &queue,
+            config: &config,
+            frame: Instant::now(),
+        };
+        let mut renderer_3d =
+            Renderer3D::new(&config, &adapter, &device, 1, DepthConfig::default(), 1);
+        let camera = Camera::<FPSEye> {
+            position: Point3::new(0.0, 10.0, 0.0),
+            eye: FPSEye {
+                pitch: -1.2,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        renderer_3d.write_projection(&queue, camera.projection_matrix());
+
+        // the ground at a y of 2, wound both ways so it's drawn from either side
+        let ground = Model::new(
+            &device,
+            [[-50.0, -50.0], [50.0, -50.0], [50.0, 50.0], [-50.0, 50.0]]
+                .map(|[x, z]| Vertex::new([x, 2.0, z], [1.0; 3]))
+                .to_vec(),
+            vec![0, 1, 2, 0, 2, 3, 0, 2, 1, 0, 3, 2],
+            "Ground".into(),
+        );
+        let target = RenderTarget::new(&device, 16, 16, config.format, "test_target");
+        let batch = ModelBatch {
+            model: &ground,
+            matrices: vec![Matrix4::identity()],
+        };
+        let (commands, draws) =
+            renderer_3d.draw_with(&gpu, &target.view, &[batch], false, Viewport::full(&config));
+        assert_eq!(draws, 1);
+        queue.submit(Some(commands));
+
+        let depth = renderer_3d.read_depth_with(&gpu, [8, 8]).unwrap();
+        assert!(depth > 0.0 && depth < 1.0);
+        // the pixel's center in clip space
+        let mouse_clip = Vector2::new(8.5 / 16.0 * 2.0 - 1.0, 1.0 - 8.5 / 16.0 * 2.0);
+        let world = camera.unproject(mouse_clip, depth);
+        assert!((world.y - 2.0).abs() < 0.05, "{world}");
+    }
+
+    #[test]
+    fn blueprint_projection_is_parallel() {
+        let camera = OrthographicCamera::top_down(Point3::new(5.0, 0.0, 5.0), 20.0, 1.0);
+        let matrix = Matrix4::from(CameraUniform::from(&camera).matrix);
+        let project = |point: Vector4<f32>| matrix * point;
+        let high = project(Vector4::new(7.0, 10.0, 3.0, 1.0));
+        let low = project(Vector4::new(7.0, -10.0, 3.0, 1.0));
+        // no perspective divide, so the same column lands on the same pixel at any depth
+        assert_eq!(high.w, 1.0);
+        assert_eq!(low.w, 1.0);
+        assert!((high.xy() - low.xy()).magnitude() < 1e-5);
+        // above and below the target are both inside wgpu's depth range
+        assert!((0.0..=1.0).contains(&high.z) && (0.0..=1.0).contains(&low.z));
+        assert!((project(Vector4::new(5.0, 0.0, 5.0, 1.0)).xy()).magnitude() < 1e-5);
+    }
+
+    #[test]
+    fn blueprint_draws_a_parallel_wireframe() {
+        let Some((adapter, device, queue)) = headless_adapter() else {
+            return;
+        };
+        let config = test_config();
+        let gpu = Gpu {
+            device: &device,
+            queue: &queue,
+            config: &config,
+            frame: Instant::now(),
+        };
+        let mut renderer_3d =
+            Renderer3D::new(&config, &adapter, &device, 1, DepthConfig::default(), 1);
+        // a perspective camera would draw the square raised towards it bigger
+        renderer_3d.write_projection(&queue, Matrix4::identity());
+        let camera = OrthographicCamera::top_down(Point3::origin(), 16.0, 1.0);
+        let vertices = [[-4.5, -4.5], [4.5, -4.5], [4.5, 4.5], [-4.5, 4.5]]
+            .map(|[x, z]| Vertex::new([x, 30.0, z], [1.0; 3]))
+            .to_vec();
+        let square = Model::new(&device, vertices, vec![0, 1, 2, 0, 2, 3], "Square".into());
+        let batches = [ModelBatch {
+            model: &square,
+            matrices: vec![Matrix4::identity()],
+        }];
+        let target = RenderTarget::new(&device, 16, 16, config.format, "test_target");
+        let (commands, draws) = renderer_3d.draw_blueprint_with(
+            &gpu,
+            &target.view,
+            &batches,
+            &camera,
+            Viewport::full(&config),
+        );
+        assert_eq!(draws, 1);
+        queue.submit(Some(commands));
+
+        let pixels = target.read(&device, &queue).unwrap();
+        let red = |x: f32, z: f32| {
+            let clip = camera.projection_matrix() * Vector4::new(x, 30.0, z, 1.0);
+            let column = ((clip.x + 1.0) * 8.0) as usize;
+            let row = ((1.0 - clip.y) * 8.0) as usize;
+            pixels[(row * 16 + column) * 4]
+        };
+        // the edge is where the orthographic projection puts it, the inside isn't filled
+        assert!(red(4.5, 0.0) > 200, "the edge wasn't drawn");
+        assert_eq!(red(2.5, -0.5), 0, "the square was filled");
+    }
+
+    #[test]
+    fn reverse_z_keeps_nearer_fragment() {
+        let Some((adapter, device, queue)) = headless_adapter() else {
+            return;
+        };
+        let config = test_config();
+        let gpu = Gpu {
+            device: &device,
+            queue: &queue,
+            config: &config,
+            frame: Instant::now(),
+        };
+        // the near square is drawn first, so the far one only shows if the depth test fails
+        let square = |depth: f32, color: [f32; 3]| {
+            let vertices = [[-1.0, -1.0], [1.0, -1.0], [1.0, 1.0], [-1.0, 1.0]]
+                .map(|[x, y]| Vertex::new([x, y, depth], color))
+                .to_vec();
+            Model::new(&device, vertices, vec![0, 1, 2, 0, 2, 3], "Square".into())
+        };
+        let near = square(0.8, [1.0, 0.0, 0.0]);
+        let far = square(0.2, [0.0, 0.0, 1.0]);
+        let batches = [&near, &far].map(|model| ModelBatch {
+            model,
+            matrices: vec![Matrix4::identity()],
+        });
+
+        let draw = |depth: DepthConfig| {
+            let mut renderer_3d = Renderer3D::new(&config, &adapter, &device, 2, depth, 1);
+            // positions are already in clip space, reverse z puts near at 1 and far at 0
+            renderer_3d.write_projection(&queue, Matrix4::identity());
+            let target = RenderTarget::new(&device, 16, 16, config.format, "test_target");
+            let (commands, draws) =
+                renderer_3d.draw_with(&gpu, &target.view, &batches, false, Viewport::full(&config));
+            assert_eq!(draws, 2);
+            queue.submit(Some(commands));
+            let pixels = target.read(&device, &queue).unwrap();
+            [pixels[(8 * 16 + 8) * 4], pixels[(8 * 16 + 8) * 4 + 2]]
+        };
+
+        let [red, blue] = draw(DepthConfig::reverse_z());
+        assert!(red > 200 && blue == 0, "drew {red}, 0, {blue}");
+        // the usual comparison keeps the lower depth, which is the far square here
+        let [red, blue] = draw(DepthConfig::default());
+        assert!(red == 0 && blue > 200, "drew {red}, 0, {blue}");
+    }
+
+    #[test]
+    fn multisampled_split_views_keep_both_halves() {
+        let Some((adapter, device, queue)) = headless_adapter() else {
+            return;
+        };
+        let config = test_config();
+        let mut renderer_3d =
+            Renderer3D::new(&config, &adapter, &device, 1, DepthConfig::default(), 4);
+        if renderer_3d.multisample.count == 1 {
+            eprintln!("skipping: the adapter can't multisample 4 times");
+            return;
+        }
+        renderer_3d.write_projection(&queue, Matrix4::identity());
+        let square = |color: [f32; 3]| {
+            let vertices = [[-1.0, -1.0], [1.0, -1.0], [1.0, 1.0], [-1.0, 1.0]]
+                .map(|[x, y]| Vertex::new([x, y, 0.5], color))
+                .to_vec();
+            Model::new(&device, vertices, vec![0, 1, 2, 0, 2, 3], "Square".into())
+        };
+        let (red, blue) = (square([1.0, 0.0, 0.0]), square([0.0, 0.0, 1.0]));
+        let target = RenderTarget::new(&device, 16, 16, config.format, "test_target");
+        let (left, right) = Viewport::full(&config).split_horizontal(0.5);
+
+        let mut draw = |models: &[&Model], viewport, frame| {
+            let gpu = Gpu {
+                device: &device,
+                queue: &queue,
+                config: &config,
+                frame,
+            };
+            let batches: Vec<_> = models
+                .iter()
+                .map(|model| ModelBatch {
+                    model,
+                    matrices: vec![Matrix4::identity()],
+                })
+                .collect();
+            let (commands, _) =
+                renderer_3d.draw_with(&gpu, &target.view, &batches, false, viewport);
+            queue.submit(Some(commands));
+            let pixels = target.read(&device, &queue).unwrap();
+            let pixel = |x: usize| [pixels[(8 * 16 + x) * 4], pixels[(8 * 16 + x) * 4 + 2]];
+            [pixel(4), pixel(12)]
+        };
+
+        let frame = Instant::now();
+        draw(&[&red], left, frame);
+        let [left_pixel, right_pixel] = draw(&[&blue], right, frame);
+        assert!(
+            left_pixel[0] > 200,
+            "the left half was cleared to {left_pixel:?}"
+        );
+        assert!(right_pixel[1] > 200, "drew {right_pixel:?}");
+
+        // the next frame starts from the clear color
+        let next_frame = frame + std::time::Duration::from_millis(16);
+        let pixels = draw(&[], Viewport::full(&config), next_frame);
+        assert_eq!(pixels, [[0, 0]; 2]);
+    }
+
+    #[test]
+    fn depth_formats_are_validated() {
+        let stencil = DepthConfig {
+            format: TextureFormat::Depth32FloatStencil8,
+            ..Default::default()
+        };
+        assert!(stencil.validate(Features::empty()).is_err());
+        assert!(stencil.validate(Features::DEPTH32FLOAT_STENCIL8).is_ok());
+        let color = DepthConfig {
+            format: TextureFormat::Rgba8Unorm,
+            ..Default::default()
+        };
+        assert!(color.validate(Features::all()).is_err());
+        let small = DepthConfig {
+            format: TextureFormat::Depth24Plus,
+            ..Default::default()
+        };
+        assert!(small.validate(Features::empty()).is_ok());
+    }
+}
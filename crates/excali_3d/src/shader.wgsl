@@ -10,17 +10,28 @@ struct InstanceInput {
     @location(8) model_matrix_3: vec4<f32>,
 };
 
+struct LightUniform {
+    direction: vec3<f32>,
+    color: vec3<f32>,
+};
+
 @group(0) @binding(0)
 var<uniform> camera: CameraUniform;
+@group(0) @binding(1)
+var<uniform> light: LightUniform;
 
 struct VertexInput {
     @location(0) position: vec3<f32>,
     @location(1) color: vec3<f32>,
+    @location(2) normal: vec3<f32>,
+    @location(3) tex_coords: vec2<f32>,
 }
 
 struct VertexOutput {
     @builtin(position) clip_position: vec4<f32>,
 	@location(0) color: vec3<f32>,
+	@location(1) normal: vec3<f32>,
+	@location(2) tex_coords: vec2<f32>,
 }
 
 @vertex
@@ -37,12 +48,31 @@ fn vs_main(
     var out: VertexOutput;
     out.clip_position = camera.matrix * model_matrix * vec4<f32>(model.position.xyz, 1.0);
 	out.color = model.color;
+	out.normal = (model_matrix * vec4<f32>(model.normal, 0.0)).xyz;
+	out.tex_coords = model.tex_coords;
     return out;
 }
 
 // Fragment shader
 
+fn shade(in: VertexOutput) -> vec3<f32> {
+    // lambert, see lambert in renderer.rs
+    let diffuse = max(dot(normalize(in.normal), -light.direction), 0.0);
+    return in.color * light.color * diffuse;
+}
+
 @fragment
 fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
-    return vec4<f32>(in.color, 1.0);
+    return vec4<f32>(shade(in), 1.0);
+}
+
+@group(1) @binding(0)
+var t_diffuse: texture_2d<f32>;
+@group(1) @binding(1)
+var s_diffuse: sampler;
+
+@fragment
+fn fs_textured(in: VertexOutput) -> @location(0) vec4<f32> {
+    let texel = textureSample(t_diffuse, s_diffuse, in.tex_coords);
+    return vec4<f32>(texel.rgb * shade(in), texel.a);
 }
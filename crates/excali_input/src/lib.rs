@@ -1,7 +1,17 @@
+use nalgebra::Vector2;
+use std::collections::{HashMap, HashSet};
+use std::time::{Duration, Instant};
 use winit::dpi::{PhysicalPosition, PhysicalSize};
-use winit::event::{DeviceId, ElementState, Event, MouseButton, VirtualKeyCode, WindowEvent};
+use winit::event::{
+    DeviceId, ElementState, Event, MouseButton, MouseScrollDelta, VirtualKeyCode, WindowEvent,
+};
 use winit::window::{CursorGrabMode, Window, WindowId};
 
+/// how many pixels a touchpad scrolls for a line of a mouse wheel
+const PIXELS_PER_LINE: f64 = 50.0;
+/// how far in pixels the cursor can move between the clicks of a double click
+const DOUBLE_CLICK_DISTANCE: f64 = 4.0;
+
 #[derive(Copy, Clone)]
 pub struct MousePosition(pub PhysicalPosition<f64>);
 
@@ -21,41 +31,209 @@ impl MousePosition {
     }
 }
 
+/// Action names mapped to their keys, keys serialize as their names like "W" or "Space"
+pub type Bindings = HashMap<String, VirtualKeyCode>;
+
 pub trait InputMap {
     fn actions(&mut self) -> Vec<&mut Action>;
+
+    /// the same actions as [InputMap::actions], to be read
+    fn actions_ref(&self) -> Vec<&Action>;
+
+    fn bindings(&self) -> Bindings {
+        self.actions_ref()
+            .into_iter()
+            .map(|action| (action.name.to_string(), action.key_code))
+            .collect()
+    }
+
+    /// rebinds the named actions, actions missing from the bindings keep their keys
+    fn apply_bindings(&mut self, bindings: &Bindings) {
+        for action in self.actions() {
+            if let Some(key_code) = bindings.get(action.name) {
+                action.key_code = *key_code;
+            }
+        }
+    }
 }
 
 pub struct Action {
+    /// identifies the action in saved bindings
+    pub name: &'static str,
     pub key_code: VirtualKeyCode,
     pub button: Button,
 }
 
 impl Action {
-    pub fn new(key_code: VirtualKeyCode) -> Self {
+    pub fn new(name: &'static str, key_code: VirtualKeyCode) -> Self {
         Self {
+            name,
             key_code,
             button: Default::default(),
         }
     }
 }
 
+/// A pair of actions pulling a value either way, like moving forward and backward
+pub struct Axis {
+    pub positive: Action,
+    pub negative: Action,
+    /// added to the keys' value, like from a gamepad's stick
+    pub analog: f32,
+}
+
+impl Axis {
+    pub fn new(positive: Action, negative: Action) -> Self {
+        Self {
+            positive,
+            negative,
+            analog: 0.0,
+        }
+    }
+
+    /// from -1 to 1, 0 when both or neither are pressed
+    pub fn value(&self) -> f32 {
+        let key = |action: &Action| if action.button.pressed() { 1.0 } else { 0.0 };
+        (key(&self.positive) - key(&self.negative) + self.analog).clamp(-1.0, 1.0)
+    }
+
+    pub fn actions(&mut self) -> [&mut Action; 2] {
+        [&mut self.positive, &mut self.negative]
+    }
+
+    pub fn actions_ref(&self) -> [&Action; 2] {
+        [&self.positive, &self.negative]
+    }
+}
+
+/// Two axes moving in a plane
+pub struct Axis2 {
+    pub x: Axis,
+    pub y: Axis,
+}
+
+impl Axis2 {
+    pub fn new(x: Axis, y: Axis) -> Self {
+        Self { x, y }
+    }
+
+    /// no longer than 1, so diagonals aren't faster
+    pub fn value(&self) -> Vector2<f32> {
+        let value = Vector2::new(self.x.value(), self.y.value());
+        if value.magnitude() > 1.0 {
+            value.normalize()
+        } else {
+            value
+        }
+    }
+
+    pub fn actions(&mut self) -> [&mut Action; 4] {
+        let [right, left] = self.x.actions();
+        let [up, down] = self.y.actions();
+        [right, left, up, down]
+    }
+
+    pub fn actions_ref(&self) -> [&Action; 4] {
+        let [right, left] = self.x.actions_ref();
+        let [up, down] = self.y.actions_ref();
+        [right, left, up, down]
+    }
+}
+
+/// Keys which trigger together, like Ctrl+S
+pub struct Chord {
+    pub keys: Vec<VirtualKeyCode>,
+}
+
+impl Chord {
+    pub fn new(keys: &[VirtualKeyCode]) -> Self {
+        Self {
+            keys: keys.to_vec(),
+        }
+    }
+}
+
 #[derive(Default)]
 pub struct Button {
     pub state: InputState,
     /// if last state change was triggered inside UI
     pub consumed: bool,
+    /// how long it's been pressed for, as of the end of the last frame
+    pub held_for: Duration,
+    /// held_for a frame earlier, to tell when a repeat interval passed
+    previously_held_for: Duration,
+    /// if the last press was the second of a double click
+    double_clicked: bool,
+    /// when and where the last press was which could start a double click
+    last_click: Option<(Instant, PhysicalPosition<f64>)>,
 }
 
 impl Button {
     fn update(&mut self, state: &ElementState, consumed: bool) {
         self.consumed = consumed;
         self.state.update(state);
+        if self.state == InputState::JustPressed {
+            self.held_for = Duration::ZERO;
+            self.previously_held_for = Duration::ZERO;
+        }
+    }
+
+    /// records a mouse press, a click near the last one within the threshold is a double click
+    /// which the next click can't continue
+    fn click(
+        &mut self,
+        now: Instant,
+        position: Option<PhysicalPosition<f64>>,
+        threshold: Duration,
+    ) {
+        self.double_clicked = match (self.last_click, position) {
+            (Some((time, last)), Some(position)) => {
+                now.duration_since(time) <= threshold
+                    && (position.x - last.x).hypot(position.y - last.y) <= DOUBLE_CLICK_DISTANCE
+            }
+            _ => false,
+        };
+        self.last_click = match self.double_clicked {
+            true => None,
+            false => position.map(|position| (now, position)),
+        };
+    }
+
+    /// ends the frame, the frame counts towards the hold if the button was pressed
+    fn step(&mut self, delta: Duration) {
+        self.previously_held_for = self.held_for;
+        if self.state.pressed() {
+            self.held_for += delta;
+        }
+        self.state.step();
+    }
+
+    pub fn held_duration(&self) -> Duration {
+        match self.state.pressed() {
+            true => self.held_for,
+            false => Duration::ZERO,
+        }
+    }
+
+    /// true when first pressed then once each interval it's held for, like key repeat
+    pub fn repeat(&self, interval: Duration) -> bool {
+        if self.just_pressed() {
+            return true;
+        }
+        let interval = interval.as_nanos().max(1);
+        self.pressed()
+            && self.held_for.as_nanos() / interval > self.previously_held_for.as_nanos() / interval
     }
 
     pub fn just_pressed(&self) -> bool {
         !self.consumed && self.state == InputState::JustPressed
     }
 
+    /// only mouse buttons are double clicked
+    pub fn just_double_clicked(&self) -> bool {
+        self.just_pressed() && self.double_clicked
+    }
+
     pub fn pressed(&self) -> bool {
         !self.consumed && self.state == InputState::Pressed
     }
@@ -69,20 +247,44 @@ impl Button {
     }
 }
 
-#[derive(Eq, PartialEq)]
+/// Repeats an action while a button is held, once for each new cell the cursor drags into
+#[derive(Debug)]
+pub struct Painter<T> {
+    /// the cell painted last in the current drag
+    last: Option<T>,
+}
+
+impl<T> Default for Painter<T> {
+    fn default() -> Self {
+        Self { last: None }
+    }
+}
+
+impl<T: PartialEq + Copy> Painter<T> {
+    /// returns if the cell should be painted, a cell is only painted once until the
+    /// cursor leaves it or the button is let go
+    pub fn paint(&mut self, button: &Button, cell: T) -> bool {
+        if !button.just_pressed() && !button.pressed() {
+            self.last = None;
+            return false;
+        }
+        if self.last == Some(cell) {
+            return false;
+        }
+        self.last = Some(cell);
+        true
+    }
+}
+
+#[derive(Eq, PartialEq, Default)]
 pub enum InputState {
     Pressed,
     JustPressed,
+    #[default]
     Released,
     JustReleased,
 }
 
-impl Default for InputState {
-    fn default() -> Self {
-        InputState::Released
-    }
-}
-
 impl InputState {
     fn step(&mut self) {
         match self {
@@ -107,13 +309,21 @@ impl InputState {
 pub struct Input<T: InputMap> {
     pub mouse_position: Option<MousePosition>,
     pub mouse_delta: Option<MousePosition>,
+    /// lines scrolled up this frame, touchpad pixels are converted to lines
+    pub scroll_delta: f32,
+    /// lines scrolled right this frame
+    pub horizontal_scroll_delta: f32,
     pub window_id: WindowId,
     pub left_mouse_click: Button,
     pub right_mouse_click: Button,
     pub middle_mouse_click: Button,
+    /// the longest time between the clicks of a double click
+    pub double_click_threshold: Duration,
     mouse_locked: bool,
     pub input_map: T,
     cursor_device_id: Option<DeviceId>,
+    pressed_keys: HashSet<VirtualKeyCode>,
+    just_pressed_keys: HashSet<VirtualKeyCode>,
 }
 
 impl<M: InputMap> Input<M> {
@@ -122,29 +332,52 @@ impl<M: InputMap> Input<M> {
             mouse_locked: false,
             mouse_position: None,
             mouse_delta: None,
+            scroll_delta: 0.0,
+            horizontal_scroll_delta: 0.0,
             left_mouse_click: Default::default(),
             right_mouse_click: Default::default(),
             middle_mouse_click: Default::default(),
+            double_click_threshold: Duration::from_millis(400),
             input_map,
             window_id,
             cursor_device_id: None,
+            pressed_keys: HashSet::new(),
+            just_pressed_keys: HashSet::new(),
         }
     }
 
-    pub fn clear(&mut self, window: &Window) {
-        self.left_mouse_click.state.step();
-        self.right_mouse_click.state.step();
-        self.middle_mouse_click.state.step();
-        for action in self.input_map.actions().iter_mut() {
-            action.button.state.step();
-        }
+    /// returns if the frame completed the chord, every key held with one just pressed
+    pub fn chord_just_pressed(&self, chord: &Chord) -> bool {
+        chord.keys.iter().all(|key| self.pressed_keys.contains(key))
+            && chord
+                .keys
+                .iter()
+                .any(|key| self.just_pressed_keys.contains(key))
+    }
+
+    /// ends the frame which took delta
+    pub fn clear(&mut self, window: &Window, delta: Duration) {
+        self.step(delta);
         if self.mouse_locked && self.mouse_position.is_some() {
             let size = window.inner_size();
             let position = PhysicalPosition::new(size.width as f64 / 2.0, size.height as f64 / 2.0);
             self.mouse_position = None;
             window.set_cursor_position(position).unwrap();
         }
+    }
+
+    /// moves buttons on from this frame's presses and releases and forgets the frame's motion
+    fn step(&mut self, delta: Duration) {
+        self.left_mouse_click.step(delta);
+        self.right_mouse_click.step(delta);
+        self.middle_mouse_click.step(delta);
+        for action in self.input_map.actions().iter_mut() {
+            action.button.step(delta);
+        }
+        self.just_pressed_keys.clear();
         self.mouse_delta = None;
+        self.scroll_delta = 0.0;
+        self.horizontal_scroll_delta = 0.0;
     }
 
     pub fn handle_event<T>(&mut self, event: &Event<T>, consumed: bool)
@@ -161,26 +394,44 @@ impl<M: InputMap> Input<M> {
                     button,
                     state,
                     ..
-                } => {
-                    if Some(*device_id) == self.cursor_device_id {
-                        match button {
-                            MouseButton::Left => self.left_mouse_click.update(state, consumed),
-                            MouseButton::Right => self.right_mouse_click.update(state, consumed),
-                            MouseButton::Middle => self.middle_mouse_click.update(state, consumed),
-                            _ => {}
-                        }
+                } if Some(*device_id) == self.cursor_device_id => {
+                    let mouse_button = match button {
+                        MouseButton::Left => &mut self.left_mouse_click,
+                        MouseButton::Right => &mut self.right_mouse_click,
+                        MouseButton::Middle => &mut self.middle_mouse_click,
+                        _ => return,
+                    };
+                    mouse_button.update(state, consumed);
+                    if *state == ElementState::Pressed {
+                        mouse_button.click(
+                            Instant::now(),
+                            self.mouse_position.map(|position| position.0),
+                            self.double_click_threshold,
+                        );
                     }
                 }
-                WindowEvent::CursorLeft { device_id } => {
-                    if Some(*device_id) == self.cursor_device_id {
-                        self.cursor_device_id = None;
-                        self.mouse_position = None;
-                        self.mouse_delta = None;
-                    }
+                WindowEvent::MouseWheel { delta, .. } if !consumed => {
+                    let (x, y) = match *delta {
+                        MouseScrollDelta::LineDelta(x, y) => (x, y),
+                        MouseScrollDelta::PixelDelta(position) => (
+                            (position.x / PIXELS_PER_LINE) as f32,
+                            (position.y / PIXELS_PER_LINE) as f32,
+                        ),
+                    };
+                    self.horizontal_scroll_delta += x;
+                    self.scroll_delta += y;
+                }
+                WindowEvent::CursorLeft { device_id }
+                    if Some(*device_id) == self.cursor_device_id =>
+                {
+                    self.cursor_device_id = None;
+                    self.reset_mouse();
                 }
                 WindowEvent::CursorEntered { device_id } => {
                     self.cursor_device_id = Some(*device_id);
+                    self.reset_mouse();
                 }
+                WindowEvent::Focused(_) => self.reset_mouse(),
                 WindowEvent::CursorMoved {
                     device_id,
                     position,
@@ -206,6 +457,17 @@ impl<M: InputMap> Input<M> {
         } = event
         {
             if let Some(key) = input.virtual_keycode {
+                match input.state {
+                    ElementState::Pressed => {
+                        if self.pressed_keys.insert(key) {
+                            self.just_pressed_keys.insert(key);
+                        }
+                    }
+                    ElementState::Released => {
+                        self.pressed_keys.remove(&key);
+                        self.just_pressed_keys.remove(&key);
+                    }
+                }
                 for action in self.input_map.actions().iter_mut() {
                     if key != action.key_code {
                         continue;
@@ -216,6 +478,12 @@ impl<M: InputMap> Input<M> {
         }
     }
 
+    /// forgets the mouse position so the next move is a fresh baseline instead of a jump
+    fn reset_mouse(&mut self) {
+        self.mouse_position = None;
+        self.mouse_delta = None;
+    }
+
     pub fn mouse_locked(&self) -> bool {
         self.mouse_locked
     }
@@ -232,3 +500,272 @@ impl<M: InputMap> Input<M> {
             .unwrap();
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    struct NoActions;
+
+    impl InputMap for NoActions {
+        fn actions(&mut self) -> Vec<&mut Action> {
+            vec![]
+        }
+
+        fn actions_ref(&self) -> Vec<&Action> {
+            vec![]
+        }
+    }
+
+    fn new_input() -> Input<NoActions> {
+        // SAFETY: dummy ids are only compared, never passed to the windowing system
+        Input::new(unsafe { WindowId::dummy() }, NoActions)
+    }
+
+    #[allow(deprecated)]
+    fn key_event(key: VirtualKeyCode, state: ElementState) -> Event<'static, ()> {
+        Event::DeviceEvent {
+            // SAFETY: see new_input
+            device_id: unsafe { DeviceId::dummy() },
+            event: winit::event::DeviceEvent::Key(winit::event::KeyboardInput {
+                scancode: 0,
+                state,
+                virtual_keycode: Some(key),
+                modifiers: Default::default(),
+            }),
+        }
+    }
+
+    fn window_event(event: WindowEvent<'static>) -> Event<'static, ()> {
+        Event::WindowEvent {
+            // SAFETY: see new_input
+            window_id: unsafe { WindowId::dummy() },
+            event,
+        }
+    }
+
+    #[allow(deprecated)]
+    fn cursor_moved(x: f64, y: f64) -> Event<'static, ()> {
+        window_event(WindowEvent::CursorMoved {
+            // SAFETY: see new_input
+            device_id: unsafe { DeviceId::dummy() },
+            position: PhysicalPosition::new(x, y),
+            modifiers: Default::default(),
+        })
+    }
+
+    fn axis(positive: bool, negative: bool) -> Axis {
+        let action = |pressed: bool| {
+            let mut action = Action::new("axis", VirtualKeyCode::A);
+            if pressed {
+                action.button.state = InputState::Pressed;
+            }
+            action
+        };
+        Axis::new(action(positive), action(negative))
+    }
+
+    #[test]
+    fn axis_follows_one_direction() {
+        assert_eq!(axis(true, false).value(), 1.0);
+        assert_eq!(axis(false, true).value(), -1.0);
+        assert_eq!(axis(false, false).value(), 0.0);
+        let mut analog = axis(true, false);
+        analog.analog = 0.5;
+        assert_eq!(analog.value(), 1.0);
+    }
+
+    #[test]
+    fn opposing_axis_cancels() {
+        assert_eq!(axis(true, true).value(), 0.0);
+    }
+
+    #[test]
+    fn diagonal_is_normalized() {
+        let value = Axis2::new(axis(true, false), axis(false, true)).value();
+        assert!((value.magnitude() - 1.0).abs() < 1e-6);
+        assert!((value.x + value.y).abs() < 1e-6);
+        let straight = Axis2::new(axis(true, false), axis(false, false)).value();
+        assert_eq!(straight, Vector2::new(1.0, 0.0));
+    }
+
+    #[test]
+    fn drag_paints_each_cell_once() {
+        let mut painter = Painter::default();
+        let mut button = Button {
+            state: InputState::JustPressed,
+            ..Default::default()
+        };
+        let mut painted = Vec::new();
+        for cell in [1, 1, 2, 2, 2, 3, 3] {
+            if painter.paint(&button, cell) {
+                painted.push(cell);
+            }
+            button.state.step();
+        }
+        assert_eq!(painted, vec![1, 2, 3]);
+
+        button.state = InputState::JustReleased;
+        assert!(!painter.paint(&button, 3));
+        button.state = InputState::JustPressed;
+        assert!(painter.paint(&button, 3));
+    }
+
+    #[test]
+    fn held_button_repeats_each_interval() {
+        let mut button = Button::default();
+        button.update(&ElementState::Pressed, false);
+        let interval = Duration::from_millis(100);
+        let mut repeats = Vec::new();
+        for frame in 0..12 {
+            if button.repeat(interval) {
+                repeats.push(frame);
+            }
+            button.step(Duration::from_millis(40));
+        }
+        // pressed, then at 120ms, 200ms, 320ms and 400ms held
+        assert_eq!(repeats, vec![0, 3, 5, 8, 10]);
+        assert_eq!(button.held_duration(), Duration::from_millis(480));
+
+        button.update(&ElementState::Released, false);
+        assert!(!button.repeat(interval));
+        assert_eq!(button.held_duration(), Duration::ZERO);
+        button.update(&ElementState::Pressed, false);
+        assert_eq!(button.held_duration(), Duration::ZERO);
+    }
+
+    #[allow(deprecated)]
+    fn left_click(state: ElementState) -> Event<'static, ()> {
+        window_event(WindowEvent::MouseInput {
+            // SAFETY: see new_input
+            device_id: unsafe { DeviceId::dummy() },
+            state,
+            button: MouseButton::Left,
+            modifiers: Default::default(),
+        })
+    }
+
+    #[test]
+    fn second_quick_click_is_a_double_click() {
+        // SAFETY: see new_input
+        let device_id = unsafe { DeviceId::dummy() };
+        let mut input = new_input();
+        input.handle_event(
+            &window_event(WindowEvent::CursorEntered { device_id }),
+            false,
+        );
+        input.handle_event(&cursor_moved(10.0, 10.0), false);
+        let mut double_clicks = Vec::new();
+        for _ in 0..3 {
+            input.handle_event(&left_click(ElementState::Pressed), false);
+            double_clicks.push(input.left_mouse_click.just_double_clicked());
+            input.handle_event(&left_click(ElementState::Released), false);
+            next_frame(&mut input);
+        }
+        // the third click starts a new double click
+        assert_eq!(double_clicks, vec![false, true, false]);
+
+        let mut button = Button::default();
+        let threshold = Duration::from_millis(400);
+        let now = Instant::now();
+        button.click(now, Some(PhysicalPosition::new(10.0, 10.0)), threshold);
+        button.click(
+            now + Duration::from_millis(100),
+            Some(PhysicalPosition::new(30.0, 10.0)),
+            threshold,
+        );
+        assert!(!button.double_clicked);
+        button.click(
+            now + Duration::from_millis(600),
+            Some(PhysicalPosition::new(30.0, 10.0)),
+            threshold,
+        );
+        assert!(!button.double_clicked);
+    }
+
+    #[test]
+    fn first_move_after_reentry_has_no_delta() {
+        // SAFETY: see new_input
+        let device_id = unsafe { DeviceId::dummy() };
+        let mut input = new_input();
+        input.handle_event(
+            &window_event(WindowEvent::CursorEntered { device_id }),
+            false,
+        );
+        input.handle_event(&cursor_moved(10.0, 10.0), false);
+        input.handle_event(&cursor_moved(20.0, 10.0), false);
+        assert_eq!(input.mouse_delta.unwrap().0.x, 10.0);
+
+        input.handle_event(&window_event(WindowEvent::CursorLeft { device_id }), false);
+        input.handle_event(
+            &window_event(WindowEvent::CursorEntered { device_id }),
+            false,
+        );
+        input.handle_event(&cursor_moved(500.0, 500.0), false);
+        assert!(input.mouse_delta.is_none());
+
+        input.handle_event(&window_event(WindowEvent::Focused(true)), false);
+        input.handle_event(&cursor_moved(900.0, 100.0), false);
+        assert!(input.mouse_delta.is_none());
+    }
+
+    /// steps the frame without a window
+    fn next_frame(input: &mut Input<NoActions>) {
+        input.step(Duration::from_millis(16));
+    }
+
+    #[allow(deprecated)]
+    fn scrolled(delta: MouseScrollDelta) -> Event<'static, ()> {
+        window_event(WindowEvent::MouseWheel {
+            // SAFETY: see new_input
+            device_id: unsafe { DeviceId::dummy() },
+            delta,
+            phase: winit::event::TouchPhase::Moved,
+            modifiers: Default::default(),
+        })
+    }
+
+    #[test]
+    fn scroll_resets_each_frame() {
+        let mut input = new_input();
+        input.handle_event(&scrolled(MouseScrollDelta::LineDelta(0.0, 1.0)), false);
+        assert!(input.scroll_delta > 0.0);
+        next_frame(&mut input);
+        assert_eq!(input.scroll_delta, 0.0);
+
+        let pixels = PhysicalPosition::new(0.0, PIXELS_PER_LINE * 2.0);
+        input.handle_event(&scrolled(MouseScrollDelta::PixelDelta(pixels)), false);
+        assert_eq!(input.scroll_delta, 2.0);
+        next_frame(&mut input);
+        // scrolling over the UI doesn't scroll the world
+        input.handle_event(&scrolled(MouseScrollDelta::LineDelta(0.0, 1.0)), true);
+        assert_eq!(input.scroll_delta, 0.0);
+    }
+
+    #[test]
+    fn chord_fires_once() {
+        let chord = Chord::new(&[VirtualKeyCode::LControl, VirtualKeyCode::S]);
+        let mut input = new_input();
+        input.handle_event(
+            &key_event(VirtualKeyCode::LControl, ElementState::Pressed),
+            false,
+        );
+        assert!(!input.chord_just_pressed(&chord));
+        next_frame(&mut input);
+        input.handle_event(&key_event(VirtualKeyCode::S, ElementState::Pressed), false);
+        assert!(input.chord_just_pressed(&chord));
+        next_frame(&mut input);
+        // key repeat doesn't fire it again
+        input.handle_event(&key_event(VirtualKeyCode::S, ElementState::Pressed), false);
+        assert!(!input.chord_just_pressed(&chord));
+
+        input.handle_event(&key_event(VirtualKeyCode::S, ElementState::Released), false);
+        input.handle_event(
+            &key_event(VirtualKeyCode::LControl, ElementState::Released),
+            false,
+        );
+        next_frame(&mut input);
+        input.handle_event(&key_event(VirtualKeyCode::S, ElementState::Pressed), false);
+        assert!(!input.chord_just_pressed(&chord));
+    }
+}
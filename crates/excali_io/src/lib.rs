@@ -1,7 +1,10 @@
 use std::collections::HashMap;
-use std::path::Path;
+use std::future::Future;
+use std::path::{Path, PathBuf};
 use std::str::FromStr;
 
+use directories::ProjectDirs;
+use futures::future::join_all;
 pub use serde;
 use serde::de::DeserializeOwned;
 use serde::Serialize;
@@ -9,6 +12,7 @@ pub use tokio;
 use tokio::fs::File;
 use tokio::io::AsyncReadExt;
 use tokio::io::{self, AsyncWriteExt};
+use tokio::sync::watch;
 pub use toml;
 
 pub enum OneShotStatus<T> {
@@ -53,36 +57,311 @@ pub async fn load_file(path: &str) -> io::Result<Vec<u8>> {
     Ok(bytes)
 }
 
-pub async fn load_from_toml<T: DeserializeOwned>(path: impl AsRef<Path>) -> Result<T, String> {
-    match File::open(path).await {
-        Ok(mut file) => {
-            let mut contents = String::new();
-            if let Err(err) = file.read_to_string(&mut contents).await {
-                return Err(err.to_string());
+/// loads every (name, path) entry concurrently, returning the loaded files by name and the
+/// entries which failed to load
+pub async fn load_files(
+    manifest: &[(&str, &str)],
+) -> (HashMap<String, Vec<u8>>, Vec<(String, io::Error)>) {
+    let results = join_all(
+        manifest
+            .iter()
+            .map(|(name, path)| async move { (name.to_string(), load_file(path).await) }),
+    )
+    .await;
+
+    let mut files = HashMap::new();
+    let mut failures = Vec::new();
+    for (name, result) in results {
+        match result {
+            Ok(bytes) => {
+                files.insert(name, bytes);
+            }
+            Err(err) => failures.push((name, err)),
+        }
+    }
+    (files, failures)
+}
+
+const PROGRESS_CHUNK_SIZE: usize = 64 * 1024;
+
+/// loads a file in chunks, reporting the fraction read (0.0 -> 1.0) through the receiver
+pub fn load_file_with_progress(
+    path: impl AsRef<Path>,
+) -> (
+    impl Future<Output = io::Result<Vec<u8>>>,
+    watch::Receiver<f32>,
+) {
+    let path = path.as_ref().to_path_buf();
+    let (tx, rx) = watch::channel(0.0);
+    let future = async move {
+        let mut file = File::open(path).await?;
+        let length = file.metadata().await?.len() as usize;
+
+        let mut bytes = Vec::with_capacity(length);
+        let mut chunk = vec![0u8; PROGRESS_CHUNK_SIZE];
+        loop {
+            let read = file.read(&mut chunk).await?;
+            if read == 0 {
+                break;
             }
-            match toml::from_str(contents.as_str()) {
-                Err(err) => Err(err.to_string()),
-                Ok(val) => Ok(val),
+            bytes.extend_from_slice(&chunk[..read]);
+            if length > 0 {
+                // the receiver may have been dropped, progress is optional
+                let _ = tx.send((bytes.len() as f32 / length as f32).min(1.0));
             }
         }
-        Err(err) => Err(format!("{err}")),
+        let _ = tx.send(1.0);
+        Ok(bytes)
+    };
+    (future, rx)
+}
+
+/// how long a file has to stop changing before it's reloaded, saving can be several writes
+#[cfg(feature = "watch")]
+const WATCH_DEBOUNCE: std::time::Duration = std::time::Duration::from_millis(100);
+
+/// sends the file's new bytes whenever it changes on disk until the receiver is dropped. The
+/// file's directory is watched so editors which save by renaming over the file are still seen
+#[cfg(feature = "watch")]
+pub fn watch_file(
+    path: impl Into<PathBuf>,
+    tx: tokio::sync::mpsc::Sender<Vec<u8>>,
+) -> Result<(), IoError> {
+    use notify::Watcher;
+
+    let path = path.into();
+    let directory = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent.to_path_buf(),
+        _ => PathBuf::from("."),
+    };
+    let file_name = path.file_name().map(|name| name.to_os_string());
+    let (changed_tx, mut changed_rx) = tokio::sync::mpsc::unbounded_channel();
+    let mut watcher = notify::recommended_watcher(move |event: notify::Result<notify::Event>| {
+        let Ok(event) = event else {
+            return;
+        };
+        if event.kind.is_access() {
+            return;
+        }
+        if event
+            .paths
+            .iter()
+            .any(|changed| changed.file_name() == file_name.as_deref())
+        {
+            // the task may have finished, nothing needs telling
+            let _ = changed_tx.send(());
+        }
+    })
+    .map_err(IoError::Watch)?;
+    watcher
+        .watch(&directory, notify::RecursiveMode::NonRecursive)
+        .map_err(IoError::Watch)?;
+
+    tokio::spawn(async move {
+        // dropping the watcher stops it
+        let _watcher = watcher;
+        loop {
+            tokio::select! {
+                changed = changed_rx.recv() => {
+                    if changed.is_none() {
+                        return;
+                    }
+                }
+                // stops without waiting for the file to change again
+                _ = tx.closed() => return,
+            }
+            while let Ok(Some(())) = tokio::time::timeout(WATCH_DEBOUNCE, changed_rx.recv()).await {
+            }
+            // the file is missing between being removed and renamed over
+            let Ok(bytes) = tokio::fs::read(&path).await else {
+                continue;
+            };
+            if tx.send(bytes).await.is_err() {
+                return;
+            }
+        }
+    });
+    Ok(())
+}
+
+/// the per-user directory an application should write its save files to
+pub fn data_dir(app_name: &str) -> PathBuf {
+    match ProjectDirs::from("", "", app_name) {
+        Some(dirs) => dirs.data_dir().to_path_buf(),
+        None => PathBuf::from("."),
     }
 }
 
+/// where to save a file relative to the application's data directory
+pub fn resolve_save_path(app_name: &str, relative: impl AsRef<Path>) -> PathBuf {
+    data_dir(app_name).join(relative)
+}
+
+/// prefers the user's saved copy of a file, falling back to the bundled read-only asset
+pub fn resolve_load_path(
+    app_name: &str,
+    relative: impl AsRef<Path>,
+    bundled: impl AsRef<Path>,
+) -> PathBuf {
+    let saved = resolve_save_path(app_name, relative);
+    if saved.exists() {
+        saved
+    } else {
+        bundled.as_ref().to_path_buf()
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum SafeJoinError {
+    Empty,
+    Absolute,
+    Separator,
+    Relative,
+}
+
+impl std::fmt::Display for SafeJoinError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                Self::Empty => "file name is empty",
+                Self::Absolute => "file name is an absolute path",
+                Self::Separator => "file name contains a path separator",
+                Self::Relative => "file name refers to a relative directory",
+            }
+        )
+    }
+}
+
+/// joins a user supplied file name onto base, rejecting names which could escape it
+pub fn safe_join(base: impl AsRef<Path>, name: &str) -> Result<PathBuf, SafeJoinError> {
+    if name.is_empty() {
+        return Err(SafeJoinError::Empty);
+    }
+    if Path::new(name).is_absolute() || Path::new(name).has_root() {
+        return Err(SafeJoinError::Absolute);
+    }
+    if name.contains(['/', '\\']) {
+        return Err(SafeJoinError::Separator);
+    }
+    if name == "." || name == ".." {
+        return Err(SafeJoinError::Relative);
+    }
+    Ok(base.as_ref().join(name))
+}
+
+/// Why a file couldn't be loaded or saved
+#[derive(Debug)]
+pub enum IoError {
+    Io(std::io::Error),
+    Toml(toml::de::Error),
+    TomlSer(toml::ser::Error),
+    #[cfg(feature = "json")]
+    Json(serde_json::Error),
+    /// the file couldn't be watched for changes
+    #[cfg(feature = "watch")]
+    Watch(notify::Error),
+}
+
+impl std::fmt::Display for IoError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Io(err) => write!(f, "{err}"),
+            Self::Toml(err) => write!(f, "can't parse toml: {err}"),
+            Self::TomlSer(err) => write!(f, "can't serialize toml: {err}"),
+            #[cfg(feature = "json")]
+            Self::Json(err) => write!(f, "json: {err}"),
+            #[cfg(feature = "watch")]
+            Self::Watch(err) => write!(f, "can't watch file: {err}"),
+        }
+    }
+}
+
+impl std::error::Error for IoError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Io(err) => Some(err),
+            Self::Toml(err) => Some(err),
+            Self::TomlSer(err) => Some(err),
+            #[cfg(feature = "json")]
+            Self::Json(err) => Some(err),
+            #[cfg(feature = "watch")]
+            Self::Watch(err) => Some(err),
+        }
+    }
+}
+
+impl From<std::io::Error> for IoError {
+    fn from(err: std::io::Error) -> Self {
+        Self::Io(err)
+    }
+}
+
+async fn read_string(path: impl AsRef<Path>) -> Result<String, IoError> {
+    let mut contents = String::new();
+    File::open(path)
+        .await?
+        .read_to_string(&mut contents)
+        .await?;
+    Ok(contents)
+}
+
+pub async fn load_from_toml<T: DeserializeOwned>(path: impl AsRef<Path>) -> Result<T, IoError> {
+    let contents = read_string(path).await?;
+    toml::from_str(contents.as_str()).map_err(IoError::Toml)
+}
+
 pub fn save_to_toml<T: Serialize>(
     data: &T,
-    path: String,
-) -> tokio::sync::oneshot::Receiver<Result<(), String>> {
-    let string: String = toml::to_string(data).unwrap();
+    path: impl Into<PathBuf>,
+) -> tokio::sync::oneshot::Receiver<Result<(), IoError>> {
+    write_string(toml::to_string(data).map_err(IoError::TomlSer), path.into())
+}
+
+#[cfg(feature = "json")]
+pub async fn load_from_json<T: DeserializeOwned>(path: impl AsRef<Path>) -> Result<T, IoError> {
+    let contents = read_string(path).await?;
+    serde_json::from_str(contents.as_str()).map_err(IoError::Json)
+}
+
+/// more compact than toml for large data like height maps
+#[cfg(feature = "json")]
+pub fn save_to_json<T: Serialize>(
+    data: &T,
+    path: impl Into<PathBuf>,
+) -> tokio::sync::oneshot::Receiver<Result<(), IoError>> {
+    write_string(
+        serde_json::to_string(data).map_err(IoError::Json),
+        path.into(),
+    )
+}
+
+/// writes the file in the background, creating its directory if needed, the receiver gets the
+/// serialization error straight away if there is one
+fn write_string(
+    string: Result<String, IoError>,
+    path: PathBuf,
+) -> tokio::sync::oneshot::Receiver<Result<(), IoError>> {
     let (tx, rx) = tokio::sync::oneshot::channel();
+    let string = match string {
+        Ok(string) => string,
+        Err(err) => {
+            tx.send(Err(err)).unwrap();
+            return rx;
+        }
+    };
 
     tokio::spawn(async move {
+        if let Some(parent) = path.parent() {
+            if let Err(err) = tokio::fs::create_dir_all(parent).await {
+                tx.send(Err(err.into())).unwrap();
+                return;
+            }
+        }
         tx.send(match File::create(path).await {
-            Ok(mut file) => match file.write_all(string.as_bytes()).await {
-                Ok(()) => Ok(()),
-                Err(err) => Err(format!("{err}")),
-            },
-            Err(err) => Err(format!("{err}")),
+            Ok(mut file) => file.write_all(string.as_bytes()).await.map_err(IoError::Io),
+            Err(err) => Err(err.into()),
         })
         .unwrap();
     });
@@ -118,13 +397,38 @@ pub enum FromKeyError<T: FromStr>
 where
     <T as FromStr>::Err: std::fmt::Debug,
 {
-    FromStr(<T as FromStr>::Err),
-    SliceDoesntFit,
+    /// a component of key couldn't be parsed
+    FromStr {
+        key: String,
+        err: <T as FromStr>::Err,
+    },
+    /// key has the wrong number of components
+    SliceDoesntFit { key: String, count: usize },
 }
-//TODO extend impl to SMatrix
+
+impl<T: FromStr> std::fmt::Display for FromKeyError<T>
+where
+    <T as FromStr>::Err: std::fmt::Debug,
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::FromStr { key, err } => write!(f, "can't parse key \"{key}\": {err:?}"),
+            Self::SliceDoesntFit { key, count } => {
+                write!(
+                    f,
+                    "key \"{key}\" has the wrong number of components ({count})"
+                )
+            }
+        }
+    }
+}
+/// covers vectors like Vector2 and Vector3 too, components are in column-major order
 #[cfg(feature = "nalgebra")]
-impl<T: Eq + FromStr + ToString + core::fmt::Debug + std::hash::Hash + nalgebra::Scalar>
-    SerializeKey for nalgebra::Vector2<T>
+impl<
+        T: Eq + FromStr + ToString + core::fmt::Debug + std::hash::Hash + nalgebra::Scalar,
+        const R: usize,
+        const C: usize,
+    > SerializeKey for nalgebra::SMatrix<T, R, C>
 where
     <T as FromStr>::Err: std::fmt::Debug,
 {
@@ -149,12 +453,220 @@ where
                 Ok(value) => {
                     values.push(value);
                 }
-                Err(err) => return Err(FromKeyError::FromStr(err)),
+                Err(err) => {
+                    return Err(FromKeyError::FromStr {
+                        key: key.to_string(),
+                        err,
+                    })
+                }
             }
         }
-        match <[T; 2]>::try_from(values) {
-            Ok(value) => Ok(value.into()),
-            Err(_) => Err(FromKeyError::SliceDoesntFit),
+        if values.len() != R * C {
+            return Err(FromKeyError::SliceDoesntFit {
+                key: key.to_string(),
+                count: values.len(),
+            });
         }
+        Ok(Self::from_iterator(values))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[tokio::test]
+    async fn load_file_with_progress_reaches_end() {
+        let path = std::env::temp_dir().join("excali_io_load_file_with_progress.bin");
+        let data: Vec<u8> = (0..3 * 1024 * 1024).map(|i| (i % 251) as u8).collect();
+        tokio::fs::write(&path, &data).await.unwrap();
+
+        let (future, progress) = load_file_with_progress(&path);
+        let bytes = future.await.unwrap();
+        tokio::fs::remove_file(&path).await.unwrap();
+
+        assert_eq!(*progress.borrow(), 1.0);
+        assert_eq!(bytes, data);
+    }
+
+    #[test]
+    fn save_path_is_in_data_dir() {
+        let base = directories::BaseDirs::new().unwrap();
+        let path = resolve_save_path("excali_io_test", "levels/alpha.toml");
+        assert!(path.starts_with(base.data_dir()));
+        assert!(path.ends_with("levels/alpha.toml"));
+    }
+
+    #[test]
+    fn safe_join_plain_name() {
+        assert_eq!(
+            safe_join("levels", "alpha.toml"),
+            Ok(Path::new("levels").join("alpha.toml"))
+        );
+    }
+
+    #[test]
+    fn safe_join_rejects_traversal() {
+        assert_eq!(safe_join("levels", ""), Err(SafeJoinError::Empty));
+        assert_eq!(safe_join("levels", ".."), Err(SafeJoinError::Relative));
+        assert_eq!(
+            safe_join("levels", "../secret"),
+            Err(SafeJoinError::Separator)
+        );
+        assert_eq!(
+            safe_join("levels", "..\\secret"),
+            Err(SafeJoinError::Separator)
+        );
+        assert_eq!(
+            safe_join("levels", "/etc/passwd"),
+            Err(SafeJoinError::Absolute)
+        );
+    }
+
+    #[cfg(feature = "nalgebra")]
+    #[test]
+    fn from_key_error_has_key() {
+        match nalgebra::Vector2::<i32>::from_key("1 2 3") {
+            Err(FromKeyError::SliceDoesntFit { key, count }) => {
+                assert_eq!(key, "1 2 3");
+                assert_eq!(count, 3);
+            }
+            _ => panic!("expected SliceDoesntFit"),
+        }
+        match nalgebra::Vector2::<i32>::from_key("1 b") {
+            Err(FromKeyError::FromStr { key, .. }) => assert_eq!(key, "1 b"),
+            _ => panic!("expected FromStr"),
+        }
+    }
+
+    #[cfg(feature = "watch")]
+    #[tokio::test]
+    async fn watched_file_sends_changes() {
+        let directory = std::env::temp_dir().join("excali_io_watch_file");
+        tokio::fs::create_dir_all(&directory).await.unwrap();
+        let path = directory.join("level.toml");
+        tokio::fs::write(&path, "before").await.unwrap();
+
+        let (tx, mut rx) = tokio::sync::mpsc::channel(4);
+        watch_file(&path, tx).unwrap();
+        let timeout = std::time::Duration::from_secs(5);
+        tokio::fs::write(&path, "after").await.unwrap();
+        assert_eq!(
+            tokio::time::timeout(timeout, rx.recv())
+                .await
+                .unwrap()
+                .as_deref(),
+            Some(b"after".as_slice())
+        );
+
+        // saved atomically by renaming over the file
+        let temporary = directory.join("level.toml.tmp");
+        tokio::fs::write(&temporary, "renamed").await.unwrap();
+        tokio::fs::rename(&temporary, &path).await.unwrap();
+        let renamed = tokio::time::timeout(timeout, rx.recv()).await.unwrap();
+        tokio::fs::remove_dir_all(&directory).await.unwrap();
+        assert_eq!(renamed.as_deref(), Some(b"renamed".as_slice()));
+    }
+
+    #[cfg(feature = "watch")]
+    #[tokio::test]
+    async fn watching_stops_when_receiver_drops() {
+        let path = std::env::temp_dir().join("excali_io_watch_stops.toml");
+        let (tx, rx) = tokio::sync::mpsc::channel(1);
+        let weak_tx = tx.downgrade();
+        watch_file(&path, tx).unwrap();
+        drop(rx);
+
+        // the task drops its sender once it's done
+        let stopped = async {
+            while weak_tx.upgrade().is_some() {
+                tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+            }
+        };
+        tokio::time::timeout(std::time::Duration::from_secs(5), stopped)
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn missing_file_is_not_found() {
+        let path = std::env::temp_dir().join("excali_io_missing_file.toml");
+        match load_from_toml::<HashMap<String, String>>(&path).await {
+            Err(IoError::Io(err)) => assert_eq!(err.kind(), std::io::ErrorKind::NotFound),
+            _ => panic!("expected an io error"),
+        }
+    }
+
+    #[cfg(feature = "json")]
+    #[tokio::test]
+    async fn json_round_trips() {
+        #[derive(Serialize, serde::Deserialize, PartialEq, Debug)]
+        struct Settings {
+            name: String,
+            heights: Vec<u8>,
+        }
+        let path = std::env::temp_dir().join("excali_io_json_round_trips.json");
+        let settings = Settings {
+            name: "alpha".to_string(),
+            heights: vec![1, 2, 3],
+        };
+        save_to_json(&settings, path.clone())
+            .await
+            .unwrap()
+            .unwrap();
+        let loaded: Settings = load_from_json(&path).await.unwrap();
+        tokio::fs::remove_file(&path).await.unwrap();
+        assert_eq!(loaded, settings);
+    }
+
+    #[cfg(feature = "nalgebra")]
+    #[test]
+    fn matrix_keys_round_trip() {
+        let vector = nalgebra::Vector3::new(1, -2, 3);
+        assert_eq!(vector.as_key(), "1 -2 3");
+        assert_eq!(
+            nalgebra::Vector3::from_key(&vector.as_key()).ok(),
+            Some(vector)
+        );
+
+        let matrix = nalgebra::Matrix2::new(1, 2, 3, 4);
+        assert_eq!(matrix.as_key(), "1 3 2 4");
+        assert_eq!(
+            nalgebra::Matrix2::from_key(&matrix.as_key()).ok(),
+            Some(matrix)
+        );
+        assert!(nalgebra::Vector3::<i32>::from_key("1 2").is_err());
+    }
+
+    #[tokio::test]
+    async fn load_files_concurrently() {
+        const ASSETS: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/../magibreak/assets/");
+        let orbs = format!("{ASSETS}orbs.png");
+        let sigils = format!("{ASSETS}sigils.png");
+        let cursor = format!("{ASSETS}cursor.png");
+        let missing = format!("{ASSETS}missing.png");
+
+        let (files, failures) = load_files(&[
+            ("orbs", &orbs),
+            ("sigils", &sigils),
+            ("cursor", &cursor),
+            ("missing", &missing),
+        ])
+        .await;
+
+        // width and height are stored big endian in the PNG header
+        let dimensions = |name: &str| {
+            let bytes = &files[name];
+            (
+                u32::from_be_bytes(bytes[16..20].try_into().unwrap()),
+                u32::from_be_bytes(bytes[20..24].try_into().unwrap()),
+            )
+        };
+        assert_eq!(files.len(), 3);
+        assert_eq!(dimensions("orbs"), (38, 76));
+        assert_eq!(dimensions("sigils"), (95, 19));
+        assert_eq!(dimensions("cursor"), (23, 23));
+        assert_eq!(failures.len(), 1);
+        assert_eq!(failures[0].0, "missing");
     }
 }
@@ -1,16 +1,790 @@
-use std::time::Instant;
+use std::collections::{HashMap, HashSet};
+use std::future::Future;
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
 
+use log::warn;
 pub use wgpu;
 pub use wgpu::SurfaceError;
+
+#[cfg(any(test, feature = "test-util"))]
+pub mod test_util;
+use winit::dpi::PhysicalSize;
 use winit::event::{Event, WindowEvent};
 use winit::event_loop::{ControlFlow, EventLoop};
-use winit::window::{Window, WindowBuilder};
+use winit::window::{BadIcon, Icon, Window, WindowBuilder, WindowId};
 
 pub struct Texture {
     pub view: wgpu::TextureView,
     pub width: u32,
     pub height: u32,
     pub name: String,
+    /// if the color channels were multiplied by alpha when uploaded
+    pub premultiplied: bool,
+    /// 1 unless it was loaded with [Renderer::load_texture_mipmapped]
+    pub mip_level_count: u32,
+}
+
+impl Texture {
+    /// creates a texture from raw sRGB RGBA8 pixels without a window, like
+    /// [Renderer::create_texture]
+    pub fn from_rgba(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        rgba: &[u8],
+        dimensions: (u32, u32),
+        label: String,
+    ) -> Self {
+        Self::upload(device, queue, &[(rgba, dimensions)], label)
+    }
+
+    /// creates a texture with a mip level for each of the pixels and their dimensions
+    fn upload(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        levels: &[(&[u8], (u32, u32))],
+        label: String,
+    ) -> Self {
+        let dimensions = levels[0].1;
+        let texture_size = wgpu::Extent3d {
+            width: dimensions.0,
+            height: dimensions.1,
+            depth_or_array_layers: 1,
+        };
+        let diffuse_texture = device.create_texture(&wgpu::TextureDescriptor {
+            // All textures are stored as 3D, we represent our 2D texture
+            // by setting depth to 1.
+            size: texture_size,
+            mip_level_count: levels.len() as u32,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            // Most images are stored using sRGB so we need to reflect that here.
+            format: wgpu::TextureFormat::Rgba8UnormSrgb,
+            // TEXTURE_BINDING tells wgpu that we want to use this texture in shaders
+            // COPY_DST means that we want to copy data to this texture
+            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+            label: Some(label.as_str()),
+            // This is the same as with the SurfaceConfig. It
+            // specifies what texture formats can be used to
+            // create TextureViews for this texture. The base
+            // texture format (Rgba8UnormSrgb in this case) is
+            // always supported. Note that using a different
+            // texture format is not supported on the WebGL2
+            // backend.
+            view_formats: &[],
+        });
+
+        for (mip_level, (rgba, (width, height))) in levels.iter().enumerate() {
+            queue.write_texture(
+                // Tells wgpu where to copy the pixel data
+                wgpu::ImageCopyTexture {
+                    texture: &diffuse_texture,
+                    mip_level: mip_level as u32,
+                    origin: wgpu::Origin3d::ZERO,
+                    aspect: wgpu::TextureAspect::All,
+                },
+                // The actual pixel data
+                rgba,
+                // The layout of the texture
+                wgpu::ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: std::num::NonZeroU32::new(4 * width),
+                    rows_per_image: std::num::NonZeroU32::new(*height),
+                },
+                wgpu::Extent3d {
+                    width: *width,
+                    height: *height,
+                    depth_or_array_layers: 1,
+                },
+            );
+        }
+
+        Self {
+            view: diffuse_texture.create_view(&wgpu::TextureViewDescriptor::default()),
+            width: dimensions.0,
+            height: dimensions.1,
+            name: label,
+            premultiplied: false,
+            mip_level_count: levels.len() as u32,
+        }
+    }
+}
+
+/// An image which couldn't be decoded into a texture
+#[derive(Debug)]
+pub struct TextureError {
+    pub label: String,
+    pub error: image::ImageError,
+}
+
+impl std::fmt::Display for TextureError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "can't decode texture {}: {}", self.label, self.error)
+    }
+}
+
+impl std::error::Error for TextureError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(&self.error)
+    }
+}
+
+fn decode_rgba(bytes: &[u8], label: &str) -> Result<image::RgbaImage, TextureError> {
+    match image::load_from_memory(bytes) {
+        Ok(image) => Ok(image.to_rgba8()),
+        Err(error) => Err(TextureError {
+            label: label.to_string(),
+            error,
+        }),
+    }
+}
+
+/// the levels in a full mip chain, halving the larger side until it's 1 pixel
+pub fn mip_level_count(width: u32, height: u32) -> u32 {
+    u32::BITS - width.max(height).max(1).leading_zeros()
+}
+
+/// the image followed by each smaller mip level
+fn mip_chain(image: image::RgbaImage) -> Vec<image::RgbaImage> {
+    let (width, height) = image.dimensions();
+    let mut levels = vec![image];
+    for level in 1..mip_level_count(width, height) {
+        let level = image::imageops::resize(
+            &levels[0],
+            (width >> level).max(1),
+            (height >> level).max(1),
+            image::imageops::FilterType::Triangle,
+        );
+        levels.push(level);
+    }
+    levels
+}
+
+/// width over height, a zero height (like a minimized window) gives 1.0 instead of NaN
+pub fn aspect_ratio(width: f32, height: f32) -> f32 {
+    if height <= 0.0 {
+        return 1.0;
+    }
+    width / height
+}
+
+/// multiplies each pixel's color channels by its alpha
+pub fn premultiply_alpha(rgba: &mut [u8]) {
+    for pixel in rgba.chunks_exact_mut(4) {
+        let alpha = pixel[3] as u16;
+        for channel in pixel[..3].iter_mut() {
+            *channel = ((*channel as u16 * alpha + 127) / 255) as u8;
+        }
+    }
+}
+
+/// What happened to the frame after handling an event
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FrameOutcome {
+    Rendered,
+    /// no frame was drawn, either the event wasn't a redraw or the surface timed out
+    Skipped,
+    /// the surface was lost or outdated and has been reconfigured, no frame was drawn
+    Recovered,
+}
+
+/// returns how a frame recovers from the surface error, out of memory can't be recovered from
+fn surface_error_outcome(err: wgpu::SurfaceError) -> Result<FrameOutcome, wgpu::SurfaceError> {
+    match err {
+        wgpu::SurfaceError::Lost | wgpu::SurfaceError::Outdated => Ok(FrameOutcome::Recovered),
+        wgpu::SurfaceError::Timeout => Ok(FrameOutcome::Skipped),
+        wgpu::SurfaceError::OutOfMemory => Err(err),
+    }
+}
+
+/// reconfigures a lost or outdated surface at the window's current size with `configure`,
+/// returns the frame's outcome like [surface_error_outcome]
+fn recover_surface(
+    err: wgpu::SurfaceError,
+    config: &mut wgpu::SurfaceConfiguration,
+    size: PhysicalSize<u32>,
+    configure: impl FnOnce(&wgpu::SurfaceConfiguration),
+) -> Result<FrameOutcome, wgpu::SurfaceError> {
+    let outcome = surface_error_outcome(err)?;
+    if outcome == FrameOutcome::Recovered {
+        config.width = size.width;
+        config.height = size.height;
+        configure(config);
+    }
+    Ok(outcome)
+}
+
+/// A region of the view to draw into, in pixels from the top left, it must lie inside the view
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Viewport {
+    pub x: f32,
+    pub y: f32,
+    pub width: f32,
+    pub height: f32,
+}
+
+impl Viewport {
+    /// covers the whole surface
+    pub fn full(config: &wgpu::SurfaceConfiguration) -> Self {
+        Self {
+            x: 0.0,
+            y: 0.0,
+            width: config.width as f32,
+            height: config.height as f32,
+        }
+    }
+
+    pub fn aspect_ratio(&self) -> f32 {
+        aspect_ratio(self.width, self.height)
+    }
+
+    /// splits into a left and right viewport, the left taking the fraction of the width
+    pub fn split_horizontal(&self, fraction: f32) -> (Self, Self) {
+        let left_width = (self.width * fraction).round();
+        (
+            Self {
+                width: left_width,
+                ..*self
+            },
+            Self {
+                x: self.x + left_width,
+                width: self.width - left_width,
+                ..*self
+            },
+        )
+    }
+
+    /// x, y, width and height rounded to whole pixels
+    pub fn scissor_rect(&self) -> [u32; 4] {
+        let x = self.x.round();
+        let y = self.y.round();
+        [
+            x as u32,
+            y as u32,
+            ((self.x + self.width).round() - x) as u32,
+            ((self.y + self.height).round() - y) as u32,
+        ]
+    }
+
+    /// restricts the render pass's drawing to the viewport
+    pub fn apply(&self, render_pass: &mut wgpu::RenderPass) {
+        render_pass.set_viewport(self.x, self.y, self.width, self.height, 0.0, 1.0);
+        let [x, y, width, height] = self.scissor_rect();
+        render_pass.set_scissor_rect(x, y, width, height);
+    }
+}
+
+/// RGBA8 pixels for the window's icon
+pub struct WindowIcon {
+    pub rgba: Vec<u8>,
+    pub width: u32,
+    pub height: u32,
+}
+
+pub struct RendererConfig {
+    pub title: String,
+    pub initial_size: Option<PhysicalSize<u32>>,
+    pub icon: Option<WindowIcon>,
+    pub features: wgpu::Features,
+    /// Mailbox or Immediate uncap the frame rate, falls back to Fifo when unsupported
+    pub present_mode: wgpu::PresentMode,
+    /// lets the surface's frames be copied by [Renderer::capture_frame], ignored with a warning
+    /// on backends which don't support it
+    pub capture_frames: bool,
+}
+
+impl Default for RendererConfig {
+    fn default() -> Self {
+        Self {
+            title: "excalibreak".to_string(),
+            initial_size: None,
+            icon: None,
+            features: wgpu::Features::empty(),
+            present_mode: wgpu::PresentMode::Fifo,
+            capture_frames: false,
+        }
+    }
+}
+
+/// the preferred present mode if it's supported, an uncapped mode is swapped for the other
+/// uncapped mode before falling back to Fifo which every surface supports
+fn choose_present_mode(
+    preferred: wgpu::PresentMode,
+    supported: &[wgpu::PresentMode],
+) -> wgpu::PresentMode {
+    use wgpu::PresentMode::{Fifo, Immediate, Mailbox};
+    let candidates = match preferred {
+        Mailbox => vec![Mailbox, Immediate],
+        Immediate => vec![Immediate, Mailbox],
+        mode => vec![mode],
+    };
+    let mode = candidates
+        .into_iter()
+        .find(|mode| supported.contains(mode))
+        .unwrap_or(Fifo);
+    if mode != preferred {
+        warn!("Present mode {preferred:?} isn't supported, using {mode:?}");
+    }
+    mode
+}
+
+/// if the surface can be configured with COPY_SRC, wgpu 0.15 only reports a surface's formats
+/// and modes so the backend is asked
+fn surface_can_copy(adapter: &wgpu::Adapter, surface: &mut wgpu::Surface) -> bool {
+    match adapter.get_info().backend {
+        #[cfg(any(
+            windows,
+            all(
+                unix,
+                not(target_arch = "wasm32"),
+                not(target_os = "ios"),
+                not(target_os = "macos")
+            )
+        ))]
+        wgpu::Backend::Vulkan => {
+            use wgpu_hal::{api::Vulkan, Adapter};
+            // # Safety
+            //
+            // The raw adapter and surface are only queried, never destroyed.
+            unsafe {
+                adapter.as_hal::<Vulkan, _, _>(|adapter| {
+                    surface.as_hal_mut::<Vulkan, _, _>(|surface| match (adapter, surface) {
+                        (Some(adapter), Some(surface)) => {
+                            adapter.surface_capabilities(surface).is_some_and(|caps| {
+                                caps.usage.contains(wgpu_hal::TextureUses::COPY_SRC)
+                            })
+                        }
+                        _ => false,
+                    })
+                })
+            }
+        }
+        // always copyable in wgpu-hal 0.15
+        wgpu::Backend::Dx12 => true,
+        // Metal and GL surfaces can only be drawn to
+        _ => false,
+    }
+}
+
+/// frames are only captured if the surface supports being copied from
+fn surface_usage(capture_frames: bool, can_copy: bool) -> wgpu::TextureUsages {
+    match capture_frames {
+        true if can_copy => wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+        true => {
+            warn!("The surface can't be copied from, frames won't be captured");
+            wgpu::TextureUsages::RENDER_ATTACHMENT
+        }
+        false => wgpu::TextureUsages::RENDER_ATTACHMENT,
+    }
+}
+
+impl RendererConfig {
+    /// errors if the icon's pixels don't match its dimensions
+    pub fn window_builder(&self) -> Result<WindowBuilder, BadIcon> {
+        let mut builder = WindowBuilder::new().with_title(self.title.clone());
+        if let Some(size) = self.initial_size {
+            builder = builder.with_inner_size(size);
+        }
+        if let Some(icon) = self.icon.as_ref() {
+            builder = builder.with_window_icon(Some(Icon::from_rgba(
+                icon.rgba.clone(),
+                icon.width,
+                icon.height,
+            )?));
+        }
+        Ok(builder)
+    }
+}
+
+/// Window events collected since the last rendered frame
+#[derive(Default)]
+pub struct FrameEvents {
+    pub dropped_files: Vec<PathBuf>,
+    /// the window's latest size if it was resized or moved to a display with another scale
+    /// factor, the surface has already been reconfigured to it
+    pub resized: Option<PhysicalSize<u32>>,
+}
+
+impl FrameEvents {
+    pub fn handle_event(&mut self, event: &WindowEvent) {
+        match event {
+            WindowEvent::DroppedFile(path) => self.dropped_files.push(path.clone()),
+            WindowEvent::Resized(size) => self.resized = Some(*size),
+            WindowEvent::ScaleFactorChanged { new_inner_size, .. } => {
+                self.resized = Some(**new_inner_size)
+            }
+            _ => {}
+        }
+    }
+
+    fn clear(&mut self) {
+        self.dropped_files.clear();
+        self.resized = None;
+    }
+}
+
+/// A texture to draw into instead of the window, its view can be passed to anything drawing to
+/// the surface's view as long as it was made for the target's format
+pub struct RenderTarget {
+    pub texture: wgpu::Texture,
+    pub view: wgpu::TextureView,
+    pub width: u32,
+    pub height: u32,
+    pub format: wgpu::TextureFormat,
+}
+
+impl RenderTarget {
+    pub fn new(
+        device: &wgpu::Device,
+        width: u32,
+        height: u32,
+        format: wgpu::TextureFormat,
+        label: &str,
+    ) -> Self {
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            size: wgpu::Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format,
+            // drawn into, sampled as a sprite and copied out when read back
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT
+                | wgpu::TextureUsages::TEXTURE_BINDING
+                | wgpu::TextureUsages::COPY_SRC,
+            label: Some(label),
+            view_formats: &[],
+        });
+        Self {
+            view: texture.create_view(&wgpu::TextureViewDescriptor::default()),
+            texture,
+            width,
+            height,
+            format,
+        }
+    }
+
+    /// a view of what's been drawn for binding like a loaded texture, e.g. as a sprite
+    pub fn to_texture(&self, name: String) -> Texture {
+        Texture {
+            view: self
+                .texture
+                .create_view(&wgpu::TextureViewDescriptor::default()),
+            width: self.width,
+            height: self.height,
+            name,
+            premultiplied: false,
+            mip_level_count: 1,
+        }
+    }
+
+    /// waits for the GPU and returns the texture's bytes row by row from the top
+    pub fn read(
+        &self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+    ) -> Result<Vec<u8>, wgpu::BufferAsyncError> {
+        read_texture(device, queue, &self.texture)
+    }
+}
+
+/// texture copies need each row padded to [wgpu::COPY_BYTES_PER_ROW_ALIGNMENT] bytes
+fn padded_bytes_per_row(width: u32) -> u32 {
+    let align = wgpu::COPY_BYTES_PER_ROW_ALIGNMENT;
+    (4 * width).div_ceil(align) * align
+}
+
+/// drops the padding at the end of each copied row
+fn strip_row_padding(padded: &[u8], width: u32) -> Vec<u8> {
+    let row_bytes = 4 * width as usize;
+    padded
+        .chunks(padded_bytes_per_row(width) as usize)
+        .flat_map(|row| &row[..row_bytes])
+        .copied()
+        .collect()
+}
+
+/// swaps the red and blue channels of bgra pixels so they're rgba
+fn bgra_to_rgba(bytes: &mut [u8]) {
+    for pixel in bytes.chunks_exact_mut(4) {
+        pixel.swap(0, 2);
+    }
+}
+
+/// copies a 4 byte per pixel texture into a buffer which can be mapped to read it
+fn copy_to_buffer(
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    texture: &wgpu::Texture,
+) -> wgpu::Buffer {
+    let wgpu::Extent3d { width, height, .. } = texture.size();
+    let bytes_per_row = padded_bytes_per_row(width);
+    let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some("readback_buffer"),
+        size: (bytes_per_row * height) as u64,
+        usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+        mapped_at_creation: false,
+    });
+    let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+        label: Some("readback_encoder"),
+    });
+    encoder.copy_texture_to_buffer(
+        texture.as_image_copy(),
+        wgpu::ImageCopyBuffer {
+            buffer: &buffer,
+            layout: wgpu::ImageDataLayout {
+                offset: 0,
+                bytes_per_row: std::num::NonZeroU32::new(bytes_per_row),
+                rows_per_image: std::num::NonZeroU32::new(height),
+            },
+        },
+        wgpu::Extent3d {
+            width,
+            height,
+            depth_or_array_layers: 1,
+        },
+    );
+    queue.submit(Some(encoder.finish()));
+    buffer
+}
+
+/// the mapped buffer's rows without padding, bgra textures are returned as rgba
+fn mapped_bytes(buffer: &wgpu::Buffer, width: u32, format: wgpu::TextureFormat) -> Vec<u8> {
+    let mut bytes = strip_row_padding(&buffer.slice(..).get_mapped_range(), width);
+    buffer.unmap();
+    if matches!(
+        format,
+        wgpu::TextureFormat::Bgra8Unorm | wgpu::TextureFormat::Bgra8UnormSrgb
+    ) {
+        bgra_to_rgba(&mut bytes);
+    }
+    bytes
+}
+
+/// copies a 4 byte per pixel texture into a buffer and waits for it to be mapped, bgra
+/// textures are returned as rgba
+fn read_texture(
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    texture: &wgpu::Texture,
+) -> Result<Vec<u8>, wgpu::BufferAsyncError> {
+    let buffer = copy_to_buffer(device, queue, texture);
+    let (sender, receiver) = std::sync::mpsc::channel();
+    buffer
+        .slice(..)
+        .map_async(wgpu::MapMode::Read, move |result| {
+            let _ = sender.send(result);
+        });
+    device.poll(wgpu::Maintain::Wait);
+    receiver.recv().unwrap_or(Err(wgpu::BufferAsyncError))?;
+    Ok(mapped_bytes(&buffer, texture.width(), texture.format()))
+}
+
+/// like [read_texture] without waiting, the copy is mapped the next time the device is polled
+/// like when a frame is submitted. Empty if the device is lost before then
+fn capture_texture(
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    texture: &wgpu::Texture,
+) -> impl Future<Output = Vec<u8>> {
+    let (width, format) = (texture.width(), texture.format());
+    let buffer = copy_to_buffer(device, queue, texture);
+    let (sender, receiver) = futures::channel::oneshot::channel();
+    buffer
+        .slice(..)
+        .map_async(wgpu::MapMode::Read, move |result| {
+            let _ = sender.send(result);
+        });
+    async move {
+        match receiver.await {
+            Ok(Ok(())) => mapped_bytes(&buffer, width, format),
+            _ => Vec::new(),
+        }
+    }
+}
+
+/// Times frames, stopping while the window is unfocused so resuming isn't one long frame
+pub struct FrameClock {
+    pub last_frame: Instant,
+    pub paused: bool,
+    /// the next frame is the first since resuming
+    resumed: bool,
+}
+
+impl FrameClock {
+    pub fn new(now: Instant) -> Self {
+        Self {
+            last_frame: now,
+            paused: false,
+            resumed: false,
+        }
+    }
+
+    /// pauses when focus is lost, regaining it restarts the clock from now
+    pub fn set_focused(&mut self, focused: bool, now: Instant) {
+        if focused != self.paused {
+            return;
+        }
+        self.paused = !focused;
+        if focused {
+            self.last_frame = now;
+            self.resumed = true;
+        }
+    }
+
+    /// seconds since the last frame
+    pub fn delta(&self, now: Instant) -> f64 {
+        now.duration_since(self.last_frame).as_secs_f64()
+    }
+
+    /// when the frame after the last is due, the last frame if the target isn't positive
+    pub fn next_frame(&self, fps_target: f64) -> Instant {
+        Duration::try_from_secs_f64(1.0 / fps_target)
+            .ok()
+            .and_then(|frame| self.last_frame.checked_add(frame))
+            .unwrap_or(self.last_frame)
+    }
+
+    /// when to wake for the next frame, a frame from now once it's overdue so a skipped frame
+    /// doesn't spin the event loop until one's drawn
+    pub fn wake_at(&self, fps_target: f64, now: Instant) -> Instant {
+        let next_frame = self.next_frame(fps_target);
+        if next_frame > now {
+            return next_frame;
+        }
+        Duration::try_from_secs_f64(1.0 / fps_target)
+            .ok()
+            .and_then(|frame| now.checked_add(frame))
+            .unwrap_or(now)
+    }
+
+    /// starts a frame, returns if it's the first since resuming
+    fn tick(&mut self, now: Instant) -> bool {
+        self.last_frame = now;
+        std::mem::take(&mut self.resumed)
+    }
+}
+
+/// Collects the command buffers drawn to a single view and returns them in a declared layer
+/// order. Layers draw over each other with `LoadOp::Load`, so submission order is draw order.
+pub struct FrameComposer<'a> {
+    view: &'a wgpu::TextureView,
+    order: &'a [&'static str],
+    clear: Option<wgpu::Color>,
+    layers: Vec<(&'static str, wgpu::CommandBuffer)>,
+}
+
+impl<'a> FrameComposer<'a> {
+    pub fn new(view: &'a wgpu::TextureView, order: &'a [&'static str]) -> Self {
+        Self {
+            view,
+            order,
+            clear: None,
+            layers: Vec::new(),
+        }
+    }
+
+    /// clears the view before every other layer
+    pub fn clear(mut self, color: wgpu::Color) -> Self {
+        self.clear = Some(color);
+        self
+    }
+
+    /// panics if the layer isn't declared or was drawn to another view
+    pub fn add(
+        &mut self,
+        name: &'static str,
+        view: &wgpu::TextureView,
+        command_buffer: wgpu::CommandBuffer,
+    ) {
+        assert!(
+            std::ptr::eq(view, self.view),
+            "layer {name} targets a different view"
+        );
+        assert!(
+            self.order.contains(&name),
+            "layer {name} isn't in the frame's order"
+        );
+        self.layers.push((name, command_buffer));
+    }
+
+    pub fn finish(self, renderer: &mut Renderer) -> Vec<wgpu::CommandBuffer> {
+        let mut command_buffers = Vec::new();
+        if let Some(color) = self.clear {
+            command_buffers.push(renderer.clear(self.view, color));
+        }
+        command_buffers.extend(order_layers(self.order, self.layers));
+        command_buffers
+    }
+}
+
+/// sorts layers into the declared order, layers sharing a name keep their submission order
+fn order_layers<T>(order: &[&str], mut layers: Vec<(&str, T)>) -> Vec<T> {
+    layers.sort_by_key(|(name, _)| order.iter().position(|layer| layer == name));
+    layers.into_iter().map(|(_, layer)| layer).collect()
+}
+
+/// A window besides the renderer's main one, its surface is drawn with the renderer's device
+pub struct SurfaceContext {
+    // dropped before the window it was created from
+    surface: wgpu::Surface,
+    pub config: wgpu::SurfaceConfiguration,
+    pub window: Window,
+}
+
+impl SurfaceContext {
+    pub fn resize(&mut self, device: &wgpu::Device, new_size: PhysicalSize<u32>) {
+        self.config.width = new_size.width;
+        self.config.height = new_size.height;
+        self.surface.configure(device, &self.config);
+    }
+}
+
+/// Which of a renderer's windows are focused, so focus moving between them doesn't pause it
+struct WindowFocus {
+    focused: HashSet<WindowId>,
+    /// if focus changed since it was last applied
+    changed: bool,
+}
+
+impl WindowFocus {
+    /// the main window is focused until told otherwise
+    fn new(main: WindowId) -> Self {
+        Self {
+            focused: HashSet::from([main]),
+            changed: false,
+        }
+    }
+
+    fn set(&mut self, window: WindowId, focused: bool) {
+        self.changed |= match focused {
+            true => self.focused.insert(window),
+            false => self.focused.remove(&window),
+        };
+    }
+
+    /// if any window is focused, none when nothing's changed since it was last taken
+    fn take_change(&mut self) -> Option<bool> {
+        std::mem::take(&mut self.changed).then_some(!self.focused.is_empty())
+    }
+}
+
+/// the main surface's configuration at another window's size, so pipelines made for the main
+/// surface's format draw to it too
+fn window_config(
+    main: &wgpu::SurfaceConfiguration,
+    size: PhysicalSize<u32>,
+    present_modes: &[wgpu::PresentMode],
+) -> wgpu::SurfaceConfiguration {
+    wgpu::SurfaceConfiguration {
+        width: size.width,
+        height: size.height,
+        present_mode: choose_present_mode(main.present_mode, present_modes),
+        ..main.clone()
+    }
 }
 
 pub struct Renderer {
@@ -20,12 +794,67 @@ pub struct Renderer {
     pub config: wgpu::SurfaceConfiguration,
     pub window: Window,
     pub fps_target: f64,
-    pub last_frame: Instant,
+    pub clock: FrameClock,
+    pub frame_events: FrameEvents,
+    /// windows added with [Renderer::add_window], closing one removes it
+    pub windows: HashMap<WindowId, SurfaceContext>,
+    focus: WindowFocus,
+    instance: wgpu::Instance,
+    adapter: wgpu::Adapter,
+    adapter_info: wgpu::AdapterInfo,
+    /// the surface's supported present modes
+    present_modes: Vec<wgpu::PresentMode>,
+}
+
+/// describes the adapter and what the device was created with, for bug reports
+fn adapter_report(
+    info: &wgpu::AdapterInfo,
+    features: wgpu::Features,
+    limits: &wgpu::Limits,
+) -> String {
+    format!(
+        "adapter: {} ({:?})\nbackend: {:?}\ndriver: {} {}\nfeatures: {:?}\nmax texture size: {}\nmax bind groups: {}",
+        info.name,
+        info.device_type,
+        info.backend,
+        info.driver,
+        info.driver_info,
+        features,
+        limits.max_texture_dimension_2d,
+        limits.max_bind_groups,
+    )
 }
 
 impl Renderer {
+    pub fn adapter_info(&self) -> &wgpu::AdapterInfo {
+        &self.adapter_info
+    }
+
+    pub fn adapter(&self) -> &wgpu::Adapter {
+        &self.adapter
+    }
+
+    /// what the adapter can do below full WebGPU support, like copying depth textures
+    pub fn downlevel_flags(&self) -> wgpu::DownlevelFlags {
+        self.adapter.get_downlevel_capabilities().flags
+    }
+
+    /// the adapter, backend, features and limits in use
+    pub fn report(&self) -> String {
+        adapter_report(
+            &self.adapter_info,
+            self.device.features(),
+            &self.device.limits(),
+        )
+    }
+
     pub fn aspect_ratio(&self) -> f32 {
-        self.config.width as f32 / self.config.height as f32
+        aspect_ratio(self.config.width as f32, self.config.height as f32)
+    }
+
+    /// the whole surface
+    pub fn viewport(&self) -> Viewport {
+        Viewport::full(&self.config)
     }
 
     pub fn pixel_art_sampler(&mut self) -> wgpu::Sampler {
@@ -52,66 +881,85 @@ impl Renderer {
         })
     }
 
-    pub fn load_texture(&self, bytes: &[u8], label: String) -> Texture {
-        let diffuse_image = image::load_from_memory(bytes).unwrap();
-        let diffuse_rgba = diffuse_image.to_rgba8();
+    /// the texture's rgba bytes row by row from the top, the surface's current texture can be
+    /// captured to screenshot what the player sees with [RendererConfig::capture_frames].
+    /// Resolves once the next frame's been submitted, empty if the device is lost first
+    pub fn capture_frame(&self, texture: &wgpu::Texture) -> impl Future<Output = Vec<u8>> {
+        capture_texture(&self.device, &self.queue, texture)
+    }
 
-        use image::GenericImageView;
-        let dimensions = diffuse_image.dimensions();
+    /// a texture to draw into, see [RenderTarget]
+    pub fn create_render_target(
+        &self,
+        width: u32,
+        height: u32,
+        format: wgpu::TextureFormat,
+    ) -> RenderTarget {
+        RenderTarget::new(&self.device, width, height, format, "render_target")
+    }
 
-        let texture_size = wgpu::Extent3d {
-            width: dimensions.0,
-            height: dimensions.1,
-            depth_or_array_layers: 1,
-        };
-        let diffuse_texture = self.device.create_texture(&wgpu::TextureDescriptor {
-            // All textures are stored as 3D, we represent our 2D texture
-            // by setting depth to 1.
-            size: texture_size,
-            mip_level_count: 1, // We'll talk about this a little later
-            sample_count: 1,
-            dimension: wgpu::TextureDimension::D2,
-            // Most images are stored using sRGB so we need to reflect that here.
-            format: wgpu::TextureFormat::Rgba8UnormSrgb,
-            // TEXTURE_BINDING tells wgpu that we want to use this texture in shaders
-            // COPY_DST means that we want to copy data to this texture
-            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
-            label: Some(label.as_str()),
-            // This is the same as with the SurfaceConfig. It
-            // specifies what texture formats can be used to
-            // create TextureViews for this texture. The base
-            // texture format (Rgba8UnormSrgb in this case) is
-            // always supported. Note that using a different
-            // texture format is not supported on the WebGL2
-            // backend.
-            view_formats: &[],
-        });
+    pub fn load_texture(&self, bytes: &[u8], label: String) -> Result<Texture, TextureError> {
+        let diffuse_rgba = decode_rgba(bytes, &label)?;
+        Ok(self.create_texture(&diffuse_rgba, diffuse_rgba.dimensions(), label))
+    }
 
-        self.queue.write_texture(
-            // Tells wgpu where to copy the pixel data
-            wgpu::ImageCopyTexture {
-                texture: &diffuse_texture,
-                mip_level: 0,
-                origin: wgpu::Origin3d::ZERO,
-                aspect: wgpu::TextureAspect::All,
-            },
-            // The actual pixel data
-            &diffuse_rgba,
-            // The layout of the texture
-            wgpu::ImageDataLayout {
-                offset: 0,
-                bytes_per_row: std::num::NonZeroU32::new(4 * dimensions.0),
-                rows_per_image: std::num::NonZeroU32::new(dimensions.1),
-            },
-            texture_size,
-        );
+    /// loads a texture with premultiplied alpha, for drawing with premultiplied blending
+    pub fn load_texture_premultiplied(
+        &self,
+        bytes: &[u8],
+        label: String,
+    ) -> Result<Texture, TextureError> {
+        let mut diffuse_rgba = decode_rgba(bytes, &label)?;
+        premultiply_alpha(&mut diffuse_rgba);
 
-        Texture {
-            view: diffuse_texture.create_view(&wgpu::TextureViewDescriptor::default()),
-            width: dimensions.0,
-            height: dimensions.1,
-            name: label,
+        let mut texture = self.create_texture(&diffuse_rgba, diffuse_rgba.dimensions(), label);
+        texture.premultiplied = true;
+        Ok(texture)
+    }
+
+    /// like [Renderer::load_texture] with a full mip chain, so sprites drawn well below
+    /// their size don't shimmer when sampled with a mipmap filter
+    pub fn load_texture_mipmapped(
+        &self,
+        bytes: &[u8],
+        label: String,
+    ) -> Result<Texture, TextureError> {
+        let levels = mip_chain(decode_rgba(bytes, &label)?);
+        let levels: Vec<(&[u8], (u32, u32))> = levels
+            .iter()
+            .map(|level| (level.as_raw().as_slice(), level.dimensions()))
+            .collect();
+        Ok(Texture::upload(&self.device, &self.queue, &levels, label))
+    }
+
+    /// creates a texture from raw sRGB RGBA8 pixels
+    pub fn create_texture(&self, rgba: &[u8], dimensions: (u32, u32), label: String) -> Texture {
+        Texture::from_rgba(&self.device, &self.queue, rgba, dimensions, label)
+    }
+
+    /// uploads every loaded file as a texture labeled by its name
+    /// returns the textures which decoded and the errors of those which didn't
+    pub fn load_textures(
+        &self,
+        files: &HashMap<String, Vec<u8>>,
+    ) -> (HashMap<String, Texture>, Vec<TextureError>) {
+        let mut textures = HashMap::new();
+        let mut failures = Vec::new();
+        for (name, bytes) in files.iter() {
+            match self.load_texture(bytes, name.clone()) {
+                Ok(texture) => {
+                    textures.insert(name.clone(), texture);
+                }
+                Err(err) => failures.push(err),
+            }
         }
+        (textures, failures)
+    }
+
+    /// reconfigures the surface with the mode or the closest supported one
+    pub fn set_present_mode(&mut self, mode: wgpu::PresentMode) {
+        self.config.present_mode = choose_present_mode(mode, &self.present_modes);
+        self.surface.configure(&self.device, &self.config);
     }
 
     pub fn resize(&mut self, new_size: winit::dpi::PhysicalSize<u32>) {
@@ -120,6 +968,89 @@ impl Renderer {
         self.surface.configure(&self.device, &self.config);
     }
 
+    /// draws to the window with the main window's device, queue and surface format, it's
+    /// updated after the main window each frame
+    pub fn add_window(&mut self, window: Window) -> Result<WindowId, String> {
+        // # Safety
+        //
+        // The surface is dropped before the window by the SurfaceContext which owns both.
+        let mut surface = unsafe { self.instance.create_surface(&window) }
+            .map_err(|err| format!("can't create a surface for the window: {err}"))?;
+        let caps = surface.get_capabilities(&self.adapter);
+        if !caps.formats.contains(&self.config.format) {
+            return Err(format!(
+                "the window's surface doesn't support the main surface's format {:?}",
+                self.config.format
+            ));
+        }
+        let mut config = window_config(&self.config, window.inner_size(), &caps.present_modes);
+        config.usage = surface_usage(
+            self.config.usage.contains(wgpu::TextureUsages::COPY_SRC),
+            surface_can_copy(&self.adapter, &mut surface),
+        );
+        surface.configure(&self.device, &config);
+
+        let id = window.id();
+        self.windows.insert(
+            id,
+            SurfaceContext {
+                surface,
+                config,
+                window,
+            },
+        );
+        Ok(id)
+    }
+
+    /// the configuration of the main window's or an added window's surface
+    pub fn surface_config(&self, window: WindowId) -> Option<&wgpu::SurfaceConfiguration> {
+        if window == self.window.id() {
+            return Some(&self.config);
+        }
+        self.windows.get(&window).map(|context| &context.config)
+    }
+
+    fn set_focused(&mut self, focused: bool, control_flow: &mut ControlFlow) {
+        self.clock.set_focused(focused, Instant::now());
+        *control_flow = match focused {
+            true => ControlFlow::Poll,
+            false => ControlFlow::Wait,
+        };
+    }
+
+    fn draw_window<F>(
+        &mut self,
+        window: WindowId,
+        delta: f64,
+        resumed: bool,
+        update: &mut F,
+    ) -> Result<FrameOutcome, wgpu::SurfaceError>
+    where
+        F: FnMut(&mut Self, WindowId, &wgpu::TextureView, f64, bool) -> Vec<wgpu::CommandBuffer>,
+    {
+        let Some(context) = self.windows.get_mut(&window) else {
+            return Ok(FrameOutcome::Skipped);
+        };
+        let output = match context.surface.get_current_texture() {
+            Ok(output) => output,
+            Err(err) => {
+                return recover_surface(
+                    err,
+                    &mut context.config,
+                    context.window.inner_size(),
+                    |config| context.surface.configure(&self.device, config),
+                );
+            }
+        };
+        let view = output
+            .texture
+            .create_view(&wgpu::TextureViewDescriptor::default());
+        let buffers = update(self, window, &view, delta, resumed);
+        self.queue.submit(buffers);
+        output.present();
+        Ok(FrameOutcome::Rendered)
+    }
+
     pub fn clear(&mut self, view: &wgpu::TextureView, color: wgpu::Color) -> wgpu::CommandBuffer {
         let mut encoder = self
             .device
@@ -143,58 +1074,135 @@ impl Renderer {
         encoder.finish()
     }
 
+    /// renders a frame when a redraw is requested, lost or outdated surfaces are reconfigured
+    /// and only an out of memory surface is an error, which exits. Nothing is rendered while
+    /// the window is unfocused, update is told if the frame is the first since focus returned.
+    /// Update is called for the main window then each added window with the window to draw,
+    /// frame events are only collected from the main window
     pub fn handle_event<F>(
         &mut self,
         event: &Event<'_, ()>,
         control_flow: &mut ControlFlow,
         mut update: F,
-    ) -> Result<(), wgpu::SurfaceError>
+    ) -> Result<FrameOutcome, wgpu::SurfaceError>
     where
-        F: FnMut(&mut Self, &wgpu::TextureView, f64) -> Vec<wgpu::CommandBuffer>,
+        F: FnMut(&mut Self, WindowId, &wgpu::TextureView, f64, bool) -> Vec<wgpu::CommandBuffer>,
     {
         match event {
+            Event::WindowEvent {
+                ref event,
+                window_id,
+            } if self.windows.contains_key(window_id) => match *event {
+                WindowEvent::CloseRequested => {
+                    self.windows.remove(window_id);
+                    self.focus.set(*window_id, false);
+                }
+                WindowEvent::Resized(size) => {
+                    self.windows
+                        .get_mut(window_id)
+                        .unwrap()
+                        .resize(&self.device, size);
+                }
+                WindowEvent::ScaleFactorChanged {
+                    ref new_inner_size, ..
+                } => {
+                    self.windows
+                        .get_mut(window_id)
+                        .unwrap()
+                        .resize(&self.device, **new_inner_size);
+                }
+                WindowEvent::Focused(focused) => self.focus.set(*window_id, focused),
+                _ => {}
+            },
             Event::WindowEvent {
                 ref event,
                 window_id,
             } => {
                 if *window_id != self.window.id() {
-                    return Ok(());
+                    return Ok(FrameOutcome::Skipped);
                 }
+                self.frame_events.handle_event(event);
                 match *event {
                     WindowEvent::CloseRequested => *control_flow = ControlFlow::Exit,
                     WindowEvent::Resized(size) => self.resize(size),
+                    WindowEvent::ScaleFactorChanged {
+                        ref new_inner_size, ..
+                    } => self.resize(**new_inner_size),
+                    WindowEvent::Focused(focused) => self.focus.set(*window_id, focused),
                     _ => {}
                 };
             }
             Event::RedrawRequested(window_id) if *window_id == self.window.id() => {
                 let time = Instant::now();
 
-                let delta = time.duration_since(self.last_frame).as_secs_f64();
-                if delta < 1.0 / self.fps_target {
-                    return Ok(());
+                let delta = self.clock.delta(time);
+                if self.clock.paused || delta < 1.0 / self.fps_target {
+                    return Ok(FrameOutcome::Skipped);
                 }
 
-                let output = self.surface.get_current_texture()?;
+                let output = match self.surface.get_current_texture() {
+                    Ok(output) => output,
+                    Err(err) => {
+                        let outcome = recover_surface(
+                            err,
+                            &mut self.config,
+                            self.window.inner_size(),
+                            |config| self.surface.configure(&self.device, config),
+                        );
+                        if outcome.is_err() {
+                            *control_flow = ControlFlow::Exit;
+                        }
+                        return outcome;
+                    }
+                };
                 let view = output
                     .texture
                     .create_view(&wgpu::TextureViewDescriptor::default());
 
-                let buffers = update(self, &view, delta);
+                let resumed = self.clock.tick(time);
+                let buffers = update(self, self.window.id(), &view, delta, resumed);
                 self.queue.submit(buffers);
                 output.present();
 
-                self.last_frame = time;
+                let windows: Vec<WindowId> = self.windows.keys().copied().collect();
+                for window in windows {
+                    if let Err(err) = self.draw_window(window, delta, resumed, &mut update) {
+                        *control_flow = ControlFlow::Exit;
+                        return Err(err);
+                    }
+                }
+
+                self.frame_events.clear();
+                return Ok(FrameOutcome::Rendered);
             }
             Event::MainEventsCleared => {
-                self.window.request_redraw();
+                // applied once every event is handled, a window losing focus to another of the
+                // renderer's windows is followed by that window gaining it
+                if let Some(focused) = self.focus.take_change() {
+                    self.set_focused(focused, control_flow);
+                }
+                if self.clock.paused {
+                    return Ok(FrameOutcome::Skipped);
+                }
+                let now = Instant::now();
+                if now >= self.clock.next_frame(self.fps_target) {
+                    self.window.request_redraw();
+                }
+                // sleeps until the next frame rather than spinning through early redraws, which
+                // are still skipped in case it wakes too soon
+                *control_flow = ControlFlow::WaitUntil(self.clock.wake_at(self.fps_target, now));
             }
             _ => {}
         };
-        Ok(())
+        Ok(FrameOutcome::Skipped)
     }
 
-    pub async fn new(event_loop: &mut EventLoop<()>, features: wgpu::Features) -> Self {
-        let window = WindowBuilder::new().build(event_loop).unwrap();
+    pub async fn new(event_loop: &mut EventLoop<()>, renderer_config: RendererConfig) -> Self {
+        let window = renderer_config
+            .window_builder()
+            .unwrap()
+            .build(event_loop)
+            .unwrap();
         let size = window.inner_size();
 
         // The instance is a handle to our GPU
@@ -208,7 +1216,7 @@ impl Renderer {
         //
         // The surface needs to live as long as the window that created it.
         // Renderer owns the window so this should be safe.
-        let surface = unsafe { instance.create_surface(&window) }.unwrap();
+        let mut surface = unsafe { instance.create_surface(&window) }.unwrap();
 
         let adapter = instance
             .request_adapter(&wgpu::RequestAdapterOptions {
@@ -222,7 +1230,7 @@ impl Renderer {
         let (device, queue) = adapter
             .request_device(
                 &wgpu::DeviceDescriptor {
-                    features,
+                    features: renderer_config.features,
                     // WebGL doesn't support all of wgpu's features, so if
                     // we're building for the web we'll have to disable some.
                     limits: if cfg!(target_arch = "wasm32") {
@@ -250,11 +1258,17 @@ impl Renderer {
             .unwrap_or(surface_caps.formats[0]);
 
         let config = wgpu::SurfaceConfiguration {
-            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+            usage: surface_usage(
+                renderer_config.capture_frames,
+                surface_can_copy(&adapter, &mut surface),
+            ),
             format: surface_format,
             width: size.width,
             height: size.height,
-            present_mode: surface_caps.present_modes[0],
+            present_mode: choose_present_mode(
+                renderer_config.present_mode,
+                &surface_caps.present_modes,
+            ),
             alpha_mode: surface_caps.alpha_modes[0],
             view_formats: vec![],
         };
@@ -262,8 +1276,15 @@ impl Renderer {
         surface.configure(&device, &config);
 
         Self {
+            adapter_info: adapter.get_info(),
+            present_modes: surface_caps.present_modes,
             fps_target: 60.0,
-            last_frame: Instant::now(),
+            clock: FrameClock::new(Instant::now()),
+            frame_events: FrameEvents::default(),
+            windows: HashMap::new(),
+            focus: WindowFocus::new(window.id()),
+            instance,
+            adapter,
             window,
             surface,
             device,
@@ -272,3 +1293,429 @@ impl Renderer {
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn resuming_restarts_frame_clock() {
+        let start = Instant::now();
+        let mut clock = FrameClock::new(start);
+        assert!(!clock.tick(start + Duration::from_millis(16)));
+
+        clock.set_focused(false, start + Duration::from_millis(20));
+        assert!(clock.paused);
+        let resume = start + Duration::from_secs(10);
+        clock.set_focused(true, resume);
+        assert!(!clock.paused);
+
+        let frame = resume + Duration::from_millis(16);
+        assert!(clock.delta(frame) < 0.1);
+        assert!(clock.tick(frame));
+        assert!(!clock.tick(frame + Duration::from_millis(16)));
+    }
+
+    #[test]
+    fn added_windows_share_the_main_format() {
+        let main = wgpu::SurfaceConfiguration {
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+            format: wgpu::TextureFormat::Bgra8UnormSrgb,
+            width: 800,
+            height: 600,
+            present_mode: wgpu::PresentMode::Mailbox,
+            alpha_mode: wgpu::CompositeAlphaMode::Opaque,
+            view_formats: vec![],
+        };
+        let modes = [wgpu::PresentMode::Fifo, wgpu::PresentMode::Mailbox];
+        let first = window_config(&main, PhysicalSize::new(320, 240), &modes);
+        let second = window_config(&main, PhysicalSize::new(640, 480), &modes[..1]);
+        for config in [&first, &second] {
+            assert_eq!(config.format, main.format);
+            assert_eq!(config.usage, main.usage);
+        }
+        assert_eq!((first.width, first.height), (320, 240));
+        assert_eq!((second.width, second.height), (640, 480));
+        assert_eq!(first.present_mode, wgpu::PresentMode::Mailbox);
+        assert_eq!(second.present_mode, wgpu::PresentMode::Fifo);
+    }
+
+    #[test]
+    fn focus_moving_between_windows_stays_focused() {
+        let (main, other) = (WindowId::from(1), WindowId::from(2));
+        let mut focus = WindowFocus::new(main);
+        assert_eq!(focus.take_change(), None);
+
+        focus.set(main, false);
+        focus.set(other, true);
+        assert_eq!(focus.take_change(), Some(true));
+        assert_eq!(focus.take_change(), None);
+        // already unfocused
+        focus.set(main, false);
+        assert_eq!(focus.take_change(), None);
+
+        focus.set(other, false);
+        assert_eq!(focus.take_change(), Some(false));
+        focus.set(main, true);
+        assert_eq!(focus.take_change(), Some(true));
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn added_windows_draw_with_the_main_device() {
+        use winit::platform::x11::EventLoopBuilderExtX11;
+        if std::env::var_os("DISPLAY").is_none() {
+            eprintln!("skipping added_windows_draw_with_the_main_device: no X display");
+            return;
+        }
+        let mut event_loop = winit::event_loop::EventLoopBuilder::new()
+            .with_any_thread(true)
+            .build();
+        let mut renderer =
+            futures::executor::block_on(Renderer::new(&mut event_loop, RendererConfig::default()));
+        let windows: Vec<WindowId> = [(64, 48), (32, 96)]
+            .map(|(width, height)| {
+                let window = WindowBuilder::new()
+                    .with_inner_size(PhysicalSize::new(width, height))
+                    .build(&event_loop)
+                    .unwrap();
+                renderer.add_window(window).unwrap()
+            })
+            .to_vec();
+
+        for window in windows {
+            let config = renderer.surface_config(window).unwrap();
+            assert_eq!(config.format, renderer.config.format);
+            let mut drawn_to = None;
+            let outcome =
+                renderer.draw_window(window, 0.0, false, &mut |renderer, window, view, _, _| {
+                    drawn_to = Some(window);
+                    vec![renderer.clear(view, wgpu::Color::BLACK)]
+                });
+            assert_eq!(outcome, Ok(FrameOutcome::Rendered));
+            assert_eq!(drawn_to, Some(window));
+        }
+    }
+
+    #[test]
+    fn next_frame_is_a_frame_after_the_last() {
+        let start = Instant::now();
+        let clock = FrameClock::new(start);
+        assert_eq!(
+            clock.next_frame(60.0),
+            start + Duration::from_secs_f64(1.0 / 60.0)
+        );
+        assert_eq!(clock.next_frame(0.0), start);
+    }
+
+    #[test]
+    fn skipped_frame_wakes_a_frame_later() {
+        let start = Instant::now();
+        let clock = FrameClock::new(start);
+        let frame = Duration::from_secs_f64(1.0 / 60.0);
+        let early = start + Duration::from_millis(5);
+        assert_eq!(clock.wake_at(60.0, early), start + frame);
+        // the due frame wasn't drawn so the clock wasn't ticked
+        let late = start + Duration::from_millis(100);
+        assert_eq!(clock.wake_at(60.0, late), late + frame);
+    }
+
+    #[test]
+    fn render_target_reads_back_clear_color() {
+        let Some((device, queue)) = test_util::headless_device(wgpu::Features::empty()) else {
+            return;
+        };
+        let target = RenderTarget::new(
+            &device,
+            64,
+            64,
+            wgpu::TextureFormat::Rgba8Unorm,
+            "test_target",
+        );
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("test_encoder"),
+        });
+        encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("test_clear"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: &target.view,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(wgpu::Color::RED),
+                    store: true,
+                },
+            })],
+            depth_stencil_attachment: None,
+        });
+        queue.submit(Some(encoder.finish()));
+
+        let bytes = target.read(&device, &queue).unwrap();
+        assert_eq!(bytes.len(), 64 * 64 * 4);
+        assert_eq!(bytes[..4], [255, 0, 0, 255]);
+    }
+
+    #[test]
+    fn capture_resolves_after_the_device_is_polled() {
+        let Some((device, queue)) = test_util::headless_device(wgpu::Features::empty()) else {
+            return;
+        };
+        // 4 * 3 bytes isn't aligned to 256
+        let target = RenderTarget::new(
+            &device,
+            3,
+            2,
+            wgpu::TextureFormat::Bgra8Unorm,
+            "test_target",
+        );
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("test_encoder"),
+        });
+        encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("test_clear"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: &target.view,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(wgpu::Color::RED),
+                    store: true,
+                },
+            })],
+            depth_stencil_attachment: None,
+        });
+        queue.submit(Some(encoder.finish()));
+
+        let capture = capture_texture(&device, &queue, &target.texture);
+        device.poll(wgpu::Maintain::Wait);
+        let bytes = futures::executor::block_on(capture);
+        assert_eq!(bytes.len(), 3 * 2 * 4);
+        for pixel in bytes.chunks_exact(4) {
+            assert_eq!(pixel, [255, 0, 0, 255]);
+        }
+    }
+
+    #[test]
+    fn captured_rows_are_unpadded_rgba() {
+        // 3 pixels is 12 bytes, copied rows are padded to 256
+        let mut padded = vec![0; 256 * 2];
+        padded[..12].copy_from_slice(&[1; 12]);
+        padded[256..268].copy_from_slice(&[2; 12]);
+        let rows = strip_row_padding(&padded, 3);
+        assert_eq!(rows.len(), 24);
+        assert_eq!(rows[..12], [1; 12]);
+        assert_eq!(rows[12..], [2; 12]);
+        assert_eq!(padded_bytes_per_row(64), 256);
+
+        let mut pixel = [10, 20, 30, 255];
+        bgra_to_rgba(&mut pixel);
+        assert_eq!(pixel, [30, 20, 10, 255]);
+    }
+
+    #[test]
+    fn second_frame_has_positive_delta() {
+        let start = Instant::now();
+        let mut clock = FrameClock::new(start);
+        let first = start + Duration::from_millis(16);
+        clock.tick(first);
+        let delta = clock.delta(first + Duration::from_millis(16));
+        assert!(delta > 0.0);
+        assert!((delta - 0.016).abs() < 1e-6);
+    }
+
+    #[test]
+    fn mip_chain_halves_to_one_pixel() {
+        assert_eq!(mip_level_count(256, 256), 9);
+        assert_eq!(mip_level_count(300, 20), 9);
+        assert_eq!(mip_level_count(1, 1), 1);
+
+        let levels = mip_chain(image::RgbaImage::new(256, 256));
+        assert_eq!(levels.len(), 9);
+        assert_eq!(levels[1].dimensions(), (128, 128));
+        assert_eq!(levels[8].dimensions(), (1, 1));
+        let levels = mip_chain(image::RgbaImage::new(300, 20));
+        assert_eq!(levels[5].dimensions(), (9, 1));
+        assert_eq!(levels.last().unwrap().dimensions(), (1, 1));
+    }
+
+    #[test]
+    fn uncapped_present_modes_fall_back() {
+        use wgpu::PresentMode::{Fifo, Immediate, Mailbox};
+        assert_eq!(choose_present_mode(Mailbox, &[Fifo, Mailbox]), Mailbox);
+        assert_eq!(choose_present_mode(Mailbox, &[Fifo, Immediate]), Immediate);
+        assert_eq!(choose_present_mode(Immediate, &[Fifo]), Fifo);
+        assert_eq!(choose_present_mode(Fifo, &[Fifo, Immediate]), Fifo);
+    }
+
+    #[test]
+    fn capture_needs_copyable_surface() {
+        use wgpu::TextureUsages;
+        assert_eq!(
+            surface_usage(true, true),
+            TextureUsages::RENDER_ATTACHMENT | TextureUsages::COPY_SRC
+        );
+        assert_eq!(surface_usage(true, false), TextureUsages::RENDER_ATTACHMENT);
+        assert_eq!(surface_usage(false, true), TextureUsages::RENDER_ATTACHMENT);
+    }
+
+    #[test]
+    fn aspect_ratio_survives_zero_height() {
+        assert!((aspect_ratio(1600.0, 900.0) - 1.7777).abs() < 1e-3);
+        assert_eq!(aspect_ratio(1600.0, 0.0), 1.0);
+    }
+
+    #[test]
+    fn lost_surface_is_reconfigured_at_the_current_size() {
+        let mut config = wgpu::SurfaceConfiguration {
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+            format: wgpu::TextureFormat::Bgra8UnormSrgb,
+            width: 640,
+            height: 480,
+            present_mode: wgpu::PresentMode::Fifo,
+            alpha_mode: wgpu::CompositeAlphaMode::Auto,
+            view_formats: vec![],
+        };
+        let size = PhysicalSize::new(800, 600);
+        let mut configured = None;
+        let outcome = recover_surface(SurfaceError::Lost, &mut config, size, |config| {
+            configured = Some((config.width, config.height));
+        });
+        assert_eq!(outcome, Ok(FrameOutcome::Recovered));
+        assert_eq!(configured, Some((800, 600)));
+        assert_eq!((config.width, config.height), (800, 600));
+
+        for (err, outcome) in [
+            (SurfaceError::Timeout, Ok(FrameOutcome::Skipped)),
+            (SurfaceError::OutOfMemory, Err(SurfaceError::OutOfMemory)),
+        ] {
+            let size = PhysicalSize::new(1024, 768);
+            let recovered = recover_surface(err, &mut config, size, |_| {
+                panic!("only lost and outdated surfaces are reconfigured")
+            });
+            assert_eq!(recovered, outcome);
+        }
+        assert_eq!((config.width, config.height), (800, 600));
+    }
+
+    #[test]
+    fn premultiplies_alpha() {
+        let mut rgba = [255, 128, 0, 128, 10, 20, 30, 255];
+        premultiply_alpha(&mut rgba);
+        assert_eq!(rgba, [128, 64, 0, 128, 10, 20, 30, 255]);
+    }
+
+    #[test]
+    fn layers_are_reordered() {
+        let order = ["clear", "world", "sprites", "ui"];
+        let layers = vec![("ui", 3), ("sprites", 2), ("world", 1), ("sprites", 4)];
+        assert_eq!(order_layers(&order, layers), vec![1, 2, 4, 3]);
+    }
+
+    #[test]
+    fn dropped_files_are_collected() {
+        let mut events = FrameEvents::default();
+        events.handle_event(&WindowEvent::DroppedFile(PathBuf::from("level.toml")));
+        assert_eq!(events.dropped_files, vec![PathBuf::from("level.toml")]);
+        events.clear();
+        assert!(events.dropped_files.is_empty());
+    }
+
+    #[test]
+    fn resizes_are_collected() {
+        let mut events = FrameEvents::default();
+        events.handle_event(&WindowEvent::Resized(PhysicalSize::new(800, 600)));
+        assert_eq!(events.resized, Some(PhysicalSize::new(800, 600)));
+
+        let mut new_inner_size = PhysicalSize::new(1600, 1200);
+        events.handle_event(&WindowEvent::ScaleFactorChanged {
+            scale_factor: 2.0,
+            new_inner_size: &mut new_inner_size,
+        });
+        assert_eq!(events.resized, Some(PhysicalSize::new(1600, 1200)));
+        events.clear();
+        assert_eq!(events.resized, None);
+    }
+
+    #[test]
+    fn window_builder_uses_config() {
+        let config = RendererConfig {
+            title: "Magibreak".to_string(),
+            initial_size: Some(PhysicalSize::new(1280, 720)),
+            ..Default::default()
+        };
+        let builder = config.window_builder().unwrap();
+        let attributes = builder.window_attributes();
+        assert_eq!(attributes.title, "Magibreak");
+        assert_eq!(
+            attributes.inner_size,
+            Some(PhysicalSize::new(1280, 720).into())
+        );
+    }
+
+    #[test]
+    fn window_builder_rejects_bad_icon() {
+        let config = RendererConfig {
+            icon: Some(WindowIcon {
+                rgba: vec![0; 15],
+                width: 2,
+                height: 2,
+            }),
+            ..Default::default()
+        };
+        assert!(config.window_builder().is_err());
+    }
+
+    #[test]
+    fn report_names_adapter_and_backend() {
+        let Some((adapter, device, _)) =
+            test_util::headless_adapter(wgpu::Features::empty(), wgpu::DownlevelFlags::empty())
+        else {
+            return;
+        };
+        let info = adapter.get_info();
+        assert!(!info.name.is_empty());
+        let report = adapter_report(&info, device.features(), &device.limits());
+        assert!(report.contains(&info.name), "{report}");
+        assert!(
+            report.contains(&format!("backend: {:?}", info.backend)),
+            "{report}"
+        );
+    }
+
+    #[test]
+    fn viewport_splits_into_halves() {
+        let full = Viewport {
+            x: 0.0,
+            y: 0.0,
+            width: 1280.0,
+            height: 720.0,
+        };
+        let (left, right) = full.split_horizontal(0.5);
+        assert_eq!(left.scissor_rect(), [0, 0, 640, 720]);
+        assert_eq!(right.scissor_rect(), [640, 0, 640, 720]);
+        assert_eq!(left.aspect_ratio(), 640.0 / 720.0);
+
+        let (left, right) = full.split_horizontal(1.0 / 3.0);
+        let [_, _, left_width, _] = left.scissor_rect();
+        let [right_x, _, right_width, _] = right.scissor_rect();
+        assert_eq!(right_x, left_width);
+        assert_eq!(left_width + right_width, 1280);
+    }
+
+    #[test]
+    fn garbage_texture_is_an_error() {
+        let err = decode_rgba(&[0x89, 0x50, 0x13, 0x37, 0x00, 0xff], "orbs").unwrap_err();
+        assert_eq!(err.label, "orbs");
+        assert!(err.to_string().contains("orbs"));
+        assert!(decode_rgba(b"not an image", "garbage").is_err());
+
+        let mut png = Vec::new();
+        image::RgbaImage::from_pixel(2, 3, image::Rgba([10, 20, 30, 255]))
+            .write_to(
+                &mut std::io::Cursor::new(&mut png),
+                image::ImageOutputFormat::Png,
+            )
+            .unwrap();
+        let rgba = decode_rgba(&png, "png").unwrap();
+        assert_eq!(rgba.dimensions(), (2, 3));
+        assert_eq!(rgba.get_pixel(1, 2).0, [10, 20, 30, 255]);
+    }
+}
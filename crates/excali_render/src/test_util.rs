@@ -0,0 +1,71 @@
+//! Helpers for tests which draw without a window
+use futures::executor::block_on;
+
+/// a device without a window with the features, none when there's no adapter supporting them
+/// to test on, which is reported as the current test being skipped
+pub fn headless_device(features: wgpu::Features) -> Option<(wgpu::Device, wgpu::Queue)> {
+    headless_device_with(features, wgpu::DownlevelFlags::empty())
+}
+
+/// like [headless_device] on an adapter with the downlevel flags, which backends like GL lack
+pub fn headless_device_with(
+    features: wgpu::Features,
+    downlevel: wgpu::DownlevelFlags,
+) -> Option<(wgpu::Device, wgpu::Queue)> {
+    headless_adapter(features, downlevel).map(|(_adapter, device, queue)| (device, queue))
+}
+
+/// like [headless_device_with] along with the adapter, to ask what it supports
+pub fn headless_adapter(
+    features: wgpu::Features,
+    downlevel: wgpu::DownlevelFlags,
+) -> Option<(wgpu::Adapter, wgpu::Device, wgpu::Queue)> {
+    let instance = wgpu::Instance::default();
+    let Some(adapter) = block_on(instance.request_adapter(&wgpu::RequestAdapterOptions::default()))
+    else {
+        return skip("no adapter");
+    };
+    if !adapter.features().contains(features) {
+        return skip(&format!("the adapter doesn't support {features:?}"));
+    }
+    if !adapter
+        .get_downlevel_capabilities()
+        .flags
+        .contains(downlevel)
+    {
+        return skip(&format!("the adapter doesn't support {downlevel:?}"));
+    }
+    let descriptor = wgpu::DeviceDescriptor {
+        features,
+        ..Default::default()
+    };
+    match block_on(adapter.request_device(&descriptor, None)) {
+        Ok((device, queue)) => Some((adapter, device, queue)),
+        Err(err) => skip(&format!("can't request a device: {err}")),
+    }
+}
+
+/// a renderer for tests which need one with its window, none without an X display to open the
+/// window on. Only one can be made in each test binary, winit can't recreate its event loop
+#[cfg(target_os = "linux")]
+pub fn windowed_renderer(
+    config: crate::RendererConfig,
+) -> Option<(winit::event_loop::EventLoop<()>, crate::Renderer)> {
+    use winit::platform::x11::EventLoopBuilderExtX11;
+    if std::env::var_os("DISPLAY").is_none() {
+        return skip("no X display");
+    }
+    let mut event_loop = winit::event_loop::EventLoopBuilder::new()
+        .with_any_thread(true)
+        .build();
+    let renderer = block_on(crate::Renderer::new(&mut event_loop, config));
+    Some((event_loop, renderer))
+}
+
+/// reports the current test as skipped
+fn skip<T>(reason: &str) -> Option<T> {
+    let thread = std::thread::current();
+    let test = thread.name().unwrap_or("test");
+    eprintln!("skipping {test}: {reason}");
+    None
+}
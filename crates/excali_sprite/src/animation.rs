@@ -0,0 +1,60 @@
+use super::TextureCoordinate;
+
+/// Frames of a sprite sheet played on a loop
+#[derive(Clone, Debug)]
+pub struct Animation {
+    pub frames: Vec<TextureCoordinate>,
+    /// frames per second
+    pub fps: f32,
+}
+
+impl Animation {
+    pub fn new(frames: Vec<TextureCoordinate>, fps: f32) -> Self {
+        Self { frames, fps }
+    }
+
+    /// a frame for each cell of a sheet covering the whole texture, going along each row from
+    /// x 0 then to the next row up from y 0
+    pub fn from_grid(rows: u32, columns: u32, fps: f32) -> Self {
+        let width = 1.0 / columns as f32;
+        let height = 1.0 / rows as f32;
+        let frames = (0..rows)
+            .flat_map(|row| {
+                (0..columns).map(move |column| TextureCoordinate {
+                    width,
+                    height,
+                    x: column as f32 * width,
+                    y: row as f32 * height,
+                })
+            })
+            .collect();
+        Self::new(frames, fps)
+    }
+
+    /// the frame showing after time seconds, wrapping back to the first after the last, the
+    /// whole texture if there are no frames
+    pub fn frame_at(&self, time: f32) -> TextureCoordinate {
+        if self.frames.is_empty() {
+            return TextureCoordinate::default();
+        }
+        let frame = (time * self.fps).floor() as i64;
+        self.frames[frame.rem_euclid(self.frames.len() as i64) as usize]
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn frames_loop() {
+        let animation = Animation::from_grid(2, 2, 10.0);
+        assert_eq!(animation.frames.len(), 4);
+        let frame = animation.frame_at(0.25);
+        assert_eq!([frame.x, frame.y], [0.0, 0.5]);
+        let wrapped = animation.frame_at(0.45);
+        assert_eq!([wrapped.x, wrapped.y], [0.0, 0.0]);
+        let last = animation.frame_at(-0.05);
+        assert_eq!([last.x, last.y], [0.5, 0.5]);
+    }
+}
@@ -0,0 +1,241 @@
+use excali_render::wgpu::util::DeviceExt;
+use excali_render::wgpu::*;
+use excali_render::Viewport;
+
+use super::{
+    create_pipeline, grown_length, Sprite, SpriteBlendMode, SpriteRenderer, SpriteTexture,
+    Transform, Vertex,
+};
+
+/// An instance's transform as the columns of its matrix
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct InstanceRaw {
+    matrix: [[f32; 3]; 3],
+}
+
+impl From<&Transform> for InstanceRaw {
+    fn from(transform: &Transform) -> Self {
+        let matrix = transform.matrix();
+        Self {
+            matrix: [0, 1, 2].map(|column| {
+                [
+                    matrix[(0, column)],
+                    matrix[(1, column)],
+                    matrix[(2, column)],
+                ]
+            }),
+        }
+    }
+}
+
+impl InstanceRaw {
+    fn descriptor<'a>() -> VertexBufferLayout<'a> {
+        const ATTRIBUTES: [VertexAttribute; 3] = vertex_attr_array![
+            4 => Float32x3,
+            5 => Float32x3,
+            6 => Float32x3,
+        ];
+        VertexBufferLayout {
+            array_stride: std::mem::size_of::<InstanceRaw>() as BufferAddress,
+            step_mode: VertexStepMode::Instance,
+            attributes: &ATTRIBUTES,
+        }
+    }
+}
+
+fn instance_data(instances: &[Transform]) -> Vec<InstanceRaw> {
+    instances.iter().map(InstanceRaw::from).collect()
+}
+
+/// written before it's drawn, so it's left zeroed rather than uploaded
+pub(crate) fn create_instance_buffer(instance_count: u32, device: &Device) -> Buffer {
+    device.create_buffer(&BufferDescriptor {
+        label: Some("Sprite Instance Buffer"),
+        size: (std::mem::size_of::<InstanceRaw>() * instance_count as usize) as BufferAddress,
+        usage: BufferUsages::VERTEX | BufferUsages::COPY_DST,
+        mapped_at_creation: false,
+    })
+}
+
+/// Many copies of one sprite drawn in a single call
+#[derive(Clone, Copy)]
+pub struct InstancedBatch<'a> {
+    /// drawn at every instance, which moves it like a parent transform
+    pub sprite: Sprite,
+    pub instances: &'a [Transform],
+    pub texture: &'a SpriteTexture,
+    pub blend_mode: SpriteBlendMode,
+}
+
+impl SpriteRenderer {
+    /// draws the batch's sprite once for each instance, the quad is only built once instead of
+    /// for every sprite like [SpriteRenderer::draw], the target is the view drawn to and its size
+    pub fn draw_instanced(
+        &mut self,
+        batch: &InstancedBatch,
+        device: &Device,
+        queue: &Queue,
+        (view, view_size): (&TextureView, [u32; 2]),
+        viewport: Viewport,
+        camera: &Transform,
+    ) -> CommandBuffer {
+        let mut encoder = device.create_command_encoder(&CommandEncoderDescriptor {
+            label: Some("Instanced Sprite Command Encoder"),
+        });
+        if batch.instances.is_empty() {
+            return encoder.finish();
+        }
+        self.write_uniforms(queue, viewport, camera);
+        self.resize_depth(device, view_size);
+
+        let pipeline = self
+            .instanced_pipelines
+            .entry(batch.blend_mode)
+            .or_insert_with(|| {
+                create_pipeline(
+                    device,
+                    &self.shader,
+                    &self.pipeline_layout,
+                    self.format,
+                    batch.blend_mode,
+                    (
+                        "vs_instanced",
+                        &[Vertex::descriptor(), InstanceRaw::descriptor()],
+                    ),
+                )
+            });
+
+        // the renderer's vertex buffer may already be written to this frame
+        let quad_buffer = device.create_buffer_init(&util::BufferInitDescriptor {
+            label: Some("Instanced Sprite Vertex Buffer"),
+            contents: bytemuck::cast_slice(
+                &batch
+                    .sprite
+                    .vertices(batch.texture.data.width, batch.texture.data.height),
+            ),
+            usage: BufferUsages::VERTEX,
+        });
+        let instance_count = batch.instances.len() as u32;
+        if let Some(length) = grown_length(self.instance_length, instance_count) {
+            self.instance_buffer = create_instance_buffer(length, device);
+            self.instance_length = length;
+        }
+        queue.write_buffer(
+            &self.instance_buffer,
+            0,
+            bytemuck::cast_slice(&instance_data(batch.instances)),
+        );
+
+        let mut render_pass = encoder.begin_render_pass(&RenderPassDescriptor {
+            label: Some("Instanced Sprite Render Pass"),
+            color_attachments: &[Some(RenderPassColorAttachment {
+                view,
+                resolve_target: None,
+                ops: Operations {
+                    load: LoadOp::Load,
+                    store: true,
+                },
+            })],
+            depth_stencil_attachment: Some(RenderPassDepthStencilAttachment {
+                view: &self.depth_view,
+                depth_ops: Some(Operations {
+                    load: LoadOp::Clear(0.0),
+                    store: false,
+                }),
+                stencil_ops: None,
+            }),
+        });
+
+        viewport.apply(&mut render_pass);
+        render_pass.set_pipeline(pipeline);
+        render_pass.set_bind_group(0, &batch.texture.bind_group, &[]);
+        render_pass.set_bind_group(1, &self.window_bind_group, &[]);
+        render_pass.set_bind_group(2, &self.camera_bind_group, &[]);
+        render_pass.set_vertex_buffer(0, quad_buffer.slice(..));
+        render_pass.set_vertex_buffer(1, self.instance_buffer.slice(..));
+        // the first quad's indices
+        render_pass.set_index_buffer(self.index_buffer.slice(..), IndexFormat::Uint32);
+        render_pass.draw_indexed(0..6, 0, 0..instance_count);
+
+        drop(render_pass);
+        encoder.finish()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test::{cleared_target, test_config, test_texture};
+    use excali_render::test_util::headless_device;
+    use nalgebra::Vector2;
+
+    #[test]
+    fn instance_buffer_holds_each_transform() {
+        let instances: Vec<Transform> = (0..5)
+            .map(|i| Transform::from_position(Vector2::new(i as f32 * 10.0, 0.0)))
+            .collect();
+        let data = instance_data(&instances);
+        assert_eq!(data.len(), 5);
+        assert_eq!(
+            bytemuck::cast_slice::<_, u8>(&data).len(),
+            5 * InstanceRaw::descriptor().array_stride as usize
+        );
+        // the translation is the last column
+        assert_eq!(
+            data[4].matrix,
+            [[1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [40.0, 0.0, 1.0]]
+        );
+    }
+
+    #[test]
+    fn instance_buffer_grows_to_fit() {
+        let Some((device, queue)) = headless_device(Features::empty()) else {
+            return;
+        };
+        let config = test_config(32, 4);
+        let mut sprite_renderer = SpriteRenderer::new(&config, &device, 32.0, 4.0);
+        let sampler = device.create_sampler(&SamplerDescriptor::default());
+        let white = test_texture(
+            &sprite_renderer,
+            &device,
+            &queue,
+            &sampler,
+            &[255; 4],
+            (1, 1),
+        );
+        // a column over each of the first pixels, more than the buffer starts with
+        let instances: Vec<Transform> = (0..20)
+            .map(|i| Transform::from_position(Vector2::new(i as f32 - 15.5, 0.0)))
+            .collect();
+        let batch = InstancedBatch {
+            sprite: Sprite {
+                transform: Transform {
+                    scale: Vector2::new(1.0, 4.0),
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+            instances: &instances,
+            texture: &white,
+            blend_mode: SpriteBlendMode::Alpha,
+        };
+
+        let target = cleared_target(&device, &queue, 32, 4, Color::BLACK);
+        let commands = sprite_renderer.draw_instanced(
+            &batch,
+            &device,
+            &queue,
+            (&target.view, [32, 4]),
+            Viewport::full(&config),
+            &Transform::default(),
+        );
+        queue.submit(Some(commands));
+        assert_eq!(sprite_renderer.instance_length, 32);
+
+        let pixels = target.read(&device, &queue).unwrap();
+        let red = |x: usize| pixels[(2 * 32 + x) * 4];
+        assert!((0..20).all(|x| red(x) == 255));
+        assert!((20..32).all(|x| red(x) == 0));
+    }
+}
@@ -1,8 +1,24 @@
+use std::collections::HashMap;
+
 use excali_render::wgpu::util::DeviceExt;
 use excali_render::wgpu::*;
-use nalgebra::{Vector2, Vector4};
+use excali_render::Viewport;
+use nalgebra::{Matrix3, Vector2, Vector3, Vector4};
+
+pub use self::animation::*;
+pub use self::instanced::*;
+pub use self::line::*;
+pub use self::text::*;
+
+mod animation;
+mod instanced;
+mod line;
+mod nine_patch;
+mod text;
 
-const STARTING_LENGTH: u16 = 16;
+const STARTING_LENGTH: u32 = 16;
+
+const DEPTH_FORMAT: TextureFormat = TextureFormat::Depth32Float;
 
 #[repr(C)]
 #[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
@@ -10,6 +26,29 @@ pub struct WindowUnifrom {
     pub size: [f32; 2],
 }
 
+/// a 2D transform as columns padded to WGSL's mat3x3 layout
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct CameraUniform {
+    matrix: [[f32; 4]; 3],
+}
+
+impl From<&Transform> for CameraUniform {
+    fn from(camera: &Transform) -> Self {
+        let matrix = camera.matrix();
+        Self {
+            matrix: [0, 1, 2].map(|column| {
+                [
+                    matrix[(0, column)],
+                    matrix[(1, column)],
+                    matrix[(2, column)],
+                    0.0,
+                ]
+            }),
+        }
+    }
+}
+
 type VertexTextureCoordinate = [f32; 2];
 
 #[repr(C)]
@@ -18,6 +57,8 @@ struct Vertex {
     position: [f32; 2],
     tex_coords: VertexTextureCoordinate,
     color: [f32; 4],
+    /// the sprite's [Sprite::z], nearer the front the greater it is
+    depth: f32,
 }
 
 impl Vertex {
@@ -41,13 +82,19 @@ impl Vertex {
                     shader_location: 2,
                     format: VertexFormat::Float32x4,
                 },
+                VertexAttribute {
+                    offset: std::mem::size_of::<[f32; 8]>() as BufferAddress,
+                    shader_location: 3,
+                    format: VertexFormat::Float32,
+                },
             ],
         }
     }
 }
 
-fn indices(sprites: u16) -> Vec<u16> {
-    let mut indicies = Vec::<u16>::new();
+/// u32 so the offsets don't wrap past 16384 sprites
+fn indices(sprites: u32) -> Vec<u32> {
+    let mut indicies = Vec::<u32>::new();
 
     for i in 0..sprites {
         let offset = i * 4;
@@ -66,10 +113,44 @@ pub struct SpriteRenderer {
     vertex_buffer: Buffer,
     index_buffer: Buffer,
     window_buffer: Buffer,
-    pipeline: RenderPipeline,
+    camera_buffer: Buffer,
+    /// created the first time a blend mode is drawn
+    pipelines: HashMap<SpriteBlendMode, RenderPipeline>,
+    /// like pipelines but for [SpriteRenderer::draw_instanced]
+    instanced_pipelines: HashMap<SpriteBlendMode, RenderPipeline>,
+    shader: ShaderModule,
+    pipeline_layout: PipelineLayout,
+    format: TextureFormat,
     window_bind_group: BindGroup,
+    camera_bind_group: BindGroup,
     texture_bind_group_layout: BindGroupLayout,
-    length: u16,
+    length: u32,
+    /// written by [SpriteRenderer::draw_instanced], grown like the sprite buffers
+    instance_buffer: Buffer,
+    instance_length: u32,
+    depth_view: TextureView,
+    /// the size of the depth texture, resized to match the view drawn to
+    depth_size: [u32; 2],
+}
+
+/// sprites are drawn over those with a lower z, so the depth is cleared to the back
+fn create_depth_view(device: &Device, width: u32, height: u32) -> TextureView {
+    device
+        .create_texture(&TextureDescriptor {
+            label: Some("Sprite Depth Texture"),
+            size: Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: TextureDimension::D2,
+            format: DEPTH_FORMAT,
+            usage: TextureUsages::RENDER_ATTACHMENT,
+            view_formats: &[],
+        })
+        .create_view(&TextureViewDescriptor::default())
 }
 
 #[derive(Debug, Clone, Copy)]
@@ -91,6 +172,25 @@ impl Default for TextureCoordinate {
     }
 }
 impl TextureCoordinate {
+    /// mirrors horizontally by swapping the left and right edges, the quad's winding
+    /// comes from the sprite so it isn't culled
+    pub fn flipped_x(self) -> Self {
+        Self {
+            x: self.x + self.width,
+            width: -self.width,
+            ..self
+        }
+    }
+
+    /// mirrors vertically by swapping the bottom and top edges
+    pub fn flipped_y(self) -> Self {
+        Self {
+            y: self.y + self.height,
+            height: -self.height,
+            ..self
+        }
+    }
+
     fn bottom_left(&self) -> VertexTextureCoordinate {
         [self.x, self.y]
     }
@@ -162,6 +262,71 @@ impl Transform {
         }
     }
 
+    /// the transform which undoes this one, exact when the scale is uniform
+    pub fn inverse(&self) -> Self {
+        let rotation = -self.rotation;
+        let sin = rotation.sin();
+        let cos = rotation.cos();
+        let position = -self.position.component_div(&self.scale);
+        Self {
+            rotation,
+            scale: Vector2::new(1.0 / self.scale.x, 1.0 / self.scale.y),
+            position: Vector2::new(
+                position.x * cos + position.y * -sin,
+                position.x * sin + position.y * cos,
+            ),
+        }
+    }
+
+    /// scales, rotates then moves the point, like `self * &Transform::from_position(point)`
+    pub fn transform_point(&self, point: Vector2<f32>) -> Vector2<f32> {
+        let scaled = point.component_mul(&self.scale);
+        let cos = self.rotation.cos();
+        let sin = self.rotation.sin();
+        self.position
+            + Vector2::new(
+                scaled.x * cos - scaled.y * sin,
+                scaled.x * sin + scaled.y * cos,
+            )
+    }
+
+    /// scales then turns the point clockwise like [Sprite::vertices] turns its quad before
+    /// moving it, so sprites placed on another sprite turn with it
+    pub(crate) fn sprite_point(&self, point: Vector2<f32>) -> Vector2<f32> {
+        let scaled = point.component_mul(&self.scale);
+        let cos = self.rotation.cos();
+        let sin = self.rotation.sin();
+        self.position
+            + Vector2::new(
+                scaled.x * cos + scaled.y * sin,
+                scaled.y * cos - scaled.x * sin,
+            )
+    }
+
+    /// undoes [Transform::transform_point], exact with a non uniform scale unlike
+    /// [Transform::inverse], like for a click in a rotated sprite's local space
+    pub fn inverse_transform_point(&self, point: Vector2<f32>) -> Vector2<f32> {
+        let offset = point - self.position;
+        let cos = self.rotation.cos();
+        let sin = self.rotation.sin();
+        Vector2::new(
+            offset.x * cos + offset.y * sin,
+            offset.y * cos - offset.x * sin,
+        )
+        .component_div(&self.scale)
+    }
+
+    /// the matrix applying the transform to a point, matching `self * &Transform::from_position`
+    pub fn matrix(&self) -> Matrix3<f32> {
+        let cos = self.rotation.cos();
+        let sin = self.rotation.sin();
+        Matrix3::from_columns(&[
+            Vector3::new(self.scale.x * cos, self.scale.x * sin, 0.0),
+            Vector3::new(-self.scale.y * sin, self.scale.y * cos, 0.0),
+            Vector3::new(self.position.x, self.position.y, 1.0),
+        ])
+    }
+
     pub fn from_scale(scale: Vector2<f32>) -> Self {
         Self {
             scale,
@@ -184,6 +349,33 @@ impl Transform {
     }
 }
 
+/// returns the camera transform which centers and fits all the bounds within the viewport, with
+/// padding in pixels around the edges
+pub fn fit_transform(
+    bounds: impl Iterator<Item = (Vector2<f32>, Vector2<f32>)>,
+    viewport: Vector2<f32>,
+    padding: f32,
+) -> Transform {
+    let Some((min, max)) =
+        bounds.reduce(|(min_a, max_a), (min_b, max_b)| (min_a.inf(&min_b), max_a.sup(&max_b)))
+    else {
+        return Transform::default();
+    };
+    let size = max - min;
+    let available = viewport - Vector2::new(padding, padding) * 2.0;
+    let scale = (available.x / size.x).min(available.y / size.y);
+    let scale = if scale.is_finite() && scale > 0.0 {
+        scale
+    } else {
+        1.0
+    };
+    Transform {
+        position: -(min + max) / 2.0 * scale,
+        rotation: 0.0,
+        scale: Vector2::new(scale, scale),
+    }
+}
+
 pub type Color = Vector4<f32>;
 
 #[derive(Clone, Copy)]
@@ -191,6 +383,17 @@ pub struct Sprite {
     pub transform: Transform,
     pub color: Color,
     pub texture_coordinate: TextureCoordinate,
+    /// mirrors the texture horizontally without changing the geometry
+    pub flip_x: bool,
+    /// mirrors the texture vertically without changing the geometry
+    pub flip_y: bool,
+    /// the point the sprite is positioned, rotated and scaled around, from (0, 0) at the
+    /// bottom left to (1, 1) at the top right
+    pub pivot: Vector2<f32>,
+    /// sprites with a greater z are drawn over those with a lower one whatever their batch
+    /// order, clamped from 0 to 1. Sprites with the same z are drawn in order. Transparent
+    /// parts of a sprite still hide lower sprites drawn after it
+    pub z: f32,
 }
 
 impl Default for Sprite {
@@ -199,18 +402,88 @@ impl Default for Sprite {
             transform: Transform::default(),
             texture_coordinate: TextureCoordinate::default(),
             color: Color::new(1.0, 1.0, 1.0, 1.0),
+            flip_x: false,
+            flip_y: false,
+            pivot: Vector2::new(0.5, 0.5),
+            z: 0.0,
         }
     }
 }
 
 impl Sprite {
-    fn vertices(&self, width: u32, height: u32) -> [Vertex; 4] {
-        let position = self.transform.position;
-        let rotation = self.transform.rotation;
-        let scale = self.transform.scale.component_mul(&Vector2::new(
+    /// toggles [Sprite::flip_x], like for a character turning around
+    pub fn flip_h(self) -> Self {
+        Self {
+            flip_x: !self.flip_x,
+            ..self
+        }
+    }
+
+    /// toggles [Sprite::flip_y]
+    pub fn flip_v(self) -> Self {
+        Self {
+            flip_y: !self.flip_y,
+            ..self
+        }
+    }
+
+    /// half the sprite's size before rotating
+    fn half_extents(&self, width: u32, height: u32) -> Vector2<f32> {
+        self.transform.scale.component_mul(&Vector2::new(
             width as f32 * self.texture_coordinate.width.abs(),
             height as f32 * self.texture_coordinate.height.abs(),
-        )) / 2.0;
+        )) / 2.0
+    }
+
+    /// where the middle of the sprite ends up once it's rotated around the pivot
+    fn center(&self, half_extents: Vector2<f32>) -> Vector2<f32> {
+        let offset = (Vector2::new(0.5, 0.5) - self.pivot).component_mul(&half_extents) * 2.0;
+        let sin = self.transform.rotation.sin();
+        let cos = self.transform.rotation.cos();
+        self.transform.position
+            + Vector2::new(
+                offset.x * cos + offset.y * sin,
+                offset.y * cos - offset.x * sin,
+            )
+    }
+
+    /// returns the min and max corners of the rotated sprite's axis aligned bounds
+    pub fn aabb(&self, tex_width: u32, tex_height: u32) -> (Vector2<f32>, Vector2<f32>) {
+        let center = self.center(self.half_extents(tex_width, tex_height));
+        let half_extents = self.half_extents(tex_width, tex_height).abs();
+        let sin = self.transform.rotation.sin().abs();
+        let cos = self.transform.rotation.cos().abs();
+        let extents = Vector2::new(
+            half_extents.x * cos + half_extents.y * sin,
+            half_extents.x * sin + half_extents.y * cos,
+        );
+        (center - extents, center + extents)
+    }
+
+    /// returns if the point is on the sprite's rotated quad, as big as [Sprite::vertices] makes it
+    pub fn contains_point(
+        &self,
+        world_point: Vector2<f32>,
+        texture_width: u32,
+        texture_height: u32,
+    ) -> bool {
+        let half_extents = self.half_extents(texture_width, texture_height);
+        let offset = world_point - self.center(half_extents);
+        // the quad is turned clockwise, so turning back is counterclockwise
+        let sin = self.transform.rotation.sin();
+        let cos = self.transform.rotation.cos();
+        let local = Vector2::new(
+            offset.x * cos - offset.y * sin,
+            offset.x * sin + offset.y * cos,
+        );
+        let half_extents = half_extents.abs();
+        local.x.abs() <= half_extents.x && local.y.abs() <= half_extents.y
+    }
+
+    fn vertices(&self, width: u32, height: u32) -> [Vertex; 4] {
+        let rotation = self.transform.rotation;
+        let scale = self.half_extents(width, height);
+        let position = self.center(scale);
         let sin = rotation.sin();
         let cos = rotation.cos();
 
@@ -226,26 +499,47 @@ impl Sprite {
         let bottom_right = position - top_left;
         top_left += position;
 
+        let mut bottom_tex_coords = [
+            self.texture_coordinate.bottom_left(),
+            self.texture_coordinate.bottom_right(),
+        ];
+        let mut top_tex_coords = [
+            self.texture_coordinate.top_left(),
+            self.texture_coordinate.top_right(),
+        ];
+        if self.flip_x {
+            bottom_tex_coords.swap(0, 1);
+            top_tex_coords.swap(0, 1);
+        }
+        if self.flip_y {
+            std::mem::swap(&mut bottom_tex_coords, &mut top_tex_coords);
+        }
+        let depth = self.z.clamp(0.0, 1.0);
+
         [
             Vertex {
                 position: [bottom_left.x, bottom_left.y],
                 color: [self.color.x, self.color.y, self.color.z, self.color.w],
-                tex_coords: self.texture_coordinate.bottom_left(),
+                tex_coords: bottom_tex_coords[0],
+                depth,
             },
             Vertex {
                 position: [bottom_right.x, bottom_right.y],
                 color: [self.color.x, self.color.y, self.color.z, self.color.w],
-                tex_coords: self.texture_coordinate.bottom_right(),
+                tex_coords: bottom_tex_coords[1],
+                depth,
             },
             Vertex {
                 position: [top_right.x, top_right.y],
                 color: [self.color.x, self.color.y, self.color.z, self.color.w],
-                tex_coords: self.texture_coordinate.top_right(),
+                tex_coords: top_tex_coords[1],
+                depth,
             },
             Vertex {
                 position: [top_left.x, top_left.y],
                 color: [self.color.x, self.color.y, self.color.z, self.color.w],
-                tex_coords: self.texture_coordinate.top_left(),
+                tex_coords: top_tex_coords[0],
+                depth,
             },
         ]
     }
@@ -256,21 +550,144 @@ pub struct SpriteTexture {
     pub data: excali_render::Texture,
 }
 
+/// How a batch's sprites are blended over what's already drawn
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum SpriteBlendMode {
+    /// for textures with straight alpha, filtered edges of transparent textures darken
+    #[default]
+    Alpha,
+    /// for textures loaded with [excali_render::Renderer::load_texture_premultiplied], avoids the
+    /// dark fringes around filtered transparent edges
+    PremultipliedAlpha,
+    /// adds the color weighted by alpha to what's underneath, for glows like fire and magic
+    Additive,
+    /// replaces what's underneath, ignoring alpha
+    Opaque,
+}
+
+impl SpriteBlendMode {
+    pub fn blend_state(&self) -> BlendState {
+        let src_factor = match self {
+            Self::Alpha => BlendFactor::SrcAlpha,
+            Self::PremultipliedAlpha => BlendFactor::One,
+            Self::Additive => {
+                return BlendState {
+                    color: BlendComponent {
+                        src_factor: BlendFactor::SrcAlpha,
+                        dst_factor: BlendFactor::One,
+                        operation: BlendOperation::Add,
+                    },
+                    alpha: BlendComponent {
+                        src_factor: BlendFactor::Zero,
+                        dst_factor: BlendFactor::One,
+                        operation: BlendOperation::Add,
+                    },
+                }
+            }
+            Self::Opaque => return BlendState::REPLACE,
+        };
+        BlendState {
+            color: BlendComponent {
+                src_factor,
+                dst_factor: BlendFactor::OneMinusSrcAlpha,
+                operation: BlendOperation::Add,
+            },
+            alpha: BlendComponent {
+                src_factor: BlendFactor::One,
+                dst_factor: BlendFactor::OneMinusSrcAlpha,
+                operation: BlendOperation::Add,
+            },
+        }
+    }
+
+    /// the color the blend state produces drawing source over destination
+    pub fn blend(&self, source: Color, destination: Color) -> Color {
+        let source_factor = match self {
+            Self::Alpha => source.w,
+            Self::PremultipliedAlpha => 1.0,
+            Self::Additive => {
+                let color = destination.xyz() + source.xyz() * source.w;
+                return Color::new(color.x, color.y, color.z, destination.w);
+            }
+            Self::Opaque => return source,
+        };
+        let color = source.xyz() * source_factor + destination.xyz() * (1.0 - source.w);
+        Color::new(
+            color.x,
+            color.y,
+            color.z,
+            source.w + destination.w * (1.0 - source.w),
+        )
+    }
+}
+
 #[derive(Clone)]
 pub struct SpriteBatch<'a> {
     pub sprites: Vec<Sprite>,
     pub texture: &'a SpriteTexture,
+    pub blend_mode: SpriteBlendMode,
+    /// only draws inside the x, y, width and height in pixels, like for a scrolling panel
+    pub clip: Option<[u32; 4]>,
 }
 
-fn create_vertex_buffer(sprite_count: u16, device: &Device) -> Buffer {
-    device.create_buffer_init(&util::BufferInitDescriptor {
+/// the clip's overlap with the viewport's scissor rect, none when nothing would be drawn
+fn clip_scissor(clip: Option<[u32; 4]>, viewport: [u32; 4]) -> Option<[u32; 4]> {
+    let [x, y, width, height] = viewport;
+    let Some([clip_x, clip_y, clip_width, clip_height]) = clip else {
+        return (width > 0 && height > 0).then_some(viewport);
+    };
+    let left = clip_x.max(x);
+    let top = clip_y.max(y);
+    let right = clip_x.saturating_add(clip_width).min(x + width);
+    let bottom = clip_y.saturating_add(clip_height).min(y + height);
+    (right > left && bottom > top).then(|| [left, top, right - left, bottom - top])
+}
+
+/// remembers the new state, returns if it changed and needs to be set
+fn changed<T: PartialEq>(current: &mut Option<T>, next: T) -> bool {
+    if current.as_ref() == Some(&next) {
+        return false;
+    }
+    *current = Some(next);
+    true
+}
+
+/// orders the items so those with the same key are next to each other,
+/// keys are ordered by their first appearance and items keep their order within a key
+fn group_order<K: PartialEq>(keys: &[K]) -> Vec<usize> {
+    let mut order: Vec<usize> = Vec::with_capacity(keys.len());
+    for (i, key) in keys.iter().enumerate() {
+        if order.iter().any(|j| keys[*j] == *key) {
+            continue;
+        }
+        order.extend((i..keys.len()).filter(|j| keys[*j] == *key));
+    }
+    order
+}
+
+/// the doubled length the buffers need to grow to for the sprites, none if they fit
+fn grown_length(length: u32, sprite_count: u32) -> Option<u32> {
+    if sprite_count <= length {
+        return None;
+    }
+    let mut grown = length.max(1);
+    while grown < sprite_count {
+        grown = grown.saturating_mul(2);
+    }
+    Some(grown)
+}
+
+/// written before it's drawn, so it's left zeroed rather than uploaded
+fn create_vertex_buffer(sprite_count: u32, device: &Device) -> Buffer {
+    device.create_buffer(&BufferDescriptor {
         label: Some("Sprite Vertex Buffer"),
-        contents: &vec![0u8; std::mem::size_of::<Vertex>() * (sprite_count * 4) as usize],
+        size: (std::mem::size_of::<Vertex>() * sprite_count as usize * 4) as BufferAddress,
         usage: BufferUsages::VERTEX | BufferUsages::COPY_DST,
+        mapped_at_creation: false,
     })
 }
 
-fn create_index_buffer(sprite_count: u16, device: &Device) -> Buffer {
+fn create_index_buffer(sprite_count: u32, device: &Device) -> Buffer {
     let index_buffer = device.create_buffer_init(&util::BufferInitDescriptor {
         label: Some("Sprite Index Buffer"),
         contents: bytemuck::cast_slice(&indices(sprite_count)),
@@ -280,6 +697,56 @@ fn create_index_buffer(sprite_count: u16, device: &Device) -> Buffer {
     index_buffer
 }
 
+/// the vertex entry point reads the buffers
+fn create_pipeline(
+    device: &Device,
+    shader: &ShaderModule,
+    pipeline_layout: &PipelineLayout,
+    format: TextureFormat,
+    blend_mode: SpriteBlendMode,
+    (entry_point, buffers): (&str, &[VertexBufferLayout]),
+) -> RenderPipeline {
+    device.create_render_pipeline(&RenderPipelineDescriptor {
+        label: Some(&format!("{blend_mode:?} Sprite Pipeline ({entry_point})")),
+        layout: Some(pipeline_layout),
+        vertex: VertexState {
+            module: shader,
+            entry_point,
+            buffers,
+        },
+        fragment: Some(FragmentState {
+            module: shader,
+            entry_point: "fs_main",
+            targets: &[Some(ColorTargetState {
+                format,
+                blend: Some(blend_mode.blend_state()),
+                write_mask: ColorWrites::ALL,
+            })],
+        }),
+        primitive: PrimitiveState {
+            topology: PrimitiveTopology::TriangleList,
+            strip_index_format: None,
+            front_face: FrontFace::Ccw,
+            cull_mode: Some(Face::Back),
+            // Setting this to anything other than Fill requires Features::NON_FILL_POLYGON_MODE
+            polygon_mode: PolygonMode::Fill,
+            // Requires Features::DEPTH_CLIP_CONTROL
+            unclipped_depth: false,
+            // Requires Features::CONSERVATIVE_RASTERIZATION
+            conservative: false,
+        },
+        depth_stencil: Some(DepthStencilState {
+            format: DEPTH_FORMAT,
+            depth_write_enabled: true,
+            depth_compare: CompareFunction::GreaterEqual,
+            stencil: StencilState::default(),
+            bias: DepthBiasState::default(),
+        }),
+        multisample: MultisampleState::default(),
+        multiview: None,
+    })
+}
+
 impl SpriteRenderer {
     pub fn new(
         config: &SurfaceConfiguration,
@@ -352,68 +819,105 @@ impl SpriteRenderer {
             label: Some("window_bind_group"),
         });
 
+        let camera_buffer = device.create_buffer_init(&util::BufferInitDescriptor {
+            label: Some("Sprite Camera Buffer"),
+            contents: bytemuck::cast_slice(&[CameraUniform::from(&Transform::default())]),
+            usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
+        });
+
+        let camera_bind_group_layout =
+            device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+                entries: &[BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: ShaderStages::VERTEX,
+                    ty: BindingType::Buffer {
+                        ty: BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: BufferSize::new(
+                            std::mem::size_of::<CameraUniform>() as BufferAddress
+                        ),
+                    },
+                    count: None,
+                }],
+                label: Some("sprite_camera_bind_group_layout"),
+            });
+
+        let camera_bind_group = device.create_bind_group(&BindGroupDescriptor {
+            layout: &camera_bind_group_layout,
+            entries: &[BindGroupEntry {
+                binding: 0,
+                resource: camera_buffer.as_entire_binding(),
+            }],
+            label: Some("sprite_camera_bind_group"),
+        });
+
         let pipeline_layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
             label: Some("Render Pipeline Layout"),
-            bind_group_layouts: &[&texture_bind_group_layout, &window_bind_group_layout],
+            bind_group_layouts: &[
+                &texture_bind_group_layout,
+                &window_bind_group_layout,
+                &camera_bind_group_layout,
+            ],
             push_constant_ranges: &[],
         });
 
-        let pipeline = device.create_render_pipeline(&RenderPipelineDescriptor {
-            label: Some("Render Pipeline"),
-            layout: Some(&pipeline_layout),
-            vertex: VertexState {
-                module: &shader,
-                entry_point: "vs_main",
-                buffers: &[Vertex::descriptor()],
-            },
-            fragment: Some(FragmentState {
-                module: &shader,
-                entry_point: "fs_main",
-                targets: &[Some(ColorTargetState {
-                    format: config.format,
-                    blend: Some(BlendState {
-                        color: BlendComponent {
-                            src_factor: BlendFactor::SrcAlpha,
-                            dst_factor: BlendFactor::OneMinusSrcAlpha,
-                            operation: BlendOperation::Add,
-                        },
-                        alpha: BlendComponent {
-                            src_factor: BlendFactor::One,
-                            dst_factor: BlendFactor::OneMinusSrcAlpha,
-                            operation: BlendOperation::Add,
-                        },
-                    }),
-                    write_mask: ColorWrites::ALL,
-                })],
-            }),
-            primitive: PrimitiveState {
-                topology: PrimitiveTopology::TriangleList,
-                strip_index_format: None,
-                front_face: FrontFace::Ccw,
-                cull_mode: Some(Face::Back),
-                // Setting this to anything other than Fill requires Features::NON_FILL_POLYGON_MODE
-                polygon_mode: PolygonMode::Fill,
-                // Requires Features::DEPTH_CLIP_CONTROL
-                unclipped_depth: false,
-                // Requires Features::CONSERVATIVE_RASTERIZATION
-                conservative: false,
-            },
-            depth_stencil: None,
-            multisample: MultisampleState::default(),
-            multiview: None,
-        });
-
         Self {
             texture_bind_group_layout,
             length: STARTING_LENGTH,
+            instance_buffer: create_instance_buffer(STARTING_LENGTH, device),
+            instance_length: STARTING_LENGTH,
             vertex_buffer,
             index_buffer,
-            pipeline,
+            pipelines: HashMap::new(),
+            instanced_pipelines: HashMap::new(),
+            shader,
+            pipeline_layout,
+            format: config.format,
             window_bind_group,
             window_buffer,
+            camera_bind_group,
+            camera_buffer,
+            depth_view: create_depth_view(device, config.width, config.height),
+            depth_size: [config.width, config.height],
+        }
+    }
+
+    /// recreates the depth texture when the size of the view drawn to changes, like when the
+    /// window's resized
+    fn resize_depth(&mut self, device: &Device, [width, height]: [u32; 2]) {
+        if [width, height] != self.depth_size {
+            self.depth_view = create_depth_view(device, width, height);
+            self.depth_size = [width, height];
         }
     }
 
+    /// creates the renderer sized to the renderer's surface
+    pub fn from_renderer(renderer: &excali_render::Renderer) -> Self {
+        Self::new(
+            &renderer.config,
+            &renderer.device,
+            renderer.config.width as f32,
+            renderer.config.height as f32,
+        )
+    }
+
+    /// draws to the view using the renderer's device, queue and surface size without a camera
+    pub fn draw_with(
+        &mut self,
+        renderer: &excali_render::Renderer,
+        view: &TextureView,
+        sprite_batches: &[SpriteBatch],
+    ) -> CommandBuffer {
+        self.draw(
+            sprite_batches,
+            &renderer.device,
+            &renderer.queue,
+            (view, [renderer.config.width, renderer.config.height]),
+            renderer.viewport(),
+            &Transform::default(),
+        )
+    }
+
     pub fn create_bind_group(
         &self,
         device: &Device,
@@ -436,37 +940,101 @@ impl SpriteRenderer {
         })
     }
 
+    /// sprites are moved by the camera on the GPU then positioned relative to the viewport's
+    /// center, one world unit is a pixel at a scale of 1 and the default camera doesn't move them.
+    /// The target is the view drawn to and its size
     pub fn draw(
         &mut self,
         sprite_batches: &[SpriteBatch],
         device: &Device,
         queue: &Queue,
-        view: &TextureView,
-        window_size: [f32; 2],
+        target: (&TextureView, [u32; 2]),
+        viewport: Viewport,
+        camera: &Transform,
     ) -> CommandBuffer {
-        let mut encoder = device.create_command_encoder(&CommandEncoderDescriptor {
-            label: Some("Sprite Command Encoder"),
-        });
+        self.draw_ordered(
+            sprite_batches.iter().collect(),
+            device,
+            queue,
+            target,
+            viewport,
+            camera,
+        )
+    }
+
+    /// like [SpriteRenderer::draw] but groups batches of the same texture to bind it once,
+    /// batches keep their order within a texture but may be reordered across textures
+    /// so it's only safe when they're opaque or don't overlap
+    pub fn draw_sorted(
+        &mut self,
+        sprite_batches: &[SpriteBatch],
+        device: &Device,
+        queue: &Queue,
+        target: (&TextureView, [u32; 2]),
+        viewport: Viewport,
+        camera: &Transform,
+    ) -> CommandBuffer {
+        let keys: Vec<*const SpriteTexture> = sprite_batches
+            .iter()
+            .map(|batch| batch.texture as *const SpriteTexture)
+            .collect();
+        self.draw_ordered(
+            group_order(&keys)
+                .into_iter()
+                .map(|i| &sprite_batches[i])
+                .collect(),
+            device,
+            queue,
+            target,
+            viewport,
+            camera,
+        )
+    }
 
+    fn write_uniforms(&self, queue: &Queue, viewport: Viewport, camera: &Transform) {
         // this doesn't need to write every frame, but I don't want to overcomplicate things
         queue.write_buffer(
             &self.window_buffer,
             0,
-            bytemuck::cast_slice(&[WindowUnifrom { size: window_size }]),
+            bytemuck::cast_slice(&[WindowUnifrom {
+                size: [viewport.width, viewport.height],
+            }]),
+        );
+        queue.write_buffer(
+            &self.camera_buffer,
+            0,
+            bytemuck::cast_slice(&[CameraUniform::from(camera)]),
         );
+    }
 
-        let mut render_pass = encoder.begin_render_pass(&RenderPassDescriptor {
-            label: Some("Sprite Render Pass"),
-            color_attachments: &[Some(RenderPassColorAttachment {
-                view,
-                resolve_target: None,
-                ops: Operations {
-                    load: LoadOp::Load,
-                    store: true,
-                },
-            })],
-            depth_stencil_attachment: None,
+    fn draw_ordered(
+        &mut self,
+        sprite_batches: Vec<&SpriteBatch>,
+        device: &Device,
+        queue: &Queue,
+        (view, view_size): (&TextureView, [u32; 2]),
+        viewport: Viewport,
+        camera: &Transform,
+    ) -> CommandBuffer {
+        let mut encoder = device.create_command_encoder(&CommandEncoderDescriptor {
+            label: Some("Sprite Command Encoder"),
         });
+        self.resize_depth(device, view_size);
+
+        self.write_uniforms(queue, viewport, camera);
+
+        for batch in sprite_batches.iter() {
+            self.pipelines.entry(batch.blend_mode).or_insert_with(|| {
+                create_pipeline(
+                    device,
+                    &self.shader,
+                    &self.pipeline_layout,
+                    self.format,
+                    batch.blend_mode,
+                    ("vs_main", &[Vertex::descriptor()]),
+                )
+            });
+        }
 
         let mut vertices = Vec::<Vertex>::new();
 
@@ -480,26 +1048,58 @@ impl SpriteRenderer {
                 vertices.push(sprite_vertices[3]);
             }
         }
-        let sprite_count = vertices.len() as u16 / 4;
+        let sprite_count = (vertices.len() / 4) as u32;
 
-        if self.length < sprite_count {
-            self.resize(sprite_count, device);
-        }
+        self.resize(sprite_count, device);
+
+        let mut render_pass = encoder.begin_render_pass(&RenderPassDescriptor {
+            label: Some("Sprite Render Pass"),
+            color_attachments: &[Some(RenderPassColorAttachment {
+                view,
+                resolve_target: None,
+                ops: Operations {
+                    load: LoadOp::Load,
+                    store: true,
+                },
+            })],
+            depth_stencil_attachment: Some(RenderPassDepthStencilAttachment {
+                view: &self.depth_view,
+                depth_ops: Some(Operations {
+                    load: LoadOp::Clear(0.0),
+                    store: false,
+                }),
+                stencil_ops: None,
+            }),
+        });
 
-        render_pass.set_pipeline(&self.pipeline);
+        viewport.apply(&mut render_pass);
         render_pass.set_bind_group(1, &self.window_bind_group, &[]);
+        render_pass.set_bind_group(2, &self.camera_bind_group, &[]);
 
         // can only write to buffer once a frame
         queue.write_buffer(&self.vertex_buffer, 0, bytemuck::cast_slice(&vertices));
 
         render_pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
-        render_pass.set_index_buffer(self.index_buffer.slice(..), IndexFormat::Uint16);
+        render_pass.set_index_buffer(self.index_buffer.slice(..), IndexFormat::Uint32);
 
         let mut indices_offset = 0;
+        let mut bound: Option<*const SpriteTexture> = None;
+        let mut blend_mode: Option<SpriteBlendMode> = None;
         for batch in sprite_batches.iter() {
             let sprite_indices = batch.sprites.len() as u32 * 6;
 
-            render_pass.set_bind_group(0, &batch.texture.bind_group, &[]);
+            let Some([x, y, width, height]) = clip_scissor(batch.clip, viewport.scissor_rect())
+            else {
+                indices_offset += sprite_indices;
+                continue;
+            };
+            render_pass.set_scissor_rect(x, y, width, height);
+            if changed(&mut blend_mode, batch.blend_mode) {
+                render_pass.set_pipeline(&self.pipelines[&batch.blend_mode]);
+            }
+            if changed(&mut bound, batch.texture as *const SpriteTexture) {
+                render_pass.set_bind_group(0, &batch.texture.bind_group, &[]);
+            }
             render_pass.draw_indexed(indices_offset..indices_offset + sprite_indices, 0, 0..1);
 
             indices_offset += sprite_indices;
@@ -509,12 +1109,674 @@ impl SpriteRenderer {
         encoder.finish()
     }
 
-    pub fn resize(&mut self, sprite_count: u16, device: &Device) {
-        if sprite_count == 0 {
+    /// makes room for at least the sprites, the buffers only grow and double when they do so
+    /// a changing number of sprites doesn't reallocate every frame
+    pub fn resize(&mut self, sprite_count: u32, device: &Device) {
+        let Some(length) = grown_length(self.length, sprite_count) else {
             return;
+        };
+        self.vertex_buffer = create_vertex_buffer(length, device);
+        self.index_buffer = create_index_buffer(length, device);
+        self.length = length;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use excali_render::test_util::headless_device;
+    use excali_render::RenderTarget;
+
+    pub(crate) fn test_config(width: u32, height: u32) -> SurfaceConfiguration {
+        SurfaceConfiguration {
+            usage: TextureUsages::RENDER_ATTACHMENT,
+            format: TextureFormat::Rgba8Unorm,
+            width,
+            height,
+            present_mode: PresentMode::Fifo,
+            alpha_mode: CompositeAlphaMode::Auto,
+            view_formats: vec![],
+        }
+    }
+
+    /// a target drawn to like the test config's surface, cleared to the color
+    pub(crate) fn cleared_target(
+        device: &Device,
+        queue: &Queue,
+        width: u32,
+        height: u32,
+        color: excali_render::wgpu::Color,
+    ) -> RenderTarget {
+        let target = RenderTarget::new(device, width, height, TextureFormat::Rgba8Unorm, "test");
+        let mut encoder = device.create_command_encoder(&CommandEncoderDescriptor::default());
+        encoder.begin_render_pass(&RenderPassDescriptor {
+            label: Some("test_clear"),
+            color_attachments: &[Some(RenderPassColorAttachment {
+                view: &target.view,
+                resolve_target: None,
+                ops: Operations {
+                    load: LoadOp::Clear(color),
+                    store: true,
+                },
+            })],
+            depth_stencil_attachment: None,
+        });
+        queue.submit(Some(encoder.finish()));
+        target
+    }
+
+    pub(crate) fn test_texture(
+        sprite_renderer: &SpriteRenderer,
+        device: &Device,
+        queue: &Queue,
+        sampler: &Sampler,
+        rgba: &[u8],
+        dimensions: (u32, u32),
+    ) -> SpriteTexture {
+        let data =
+            excali_render::Texture::from_rgba(device, queue, rgba, dimensions, "test".into());
+        SpriteTexture {
+            bind_group: sprite_renderer.create_bind_group(device, sampler, &data),
+            data,
+        }
+    }
+
+    #[test]
+    fn inverse_undoes_transform() {
+        let transform = Transform {
+            position: Vector2::new(10.0, -4.0),
+            rotation: 0.5,
+            scale: Vector2::new(2.0, 2.0),
+        };
+        let point = Transform::from_position(Vector2::new(3.0, 7.0));
+        let result = &transform.inverse() * &(&transform * &point);
+        assert!((result.position - point.position).magnitude() < 1e-4);
+        assert!(result.rotation.abs() < 1e-6);
+    }
+
+    #[test]
+    fn inverse_composes_to_identity() {
+        let transform = Transform {
+            position: Vector2::new(-3.0, 8.0),
+            rotation: 2.0,
+            scale: Vector2::new(0.5, 0.5),
+        };
+        let identity = &transform * &transform.inverse();
+        assert!(identity.position.magnitude() < 1e-5);
+        assert!(identity.rotation.abs() < 1e-6);
+        assert!((identity.scale - Vector2::new(1.0, 1.0)).magnitude() < 1e-6);
+    }
+
+    #[test]
+    fn point_round_trips_through_transform() {
+        let transform = Transform {
+            position: Vector2::new(10.0, -4.0),
+            rotation: 0.7,
+            scale: Vector2::new(2.0, 3.0),
+        };
+        let point = Vector2::new(3.0, 7.0);
+        let moved = transform.transform_point(point);
+        let expected = (&transform * &Transform::from_position(point)).position;
+        assert!((moved - expected).magnitude() < 1e-5);
+        assert!((transform.inverse_transform_point(moved) - point).magnitude() < 1e-5);
+    }
+
+    #[test]
+    fn fit_transform_frames_sprites() {
+        let sprites = [
+            Sprite {
+                transform: Transform::from_position(Vector2::new(-100.0, 0.0)),
+                ..Default::default()
+            },
+            Sprite {
+                transform: Transform::from_position(Vector2::new(300.0, 50.0)),
+                ..Default::default()
+            },
+        ];
+        let camera = fit_transform(
+            sprites.iter().map(|sprite| sprite.aabb(10, 10)),
+            Vector2::new(200.0, 200.0),
+            10.0,
+        );
+        // 410 wide fit into 180 pixels
+        assert!((camera.scale.x - 180.0 / 410.0).abs() < 1e-5);
+        let center = &camera * &Transform::from_position(Vector2::new(100.0, 25.0));
+        assert!(center.position.magnitude() < 1e-4);
+    }
+
+    #[test]
+    fn point_inside_sprite_is_contained() {
+        let sprite = Sprite {
+            transform: Transform {
+                position: Vector2::new(100.0, 50.0),
+                scale: Vector2::new(2.0, 1.0),
+                ..Default::default()
+            },
+            texture_coordinate: TextureCoordinate {
+                width: 0.5,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        // 16 by 16 across the texture, half of it 2 times as wide
+        assert!(sprite.contains_point(Vector2::new(107.0, 57.0), 16, 16));
+        assert!(!sprite.contains_point(Vector2::new(108.5, 58.5), 16, 16));
+        assert!(!sprite.contains_point(Vector2::new(100.0, 59.0), 16, 16));
+    }
+
+    #[test]
+    fn rotated_sprite_is_hit_by_its_quad() {
+        let sprite = Sprite {
+            transform: Transform {
+                rotation: std::f32::consts::FRAC_PI_4,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        // the corner of the unrotated quad is outside the diamond
+        assert!(!sprite.contains_point(Vector2::new(4.5, 4.5), 10, 10));
+        // but the diamond reaches further along the axes
+        assert!(sprite.contains_point(Vector2::new(6.5, 0.0), 10, 10));
+        let [corner, ..] = sprite.vertices(10, 10);
+        let corner = Vector2::from(corner.position) * 0.99;
+        assert!(sprite.contains_point(corner, 10, 10));
+        assert!(!sprite.contains_point(corner * 1.03, 10, 10));
+    }
+
+    #[test]
+    fn buffers_double_and_never_shrink() {
+        assert_eq!(grown_length(STARTING_LENGTH, 17), Some(32));
+        assert_eq!(grown_length(32, 100), Some(128));
+        assert_eq!(grown_length(32, 32), None);
+        assert_eq!(grown_length(32, 3), None);
+        assert_eq!(grown_length(32, 0), None);
+    }
+
+    #[test]
+    fn flip_x_swaps_tex_coords() {
+        let sprite = Sprite::default();
+        let flipped = Sprite {
+            flip_x: true,
+            ..Default::default()
+        };
+        let vertices = sprite.vertices(4, 4);
+        let flipped_vertices = flipped.vertices(4, 4);
+        assert_eq!(flipped_vertices[0].tex_coords, vertices[1].tex_coords);
+        assert_eq!(flipped_vertices[1].tex_coords, vertices[0].tex_coords);
+        for (vertex, flipped_vertex) in vertices.iter().zip(flipped_vertices.iter()) {
+            assert_eq!(vertex.position, flipped_vertex.position);
+        }
+    }
+
+    #[test]
+    fn higher_sprite_is_nearer() {
+        let sprite = Sprite {
+            z: 0.75,
+            ..Default::default()
+        };
+        assert!(Sprite::default()
+            .vertices(4, 4)
+            .iter()
+            .all(|vertex| vertex.depth == 0.0));
+        assert!(sprite
+            .vertices(4, 4)
+            .iter()
+            .all(|vertex| vertex.depth == 0.75));
+        let clamped = Sprite { z: 3.0, ..sprite };
+        assert_eq!(clamped.vertices(4, 4)[0].depth, 1.0);
+    }
+
+    #[test]
+    fn flipped_coordinates_swap_corners() {
+        let coordinate = TextureCoordinate {
+            x: 0.25,
+            y: 0.5,
+            width: 0.25,
+            height: 0.5,
+        };
+        let flipped = coordinate.flipped_x();
+        assert_eq!(flipped.bottom_left(), coordinate.bottom_right());
+        assert_eq!(flipped.bottom_right(), coordinate.bottom_left());
+        assert_eq!(flipped.top_left(), coordinate.top_right());
+        assert_eq!(flipped.top_right(), coordinate.top_left());
+        let flipped = coordinate.flipped_y();
+        assert_eq!(flipped.bottom_left(), coordinate.top_left());
+        assert_eq!(flipped.top_right(), coordinate.bottom_right());
+
+        // the geometry and so the winding is unchanged
+        let sprite = Sprite {
+            texture_coordinate: coordinate,
+            ..Default::default()
+        };
+        let mirrored = Sprite {
+            texture_coordinate: coordinate.flipped_x(),
+            ..sprite
+        };
+        let (vertices, mirrored_vertices) = (sprite.vertices(8, 8), mirrored.vertices(8, 8));
+        for (vertex, mirrored_vertex) in vertices.iter().zip(mirrored_vertices.iter()) {
+            assert_eq!(vertex.position, mirrored_vertex.position);
+        }
+        let flipped = sprite.flip_h().vertices(8, 8);
+        for (flipped_vertex, mirrored_vertex) in flipped.iter().zip(mirrored_vertices.iter()) {
+            assert_eq!(flipped_vertex.tex_coords, mirrored_vertex.tex_coords);
+        }
+    }
+
+    #[test]
+    fn premultiplied_edges_avoid_dark_fringe() {
+        let Some((device, queue)) = headless_device(Features::empty()) else {
+            return;
+        };
+        let mut sprite_renderer = SpriteRenderer::new(&test_config(8, 8), &device, 8.0, 8.0);
+        let sampler = device.create_sampler(&SamplerDescriptor {
+            mag_filter: FilterMode::Linear,
+            min_filter: FilterMode::Linear,
+            ..Default::default()
+        });
+        // a white texel next to a transparent black one
+        let mut texels = [255, 255, 255, 255, 0, 0, 0, 0];
+        let straight = test_texture(&sprite_renderer, &device, &queue, &sampler, &texels, (2, 1));
+        excali_render::premultiply_alpha(&mut texels);
+        let premultiplied =
+            test_texture(&sprite_renderer, &device, &queue, &sampler, &texels, (2, 1));
+
+        // samples halfway between the texels over a white background
+        let mut edge_over_white = |texture: &SpriteTexture, blend_mode| {
+            let target = cleared_target(&device, &queue, 8, 8, excali_render::wgpu::Color::WHITE);
+            let batch = SpriteBatch {
+                sprites: vec![Sprite {
+                    transform: Transform {
+                        scale: Vector2::new(100.0, 4.0),
+                        ..Default::default()
+                    },
+                    texture_coordinate: TextureCoordinate {
+                        x: 0.49,
+                        y: 0.0,
+                        width: 0.02,
+                        height: 1.0,
+                    },
+                    ..Default::default()
+                }],
+                texture,
+                blend_mode,
+                clip: None,
+            };
+            let commands = sprite_renderer.draw(
+                &[batch],
+                &device,
+                &queue,
+                (&target.view, [8, 8]),
+                Viewport::full(&test_config(8, 8)),
+                &Transform::default(),
+            );
+            queue.submit(Some(commands));
+            let pixels = target.read(&device, &queue).unwrap();
+            pixels[(4 * 8 + 4) * 4]
+        };
+
+        assert!(edge_over_white(&straight, SpriteBlendMode::Alpha) < 230);
+        assert!(edge_over_white(&premultiplied, SpriteBlendMode::PremultipliedAlpha) > 250);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn draw_with_matches_explicit_draw() {
+        use excali_render::test_util::windowed_renderer;
+        let Some((_event_loop, mut renderer)) = windowed_renderer(Default::default()) else {
+            return;
+        };
+        let mut sprite_renderer = SpriteRenderer::from_renderer(&renderer);
+        let sampler = renderer.pixel_art_sampler();
+        let texture = test_texture(
+            &sprite_renderer,
+            &renderer.device,
+            &renderer.queue,
+            &sampler,
+            &[255, 0, 0, 255, 0, 0, 255, 255],
+            (2, 1),
+        );
+        let batch = SpriteBatch {
+            sprites: vec![Sprite {
+                transform: Transform {
+                    position: Vector2::new(-3.0, 1.0),
+                    scale: Vector2::new(2.0, 3.0),
+                    rotation: 0.3,
+                },
+                ..Default::default()
+            }],
+            texture: &texture,
+            blend_mode: SpriteBlendMode::Alpha,
+            clip: None,
+        };
+        // drawn like the window's surface would be
+        let size = [renderer.config.width, renderer.config.height];
+        let [convenient, explicit] = ["convenient", "explicit"].map(|label| {
+            let target = RenderTarget::new(
+                &renderer.device,
+                size[0],
+                size[1],
+                renderer.config.format,
+                label,
+            );
+            let clear = renderer.clear(&target.view, excali_render::wgpu::Color::BLACK);
+            renderer.queue.submit(Some(clear));
+            target
+        });
+
+        let commands =
+            sprite_renderer.draw_with(&renderer, &convenient.view, std::slice::from_ref(&batch));
+        renderer.queue.submit(Some(commands));
+        let commands = sprite_renderer.draw(
+            &[batch],
+            &renderer.device,
+            &renderer.queue,
+            (&explicit.view, size),
+            Viewport::full(&renderer.config),
+            &Transform::default(),
+        );
+        renderer.queue.submit(Some(commands));
+
+        let read = |target: &RenderTarget| target.read(&renderer.device, &renderer.queue).unwrap();
+        let pixels = read(&convenient);
+        assert!(pixels.chunks_exact(4).any(|pixel| pixel[0] > 0));
+        assert_eq!(pixels, read(&explicit));
+    }
+
+    #[test]
+    fn split_viewports_draw_side_by_side() {
+        let Some((device, queue)) = headless_device(Features::empty()) else {
+            return;
+        };
+        let config = test_config(16, 8);
+        let mut sprite_renderer = SpriteRenderer::new(&config, &device, 16.0, 8.0);
+        let sampler = device.create_sampler(&SamplerDescriptor::default());
+        let white = test_texture(
+            &sprite_renderer,
+            &device,
+            &queue,
+            &sampler,
+            &[255; 4],
+            (1, 1),
+        );
+        let target = cleared_target(&device, &queue, 16, 8, excali_render::wgpu::Color::BLACK);
+        let (left, right) = Viewport::full(&config).split_horizontal(0.5);
+
+        // a 2 pixel square at the center of each half
+        for (viewport, color) in [
+            (left, Color::new(1.0, 0.0, 0.0, 1.0)),
+            (right, Color::new(0.0, 0.0, 1.0, 1.0)),
+        ] {
+            let batch = SpriteBatch {
+                sprites: vec![Sprite {
+                    transform: Transform {
+                        scale: Vector2::new(2.0, 2.0),
+                        ..Default::default()
+                    },
+                    color,
+                    ..Default::default()
+                }],
+                texture: &white,
+                blend_mode: SpriteBlendMode::Alpha,
+                clip: None,
+            };
+            let commands = sprite_renderer.draw(
+                &[batch],
+                &device,
+                &queue,
+                (&target.view, [16, 8]),
+                viewport,
+                &Transform::default(),
+            );
+            queue.submit(Some(commands));
+        }
+
+        let pixels = target.read(&device, &queue).unwrap();
+        let pixel = |x: usize, y: usize| &pixels[(y * 16 + x) * 4..(y * 16 + x) * 4 + 3];
+        assert_eq!(pixel(4, 4), [255, 0, 0]);
+        assert_eq!(pixel(12, 4), [0, 0, 255]);
+        assert_eq!(pixel(8, 4), [0, 0, 0]);
+        assert_eq!(pixel(0, 0), [0, 0, 0]);
+    }
+
+    #[test]
+    fn transparent_texels_dont_hide_sprites_behind() {
+        let Some((device, queue)) = headless_device(Features::empty()) else {
+            return;
+        };
+        let config = test_config(8, 8);
+        // the depth texture is resized to the view when drawing
+        let mut sprite_renderer = SpriteRenderer::new(&test_config(2, 2), &device, 8.0, 8.0);
+        let sampler = device.create_sampler(&SamplerDescriptor::default());
+        let clear = test_texture(&sprite_renderer, &device, &queue, &sampler, &[0; 4], (1, 1));
+        let white = test_texture(
+            &sprite_renderer,
+            &device,
+            &queue,
+            &sampler,
+            &[255; 4],
+            (1, 1),
+        );
+        let square = |z: f32, color: Color| Sprite {
+            transform: Transform {
+                scale: Vector2::new(4.0, 4.0),
+                ..Default::default()
+            },
+            z,
+            color,
+            ..Default::default()
+        };
+        // the nearer sprite is drawn first, so the one behind fails the depth test under it
+        let batches = [
+            (&clear, square(1.0, Color::new(1.0, 1.0, 1.0, 1.0))),
+            (&white, square(0.0, Color::new(1.0, 0.0, 0.0, 1.0))),
+        ]
+        .map(|(texture, sprite)| SpriteBatch {
+            sprites: vec![sprite],
+            texture,
+            blend_mode: SpriteBlendMode::Alpha,
+            clip: None,
+        });
+
+        let target = cleared_target(&device, &queue, 8, 8, excali_render::wgpu::Color::BLACK);
+        let commands = sprite_renderer.draw(
+            &batches,
+            &device,
+            &queue,
+            (&target.view, [8, 8]),
+            Viewport::full(&config),
+            &Transform::default(),
+        );
+        queue.submit(Some(commands));
+        let pixels = target.read(&device, &queue).unwrap();
+        assert_eq!(pixels[(4 * 8 + 4) * 4..(4 * 8 + 4) * 4 + 3], [255, 0, 0]);
+    }
+
+    #[test]
+    fn camera_matrix_matches_transform() {
+        let camera = Transform {
+            position: Vector2::new(40.0, -15.0),
+            rotation: 0.3,
+            scale: Vector2::new(2.0, 2.0),
+        };
+        let point = Vector2::new(3.0, 7.0);
+        let moved = camera.matrix() * Vector3::new(point.x, point.y, 1.0);
+        let expected = (&camera * &Transform::from_position(point)).position;
+        assert!((moved.xy() - expected).magnitude() < 1e-4);
+        assert_eq!(Transform::default().matrix(), Matrix3::identity());
+    }
+
+    #[test]
+    fn indices_reach_past_u16() {
+        let sprites = 20_000;
+        let indices = indices(sprites);
+        assert_eq!(indices.len(), sprites as usize * 6);
+        let last = (sprites - 1) * 4;
+        assert_eq!(
+            indices[indices.len() - 6..],
+            [last, last + 1, last + 2, last + 2, last + 3, last]
+        );
+    }
+
+    #[test]
+    fn corner_pivot_stays_fixed() {
+        let position = Vector2::new(3.0, -2.0);
+        let sprite = Sprite {
+            transform: Transform {
+                position,
+                rotation: std::f32::consts::FRAC_PI_2,
+                scale: Vector2::new(2.0, 1.0),
+            },
+            pivot: Vector2::zeros(),
+            ..Default::default()
+        };
+        let vertices = sprite.vertices(4, 6);
+        let bottom_left = Vector2::from(vertices[0].position);
+        assert!((bottom_left - position).magnitude() < 1e-5);
+        // the top right swings a quarter turn clockwise around the pivot
+        let top_right = Vector2::from(vertices[2].position);
+        assert!((top_right - (position + Vector2::new(6.0, -8.0))).magnitude() < 1e-4);
+
+        let (min, max) = sprite.aabb(4, 6);
+        assert!((min - Vector2::new(3.0, -10.0)).magnitude() < 1e-4);
+        assert!((max - Vector2::new(9.0, -2.0)).magnitude() < 1e-4);
+    }
+
+    #[test]
+    fn aabb_of_unrotated_sprite() {
+        let sprite = Sprite {
+            transform: Transform {
+                position: Vector2::new(5.0, 5.0),
+                rotation: 0.0,
+                scale: Vector2::new(2.0, 1.0),
+            },
+            ..Default::default()
+        };
+        let (min, max) = sprite.aabb(10, 4);
+        assert_eq!(min, Vector2::new(-5.0, 3.0));
+        assert_eq!(max, Vector2::new(15.0, 7.0));
+    }
+
+    #[test]
+    fn aabb_of_rotated_sprite_expands() {
+        let sprite = Sprite {
+            transform: Transform::from_rotation(std::f32::consts::FRAC_PI_4),
+            ..Default::default()
+        };
+        let (min, max) = sprite.aabb(2, 2);
+        let corner = std::f32::consts::SQRT_2;
+        assert!((max - Vector2::new(corner, corner)).magnitude() < 1e-5);
+        assert!((min + Vector2::new(corner, corner)).magnitude() < 1e-5);
+    }
+
+    #[test]
+    fn clip_is_clamped_to_viewport() {
+        let window = [0, 0, 800, 600];
+        assert_eq!(clip_scissor(None, window), Some(window));
+        assert_eq!(
+            clip_scissor(Some([700, 500, 300, 300]), window),
+            Some([700, 500, 100, 100])
+        );
+        assert_eq!(
+            clip_scissor(Some([10, 20, 30, 40]), window),
+            Some([10, 20, 30, 40])
+        );
+        assert_eq!(clip_scissor(Some([900, 0, 50, 50]), window), None);
+        assert_eq!(clip_scissor(Some([10, 10, 0, 50]), window), None);
+        assert_eq!(
+            clip_scissor(Some([0, 0, u32::MAX, u32::MAX]), [400, 0, 400, 600]),
+            Some([400, 0, 400, 600])
+        );
+    }
+
+    #[test]
+    fn blend_modes_switch_pipelines() {
+        let Some((device, queue)) = headless_device(Features::empty()) else {
+            return;
+        };
+        let config = test_config(16, 8);
+        let mut sprite_renderer = SpriteRenderer::new(&config, &device, 16.0, 8.0);
+        let sampler = device.create_sampler(&SamplerDescriptor::default());
+        let white = test_texture(
+            &sprite_renderer,
+            &device,
+            &queue,
+            &sampler,
+            &[255; 4],
+            (1, 1),
+        );
+        let gray = excali_render::wgpu::Color {
+            r: 0.2,
+            g: 0.2,
+            b: 0.2,
+            a: 1.0,
+        };
+        let target = cleared_target(&device, &queue, 16, 8, gray);
+
+        // a 2 pixel square at the center, left and right of the view, each in its own batch
+        let color = Color::new(0.5, 0.5, 0.5, 0.5);
+        let modes = [
+            (-4.0, SpriteBlendMode::Additive),
+            (4.0, SpriteBlendMode::Opaque),
+            (0.0, SpriteBlendMode::Additive),
+        ];
+        let batches: Vec<SpriteBatch> = modes
+            .iter()
+            .map(|(x, blend_mode)| SpriteBatch {
+                sprites: vec![Sprite {
+                    transform: Transform {
+                        position: Vector2::new(*x, 0.0),
+                        scale: Vector2::new(2.0, 2.0),
+                        ..Default::default()
+                    },
+                    color,
+                    ..Default::default()
+                }],
+                texture: &white,
+                blend_mode: *blend_mode,
+                clip: None,
+            })
+            .collect();
+        let commands = sprite_renderer.draw(
+            &batches,
+            &device,
+            &queue,
+            (&target.view, [16, 8]),
+            Viewport::full(&config),
+            &Transform::default(),
+        );
+        queue.submit(Some(commands));
+        assert_eq!(sprite_renderer.pipelines.len(), 2);
+
+        let pixels = target.read(&device, &queue).unwrap();
+        let background = Color::new(0.2, 0.2, 0.2, 1.0);
+        for (x, blend_mode) in modes {
+            let column = (x + 8.0) as usize;
+            let pixel = (4 * 16 + column) * 4;
+            let expected = blend_mode.blend(color, background) * 255.0;
+            for (channel, expected) in pixels[pixel..pixel + 4].iter().zip(expected.iter()) {
+                assert!(
+                    (*channel as f32 - expected).abs() <= 1.0,
+                    "{blend_mode:?} drew {:?}",
+                    &pixels[pixel..pixel + 4]
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn interleaved_textures_bind_once_each() {
+        // the number of times the texture changes, each one is a bind
+        fn binds(keys: &[u8]) -> usize {
+            keys.iter()
+                .enumerate()
+                .filter(|(i, key)| *i == 0 || keys[i - 1] != **key)
+                .count()
         }
-        self.vertex_buffer = create_vertex_buffer(sprite_count, device);
-        self.index_buffer = create_index_buffer(sprite_count, device);
-        self.length = sprite_count;
+        let keys = [1u8, 2, 1];
+        assert_eq!(binds(&keys), 3);
+        let order = group_order(&keys);
+        assert_eq!(order, vec![0, 2, 1]);
+        let sorted: Vec<u8> = order.iter().map(|i| keys[*i]).collect();
+        assert_eq!(binds(&sorted), 2);
     }
 }
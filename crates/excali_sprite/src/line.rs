@@ -0,0 +1,251 @@
+use excali_render::wgpu::util::DeviceExt;
+use excali_render::wgpu::*;
+use nalgebra::Vector2;
+
+use super::{indices, Color, WindowUnifrom, STARTING_LENGTH};
+
+/// start, end, color and width in pixels
+pub type Segment = (Vector2<f32>, Vector2<f32>, Color, f32);
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct LineVertex {
+    position: [f32; 2],
+    color: [f32; 4],
+    /// distance across the line from -1 to 1 and the half width, for anti-aliasing the edges
+    edge: [f32; 2],
+}
+
+impl LineVertex {
+    fn descriptor<'a>() -> VertexBufferLayout<'a> {
+        VertexBufferLayout {
+            array_stride: std::mem::size_of::<LineVertex>() as BufferAddress,
+            step_mode: VertexStepMode::Vertex,
+            attributes: &[
+                VertexAttribute {
+                    offset: 0,
+                    shader_location: 0,
+                    format: VertexFormat::Float32x2,
+                },
+                VertexAttribute {
+                    offset: std::mem::size_of::<[f32; 2]>() as BufferAddress,
+                    shader_location: 1,
+                    format: VertexFormat::Float32x4,
+                },
+                VertexAttribute {
+                    offset: std::mem::size_of::<[f32; 6]>() as BufferAddress,
+                    shader_location: 2,
+                    format: VertexFormat::Float32x2,
+                },
+            ],
+        }
+    }
+}
+
+/// a quad along the segment, counter clockwise like sprites
+fn segment_vertices(segment: &Segment) -> [LineVertex; 4] {
+    let (start, end, color, width) = *segment;
+    let direction = (end - start)
+        .try_normalize(f32::EPSILON)
+        .unwrap_or_default();
+    let half_width = width / 2.0;
+    let normal = Vector2::new(-direction.y, direction.x) * half_width;
+    let color = [color.x, color.y, color.z, color.w];
+    let vertex = |position: Vector2<f32>, edge: f32| LineVertex {
+        position: [position.x, position.y],
+        color,
+        edge: [edge, half_width],
+    };
+    [
+        vertex(start - normal, -1.0),
+        vertex(end - normal, -1.0),
+        vertex(end + normal, 1.0),
+        vertex(start + normal, 1.0),
+    ]
+}
+
+fn create_vertex_buffer(segment_count: u32, device: &Device) -> Buffer {
+    device.create_buffer(&BufferDescriptor {
+        label: Some("Line 2D Vertex Buffer"),
+        size: (std::mem::size_of::<LineVertex>() * segment_count as usize * 4) as BufferAddress,
+        usage: BufferUsages::VERTEX | BufferUsages::COPY_DST,
+        mapped_at_creation: false,
+    })
+}
+
+fn create_index_buffer(segment_count: u32, device: &Device) -> Buffer {
+    device.create_buffer_init(&util::BufferInitDescriptor {
+        label: Some("Line 2D Index Buffer"),
+        contents: bytemuck::cast_slice(&indices(segment_count)),
+        usage: BufferUsages::INDEX | BufferUsages::COPY_DST,
+    })
+}
+
+/// Draws screen space line segments as untextured quads
+pub struct LineRenderer2D {
+    vertex_buffer: Buffer,
+    index_buffer: Buffer,
+    window_buffer: Buffer,
+    window_bind_group: BindGroup,
+    pipeline: RenderPipeline,
+    length: u32,
+}
+
+impl LineRenderer2D {
+    pub fn new(config: &SurfaceConfiguration, device: &Device) -> Self {
+        let shader = device.create_shader_module(include_wgsl!("line.wgsl"));
+
+        let window_bind_group_layout =
+            device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+                entries: &[BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: ShaderStages::VERTEX,
+                    ty: BindingType::Buffer {
+                        ty: BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                }],
+                label: Some("line_2d_window_bind_group_layout"),
+            });
+
+        let window_buffer = device.create_buffer_init(&util::BufferInitDescriptor {
+            label: Some("Line 2D Window Buffer"),
+            contents: bytemuck::cast_slice(&[WindowUnifrom {
+                size: [config.width as f32, config.height as f32],
+            }]),
+            usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
+        });
+
+        let window_bind_group = device.create_bind_group(&BindGroupDescriptor {
+            layout: &window_bind_group_layout,
+            entries: &[BindGroupEntry {
+                binding: 0,
+                resource: window_buffer.as_entire_binding(),
+            }],
+            label: Some("line_2d_window_bind_group"),
+        });
+
+        let pipeline_layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
+            label: Some("Line 2D Pipeline Layout"),
+            bind_group_layouts: &[&window_bind_group_layout],
+            push_constant_ranges: &[],
+        });
+
+        let pipeline = device.create_render_pipeline(&RenderPipelineDescriptor {
+            label: Some("Line 2D Pipeline"),
+            layout: Some(&pipeline_layout),
+            vertex: VertexState {
+                module: &shader,
+                entry_point: "vs_main",
+                buffers: &[LineVertex::descriptor()],
+            },
+            fragment: Some(FragmentState {
+                module: &shader,
+                entry_point: "fs_main",
+                targets: &[Some(ColorTargetState {
+                    format: config.format,
+                    blend: Some(BlendState::ALPHA_BLENDING),
+                    write_mask: ColorWrites::ALL,
+                })],
+            }),
+            primitive: PrimitiveState {
+                topology: PrimitiveTopology::TriangleList,
+                strip_index_format: None,
+                front_face: FrontFace::Ccw,
+                cull_mode: None,
+                polygon_mode: PolygonMode::Fill,
+                unclipped_depth: false,
+                conservative: false,
+            },
+            depth_stencil: None,
+            multisample: MultisampleState::default(),
+            multiview: None,
+        });
+
+        Self {
+            vertex_buffer: create_vertex_buffer(STARTING_LENGTH, device),
+            index_buffer: create_index_buffer(STARTING_LENGTH, device),
+            window_buffer,
+            window_bind_group,
+            pipeline,
+            length: STARTING_LENGTH,
+        }
+    }
+
+    pub fn draw(
+        &mut self,
+        segments: &[Segment],
+        device: &Device,
+        queue: &Queue,
+        view: &TextureView,
+        window_size: [f32; 2],
+    ) -> CommandBuffer {
+        let mut encoder = device.create_command_encoder(&CommandEncoderDescriptor {
+            label: Some("Line 2D Command Encoder"),
+        });
+
+        queue.write_buffer(
+            &self.window_buffer,
+            0,
+            bytemuck::cast_slice(&[WindowUnifrom { size: window_size }]),
+        );
+
+        let segment_count = segments.len() as u32;
+        if self.length < segment_count {
+            self.vertex_buffer = create_vertex_buffer(segment_count, device);
+            self.index_buffer = create_index_buffer(segment_count, device);
+            self.length = segment_count;
+        }
+
+        let vertices: Vec<LineVertex> = segments.iter().flat_map(segment_vertices).collect();
+        queue.write_buffer(&self.vertex_buffer, 0, bytemuck::cast_slice(&vertices));
+
+        let mut render_pass = encoder.begin_render_pass(&RenderPassDescriptor {
+            label: Some("Line 2D Render Pass"),
+            color_attachments: &[Some(RenderPassColorAttachment {
+                view,
+                resolve_target: None,
+                ops: Operations {
+                    load: LoadOp::Load,
+                    store: true,
+                },
+            })],
+            depth_stencil_attachment: None,
+        });
+
+        if segment_count > 0 {
+            render_pass.set_pipeline(&self.pipeline);
+            render_pass.set_bind_group(0, &self.window_bind_group, &[]);
+            render_pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
+            render_pass.set_index_buffer(self.index_buffer.slice(..), IndexFormat::Uint32);
+            render_pass.draw_indexed(0..segment_count * 6, 0, 0..1);
+        }
+
+        drop(render_pass);
+        encoder.finish()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn segment_is_one_quad_along_it() {
+        let segment: Segment = (
+            Vector2::new(0.0, 0.0),
+            Vector2::new(10.0, 0.0),
+            Color::new(1.0, 0.0, 0.0, 1.0),
+            4.0,
+        );
+        let vertices = segment_vertices(&segment);
+        assert_eq!(indices(1).len(), 6);
+        assert_eq!(vertices[0].position, [0.0, -2.0]);
+        assert_eq!(vertices[1].position, [10.0, -2.0]);
+        assert_eq!(vertices[2].position, [10.0, 2.0]);
+        assert_eq!(vertices[3].position, [0.0, 2.0]);
+        assert_eq!(vertices[2].color, [1.0, 0.0, 0.0, 1.0]);
+    }
+}
@@ -0,0 +1,39 @@
+// Vertex shader
+struct WindowUniform {
+    size: vec2<f32>,
+};
+
+@group(0) @binding(0)
+var<uniform> window: WindowUniform;
+
+struct VertexInput {
+    @location(0) position: vec2<f32>,
+	@location(1) color: vec4<f32>,
+	@location(2) edge: vec2<f32>,
+};
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+	@location(0) color: vec4<f32>,
+	@location(1) edge: vec2<f32>,
+};
+
+@vertex
+fn vs_main(
+	model: VertexInput,
+) -> VertexOutput {
+    var out: VertexOutput;
+    out.clip_position = vec4<f32>((model.position + window.size / 2.0) / window.size * 2.0 - 1.0, 0.5, 1.0);
+	out.color = model.color;
+	out.edge = model.edge;
+    return out;
+}
+
+// Fragment shader
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+	// pixels from the nearest edge, fading the outermost pixel
+	let distance = (1.0 - abs(in.edge.x)) * in.edge.y;
+	return vec4<f32>(in.color.rgb, in.color.a * clamp(distance, 0.0, 1.0));
+}
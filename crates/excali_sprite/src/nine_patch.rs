@@ -0,0 +1,174 @@
+use nalgebra::Vector2;
+
+use super::{Sprite, SpriteRenderer, SpriteTexture, TextureCoordinate, Transform};
+
+/// the start and length of each third, the borders shrink evenly when they don't fit
+fn thirds(size: f32, start: f32, end: f32) -> [(f32, f32); 3] {
+    let shrink = if start + end > size {
+        size / (start + end)
+    } else {
+        1.0
+    };
+    let [start, end] = [start * shrink, end * shrink];
+    [(0.0, start), (start, size - start - end), (size - end, end)]
+}
+
+fn nine_patch_sprites(
+    width: u32,
+    height: u32,
+    transform: &Transform,
+    border_px: [f32; 4],
+) -> Vec<Sprite> {
+    let [left, right, bottom, top] = border_px;
+    let texture_size = Vector2::new(width as f32, height as f32);
+    let size = texture_size.component_mul(&transform.scale.abs());
+    let texture_columns = thirds(texture_size.x, left, right);
+    let texture_rows = thirds(texture_size.y, bottom, top);
+    let columns = thirds(size.x, left, right);
+    let rows = thirds(size.y, bottom, top);
+    // the patches are moved and turned with the panel but keep their own scale
+    let panel = Transform {
+        scale: Vector2::new(1.0, 1.0),
+        ..*transform
+    };
+
+    let mut sprites = Vec::with_capacity(9);
+    for ((texture_y, texture_height), (y, height)) in texture_rows.into_iter().zip(rows) {
+        for ((texture_x, texture_width), (x, width)) in texture_columns.into_iter().zip(columns) {
+            // stretches the patch's part of the texture over its part of the panel
+            let scale = Vector2::new(width / texture_width, height / texture_height).map(|scale| {
+                if scale.is_finite() {
+                    scale
+                } else {
+                    0.0
+                }
+            });
+            let center = Vector2::new(x + width / 2.0, y + height / 2.0) - size / 2.0;
+            sprites.push(Sprite {
+                transform: Transform {
+                    position: panel.sprite_point(center),
+                    rotation: panel.rotation,
+                    scale,
+                },
+                texture_coordinate: TextureCoordinate {
+                    x: texture_x / texture_size.x,
+                    y: texture_y / texture_size.y,
+                    width: texture_width / texture_size.x,
+                    height: texture_height / texture_size.y,
+                },
+                ..Default::default()
+            });
+        }
+    }
+    sprites
+}
+
+impl SpriteRenderer {
+    /// splits the texture into a panel of 9 sprites as big as one sprite with the transform,
+    /// the left, right, bottom and top borders in pixels keep their size while the edges and
+    /// center stretch. The borders shrink when the panel's too small for them
+    pub fn nine_patch(
+        &self,
+        texture: &SpriteTexture,
+        transform: &Transform,
+        border_px: [f32; 4],
+    ) -> Vec<Sprite> {
+        nine_patch_sprites(
+            texture.data.width,
+            texture.data.height,
+            transform,
+            border_px,
+        )
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn patches_tile_the_texture() {
+        let transform = Transform {
+            position: Vector2::new(50.0, 0.0),
+            scale: Vector2::new(10.0, 4.0),
+            ..Default::default()
+        };
+        let sprites = nine_patch_sprites(16, 16, &transform, [2.0, 4.0, 3.0, 5.0]);
+        assert_eq!(sprites.len(), 9);
+
+        let area: f32 = sprites
+            .iter()
+            .map(|sprite| sprite.texture_coordinate.width * sprite.texture_coordinate.height)
+            .sum();
+        assert!((area - 1.0).abs() < 1e-6);
+        for row in sprites.chunks_exact(3) {
+            assert_eq!(row[0].texture_coordinate.x, 0.0);
+            for pair in row.windows(2) {
+                let [a, b] = [pair[0].texture_coordinate, pair[1].texture_coordinate];
+                assert_eq!(a.x + a.width, b.x);
+                assert_eq!(a.y, b.y);
+            }
+            let last = row[2].texture_coordinate;
+            assert_eq!(last.x + last.width, 1.0);
+        }
+        let top = sprites[6].texture_coordinate;
+        assert_eq!(top.y + top.height, 1.0);
+
+        // the bottom left corner keeps its size at the panel's corner
+        let (min, max) = sprites[0].aabb(16, 16);
+        assert_eq!(max - min, Vector2::new(2.0, 3.0));
+        assert_eq!(min, Vector2::new(50.0 - 80.0, -32.0));
+    }
+
+    #[test]
+    fn borders_shrink_to_fit() {
+        let transform = Transform {
+            scale: Vector2::new(0.25, 1.0),
+            ..Default::default()
+        };
+        let sprites = nine_patch_sprites(16, 16, &transform, [4.0, 4.0, 4.0, 4.0]);
+        let widths: Vec<f32> = sprites[..3]
+            .iter()
+            .map(|sprite| {
+                let (min, max) = sprite.aabb(16, 16);
+                max.x - min.x
+            })
+            .collect();
+        assert_eq!(widths, [2.0, 0.0, 2.0]);
+    }
+
+    #[test]
+    fn rotated_patches_stay_joined() {
+        let transform = Transform {
+            position: Vector2::new(50.0, -20.0),
+            rotation: 0.7,
+            scale: Vector2::new(10.0, 4.0),
+        };
+        let sprites = nine_patch_sprites(16, 16, &transform, [2.0, 4.0, 3.0, 5.0]);
+        let vertices: Vec<_> = sprites
+            .iter()
+            .map(|sprite| sprite.vertices(16, 16))
+            .collect();
+        let joined = |a: [f32; 2], b: [f32; 2]| {
+            let offset = Vector2::from(a) - Vector2::from(b);
+            assert!(offset.norm() < 1e-3, "{a:?} {b:?}");
+        };
+        for row in 0..3 {
+            for column in 0..3 {
+                let patch = &vertices[row * 3 + column];
+                // the right edge meets the next patch's left edge
+                if column < 2 {
+                    let right = &vertices[row * 3 + column + 1];
+                    joined(patch[1].position, right[0].position);
+                    joined(patch[2].position, right[3].position);
+                }
+                // the top edge meets the bottom edge of the patch above
+                if row < 2 {
+                    let above = &vertices[(row + 1) * 3 + column];
+                    joined(patch[3].position, above[0].position);
+                    joined(patch[2].position, above[1].position);
+                }
+            }
+        }
+    }
+}
@@ -6,10 +6,19 @@ struct WindowUniform {
 @group(1) @binding(0)
 var<uniform> window: WindowUniform;
 
+// moves the sprites through the scene, the identity when there's no camera
+struct CameraUniform {
+    matrix: mat3x3<f32>,
+};
+
+@group(2) @binding(0)
+var<uniform> camera: CameraUniform;
+
 struct VertexInput {
     @location(0) position: vec2<f32>,
 	@location(1) tex_coords: vec2<f32>,
 	@location(2) color: vec4<f32>,
+	@location(3) depth: f32,
 };
 
 struct VertexOutput {
@@ -18,17 +27,39 @@ struct VertexOutput {
 	@location(1) color: vec4<f32>,
 };
 
-@vertex
-fn vs_main(
-	model: VertexInput,
-) -> VertexOutput {
+// each instance's transform as the columns of a matrix
+struct InstanceInput {
+    @location(4) matrix_0: vec3<f32>,
+    @location(5) matrix_1: vec3<f32>,
+    @location(6) matrix_2: vec3<f32>,
+};
+
+// moves the vertex from the world to the screen
+fn screen_vertex(world_position: vec2<f32>, model: VertexInput) -> VertexOutput {
     var out: VertexOutput;
-    out.clip_position = vec4<f32>((model.position + window.size / 2.0) / window.size * 2.0 - 1.0, 0.5, 1.0);
+    let position = (camera.matrix * vec3<f32>(world_position, 1.0)).xy;
+    out.clip_position = vec4<f32>((position + window.size / 2.0) / window.size * 2.0 - 1.0, model.depth, 1.0);
 	out.tex_coords = model.tex_coords;
 	out.color = model.color;
     return out;
 }
 
+@vertex
+fn vs_main(
+	model: VertexInput,
+) -> VertexOutput {
+    return screen_vertex(model.position, model);
+}
+
+@vertex
+fn vs_instanced(
+	model: VertexInput,
+	instance: InstanceInput,
+) -> VertexOutput {
+    let matrix = mat3x3<f32>(instance.matrix_0, instance.matrix_1, instance.matrix_2);
+    return screen_vertex((matrix * vec3<f32>(model.position, 1.0)).xy, model);
+}
+
 // Fragment shader
 
 @group(0) @binding(0)
@@ -38,6 +69,11 @@ var s_diffuse: sampler;
 
 @fragment
 fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
-    return textureSample(t_diffuse, s_diffuse, in.tex_coords) * in.color;
+    let color = textureSample(t_diffuse, s_diffuse, in.tex_coords) * in.color;
+    // transparent texels would write depth and hide sprites drawn behind them later
+    if (color.a < 0.01) {
+        discard;
+    }
+    return color;
 }
 
@@ -0,0 +1,117 @@
+use std::collections::HashMap;
+
+use nalgebra::Vector2;
+
+use super::{
+    Color, Sprite, SpriteBatch, SpriteBlendMode, SpriteTexture, TextureCoordinate, Transform,
+};
+
+/// A bitmap font, each glyph is a part of the texture
+pub struct FontAtlas {
+    pub texture: SpriteTexture,
+    pub glyphs: HashMap<char, TextureCoordinate>,
+    /// how far apart glyphs and lines are in pixels before the text's scale
+    pub glyph_size: Vector2<f32>,
+}
+
+impl FontAtlas {
+    pub fn new(
+        texture: SpriteTexture,
+        glyphs: HashMap<char, TextureCoordinate>,
+        glyph_size: Vector2<f32>,
+    ) -> Self {
+        Self {
+            texture,
+            glyphs,
+            glyph_size,
+        }
+    }
+
+    /// a sprite for each glyph going right from the top left of the text at the transform,
+    /// newlines start another line below and characters without a glyph are skipped
+    pub fn text_batch(&self, text: &str, transform: &Transform, color: Color) -> SpriteBatch<'_> {
+        SpriteBatch {
+            sprites: text_sprites(&self.glyphs, self.glyph_size, text, transform, color),
+            texture: &self.texture,
+            blend_mode: SpriteBlendMode::default(),
+            clip: None,
+        }
+    }
+}
+
+fn text_sprites(
+    glyphs: &HashMap<char, TextureCoordinate>,
+    glyph_size: Vector2<f32>,
+    text: &str,
+    transform: &Transform,
+    color: Color,
+) -> Vec<Sprite> {
+    let mut sprites = Vec::with_capacity(text.len());
+    for (line, text) in text.lines().enumerate() {
+        for (column, character) in text.chars().enumerate() {
+            let Some(texture_coordinate) = glyphs.get(&character) else {
+                continue;
+            };
+            let offset = Vector2::new(column as f32 * glyph_size.x, -(line as f32) * glyph_size.y);
+            sprites.push(Sprite {
+                transform: Transform {
+                    position: transform.sprite_point(offset),
+                    ..*transform
+                },
+                color,
+                texture_coordinate: *texture_coordinate,
+                pivot: Vector2::new(0.0, 1.0),
+                ..Default::default()
+            });
+        }
+    }
+    sprites
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn glyphs_advance_along_the_line() {
+        let glyphs = HashMap::from([
+            ('A', TextureCoordinate::default()),
+            ('B', TextureCoordinate::default()),
+        ]);
+        let transform = Transform {
+            position: Vector2::new(10.0, 20.0),
+            scale: Vector2::new(2.0, 2.0),
+            ..Default::default()
+        };
+        let color = Color::new(1.0, 1.0, 1.0, 1.0);
+        let sprites = text_sprites(&glyphs, Vector2::new(8.0, 12.0), "AB", &transform, color);
+        assert_eq!(sprites.len(), 2);
+        assert_eq!(sprites[0].transform.position, Vector2::new(10.0, 20.0));
+        assert_eq!(sprites[1].transform.position, Vector2::new(26.0, 20.0));
+
+        let sprites = text_sprites(&glyphs, Vector2::new(8.0, 12.0), "A?\nB", &transform, color);
+        assert_eq!(sprites.len(), 2);
+        assert_eq!(sprites[1].transform.position, Vector2::new(10.0, -4.0));
+    }
+
+    #[test]
+    fn rotated_glyphs_stay_on_the_baseline() {
+        let glyphs = HashMap::from([
+            ('A', TextureCoordinate::default()),
+            ('B', TextureCoordinate::default()),
+        ]);
+        let transform = Transform {
+            position: Vector2::new(10.0, 20.0),
+            rotation: 0.7,
+            scale: Vector2::new(2.0, 3.0),
+        };
+        let color = Color::new(1.0, 1.0, 1.0, 1.0);
+        let sprites = text_sprites(&glyphs, Vector2::new(8.0, 12.0), "AB", &transform, color);
+        let [a, b] = [&sprites[0], &sprites[1]].map(|sprite| sprite.vertices(8, 12));
+        // A's right edge is B's left edge
+        for (a, b) in [(a[1], b[0]), (a[2], b[3])] {
+            let offset = Vector2::from(a.position) - Vector2::from(b.position);
+            assert!(offset.norm() < 1e-4, "{:?} {:?}", a.position, b.position);
+        }
+    }
+}
@@ -9,23 +9,55 @@ pub struct UI {
     renderer: Renderer,
     winit_state: egui_winit::State,
     context: Context,
+    /// replaces the window's scale factor when set
+    pixels_per_point: Option<f32>,
+}
+
+/// window_size is in physical pixels, egui's points are pixels_per_point pixels
+fn screen_descriptor(
+    window_size: [u32; 2],
+    pixels_per_point: f32,
+) -> egui_wgpu::renderer::ScreenDescriptor {
+    egui_wgpu::renderer::ScreenDescriptor {
+        size_in_pixels: window_size,
+        pixels_per_point,
+    }
 }
 
 impl UI {
+    /// format is the surface's format the UI is drawn to
     pub fn new<T>(
         device: &wgpu::Device,
+        format: wgpu::TextureFormat,
         event_loop_window_target: &egui_winit::winit::event_loop::EventLoopWindowTarget<T>,
     ) -> Self {
-        let renderer = Renderer::new(device, wgpu::TextureFormat::Bgra8UnormSrgb, None, 1);
-        let winit_state = egui_winit::State::new(event_loop_window_target);
+        Self::with_state(
+            device,
+            format,
+            egui_winit::State::new(event_loop_window_target),
+        )
+    }
+
+    fn with_state(
+        device: &wgpu::Device,
+        format: wgpu::TextureFormat,
+        winit_state: egui_winit::State,
+    ) -> Self {
+        let renderer = Renderer::new(device, format, None, 1);
         let context = egui::Context::default();
         Self {
             renderer,
             context,
             winit_state,
+            pixels_per_point: None,
         }
     }
 
+    /// scales the UI by this instead of the window's scale factor
+    pub fn set_pixels_per_point(&mut self, pixels_per_point: f32) {
+        self.pixels_per_point = Some(pixels_per_point);
+    }
+
     pub fn handle_event(&mut self, event: &Event<()>, id: WindowId) -> bool {
         match event {
             Event::WindowEvent { window_id, event } => {
@@ -48,7 +80,31 @@ impl UI {
         window: &Window,
         window_size: [u32; 2],
     ) -> CommandBuffer {
+        let pixels_per_point = self
+            .pixels_per_point
+            .unwrap_or_else(|| egui_winit::native_pixels_per_point(window));
+        // pointer positions are turned into points with this so clicks land where they're drawn
+        self.winit_state.set_pixels_per_point(pixels_per_point);
         let input = self.winit_state.take_egui_input(window);
+        self.draw(
+            input,
+            run_ui,
+            device,
+            queue,
+            view,
+            screen_descriptor(window_size, pixels_per_point),
+        )
+    }
+
+    fn draw(
+        &mut self,
+        input: egui::RawInput,
+        run_ui: impl FnOnce(&Context),
+        device: &Device,
+        queue: &Queue,
+        view: &TextureView,
+        descriptor: egui_wgpu::renderer::ScreenDescriptor,
+    ) -> CommandBuffer {
         let output = self.context.run(input, run_ui);
         for (id, image_delta) in output.textures_delta.set.iter() {
             self.renderer
@@ -64,10 +120,6 @@ impl UI {
             label: Some("egui Command Encoder"),
         });
 
-        let descriptor = egui_wgpu::renderer::ScreenDescriptor {
-            size_in_pixels: window_size,
-            pixels_per_point: 1.0,
-        };
         self.renderer
             .update_buffers(device, queue, &mut encoder, &triangles, &descriptor);
         let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
@@ -91,12 +143,71 @@ impl UI {
 
 pub trait Mode: ToString + std::marker::Sized {
     fn change(&self) -> Self;
+    /// undoes [Mode::change]
+    fn change_back(&self) -> Self;
     fn ui(&mut self, ui: &mut egui::Ui, text: impl Into<egui::WidgetText>) {
         ui.horizontal(|ui| {
             ui.label(text);
+            if ui.small_button("◀").clicked() {
+                *self = self.change_back();
+            }
             if ui.button(self.to_string()).clicked() {
                 *self = self.change();
             }
         });
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use excali_render::test_util::headless_device;
+
+    #[test]
+    fn draws_to_rgba_target() {
+        let Some((device, queue)) = headless_device(wgpu::Features::empty()) else {
+            return;
+        };
+        let format = wgpu::TextureFormat::Rgba8UnormSrgb;
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("ui_test_target"),
+            size: wgpu::Extent3d {
+                width: 64,
+                height: 64,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+            view_formats: &[],
+        });
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+
+        let mut ui = UI::with_state(
+            &device,
+            format,
+            egui_winit::State::new_with_wayland_display(None),
+        );
+        let commands = ui.draw(
+            egui::RawInput::default(),
+            |ctx| {
+                egui::Window::new("test").show(ctx, |ui| ui.label("hello"));
+            },
+            &device,
+            &queue,
+            &view,
+            screen_descriptor([64, 64], 1.0),
+        );
+        queue.submit(Some(commands));
+        device.poll(wgpu::Maintain::Wait);
+    }
+
+    #[test]
+    fn descriptor_keeps_scale_factor() {
+        let descriptor = screen_descriptor([1600, 1200], 2.0);
+        assert_eq!(descriptor.pixels_per_point, 2.0);
+        assert_eq!(descriptor.size_in_pixels, [1600, 1200]);
+    }
+}
@@ -0,0 +1,175 @@
+use excali_sprite::{Color, Sprite, Transform};
+use nalgebra::Vector2;
+
+/// What the game is currently showing
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum GameState {
+    Menu,
+    Map,
+    Puzzle { level_name: String },
+    Paused { resume: Box<GameState> },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum GameEvent {
+    Start,
+    Escape,
+    SelectZone { level_name: String },
+    Solved,
+    Quit,
+}
+
+impl GameState {
+    /// returns the state after the event, events which don't apply leave the state unchanged
+    pub fn transition(&self, event: GameEvent) -> Self {
+        match (self, event) {
+            (Self::Menu, GameEvent::Start) => Self::Map,
+            (Self::Map, GameEvent::Escape) => Self::Paused {
+                resume: Box::new(Self::Map),
+            },
+            (Self::Map, GameEvent::SelectZone { level_name }) => Self::Puzzle { level_name },
+            (Self::Puzzle { .. }, GameEvent::Escape | GameEvent::Solved) => Self::Map,
+            (Self::Paused { resume }, GameEvent::Escape) => *resume.clone(),
+            (Self::Paused { .. }, GameEvent::Quit) => Self::Menu,
+            (state, _) => state.clone(),
+        }
+    }
+
+    /// returns if changing to the next state should fade through a [Transition]
+    pub fn fades_to(&self, next: &GameState) -> bool {
+        matches!(self, Self::Puzzle { .. }) != matches!(next, Self::Puzzle { .. })
+    }
+}
+
+/// Fades to black and back between two states, the state should change at the midpoint while
+/// the screen is covered
+pub struct Transition {
+    pub from: GameState,
+    pub to: GameState,
+    /// seconds since the transition started
+    pub t: f32,
+    pub duration: f32,
+}
+
+impl Transition {
+    pub fn new(from: GameState, to: GameState, duration: f32) -> Self {
+        Self {
+            from,
+            to,
+            t: 0.0,
+            duration,
+        }
+    }
+
+    /// advances the transition by dt seconds, returns if it's done
+    pub fn update(&mut self, dt: f32) -> bool {
+        self.t = (self.t + dt).min(self.duration);
+        self.done()
+    }
+
+    pub fn done(&self) -> bool {
+        self.t >= self.duration
+    }
+
+    pub fn past_midpoint(&self) -> bool {
+        self.t >= self.duration / 2.0
+    }
+
+    /// the state behind the overlay, from until the midpoint then to
+    pub fn shown(&self) -> &GameState {
+        if self.past_midpoint() {
+            &self.to
+        } else {
+            &self.from
+        }
+    }
+
+    /// opacity of the black overlay, ramping 0 -> 1 -> 0
+    pub fn alpha(&self) -> f32 {
+        if self.duration <= 0.0 {
+            return 0.0;
+        }
+        1.0 - (self.t / self.duration * 2.0 - 1.0).abs()
+    }
+
+    /// a black sprite covering the screen, drawn with a 1x1 white texture
+    pub fn overlay(&self, screen_width: f32, screen_height: f32) -> Sprite {
+        Sprite {
+            transform: Transform::from_scale(Vector2::new(screen_width, screen_height)),
+            color: Color::new(0.0, 0.0, 0.0, self.alpha()),
+            ..Default::default()
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn fades_in_and_out_of_puzzles() {
+        let puzzle = GameState::Puzzle {
+            level_name: "a".to_string(),
+        };
+        assert!(GameState::Map.fades_to(&puzzle));
+        assert!(puzzle.fades_to(&GameState::Map));
+        assert!(!GameState::Menu.fades_to(&GameState::Map));
+    }
+
+    #[test]
+    fn transition_done_at_duration() {
+        let mut transition = Transition::new(GameState::Map, GameState::Menu, 1.0);
+        assert!(!transition.update(0.5));
+        assert!(!transition.update(0.49));
+        assert!(transition.update(0.01));
+    }
+
+    #[test]
+    fn transition_fades_in_and_out() {
+        let mut transition = Transition::new(GameState::Map, GameState::Menu, 1.0);
+        assert_eq!(transition.alpha(), 0.0);
+        assert_eq!(transition.shown(), &GameState::Map);
+        transition.update(0.25);
+        assert_eq!(transition.alpha(), 0.5);
+        transition.update(0.25);
+        assert_eq!(transition.alpha(), 1.0);
+        assert!(transition.past_midpoint());
+        assert_eq!(transition.shown(), &GameState::Menu);
+        transition.update(0.5);
+        assert_eq!(transition.alpha(), 0.0);
+    }
+
+    #[test]
+    fn selecting_zone_enters_puzzle() {
+        let state = GameState::Map.transition(GameEvent::SelectZone {
+            level_name: "alpha".to_string(),
+        });
+        assert_eq!(
+            state,
+            GameState::Puzzle {
+                level_name: "alpha".to_string()
+            }
+        );
+    }
+
+    #[test]
+    fn escape_from_puzzle_returns_to_map() {
+        let state = GameState::Puzzle {
+            level_name: "alpha".to_string(),
+        };
+        assert_eq!(state.transition(GameEvent::Escape), GameState::Map);
+    }
+
+    #[test]
+    fn escape_pauses_and_resumes_map() {
+        let paused = GameState::Map.transition(GameEvent::Escape);
+        assert_eq!(
+            paused,
+            GameState::Paused {
+                resume: Box::new(GameState::Map)
+            }
+        );
+        assert_eq!(paused.transition(GameEvent::Escape), GameState::Map);
+        assert_eq!(paused.transition(GameEvent::Quit), GameState::Menu);
+    }
+}
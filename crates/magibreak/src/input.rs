@@ -1,50 +1,121 @@
 use excali_input::*;
+use excali_io::tokio::sync::oneshot;
+use excali_io::{load_from_toml, resolve_save_path, save_to_toml, IoError};
 use winit::event::VirtualKeyCode;
 
+use crate::APP_NAME;
+
+const BINDINGS_PATH: &str = "bindings.toml";
+
 pub struct Actions {
     pub undo: Action,
+    pub redo: Action,
     pub debug: Action,
     pub edit: Action,
     pub escape: Action,
-    pub camera_forward: Action,
-    pub camera_backward: Action,
-    pub camera_left: Action,
-    pub camera_right: Action,
-    pub camera_up: Action,
-    pub camera_down: Action,
+    /// x is left and y is forward
+    pub camera_move: Axis2,
+    pub camera_lift: Axis,
+    pub undo_chord: Chord,
+    pub redo_chord: Chord,
 }
 
 // TODO wrap both traits into a derive macro
 impl Default for Actions {
     fn default() -> Self {
         Self {
-            undo: Action::new(VirtualKeyCode::U),
-            escape: Action::new(VirtualKeyCode::Escape),
-            debug: Action::new(VirtualKeyCode::F2),
-            edit: Action::new(VirtualKeyCode::F1),
-            camera_forward: Action::new(VirtualKeyCode::W),
-            camera_backward: Action::new(VirtualKeyCode::S),
-            camera_left: Action::new(VirtualKeyCode::A),
-            camera_right: Action::new(VirtualKeyCode::D),
-            camera_up: Action::new(VirtualKeyCode::Space),
-            camera_down: Action::new(VirtualKeyCode::LShift),
+            undo: Action::new("undo", VirtualKeyCode::U),
+            redo: Action::new("redo", VirtualKeyCode::R),
+            escape: Action::new("escape", VirtualKeyCode::Escape),
+            debug: Action::new("debug", VirtualKeyCode::F2),
+            edit: Action::new("edit", VirtualKeyCode::F1),
+            camera_move: Axis2::new(
+                Axis::new(
+                    Action::new("camera_left", VirtualKeyCode::A),
+                    Action::new("camera_right", VirtualKeyCode::D),
+                ),
+                Axis::new(
+                    Action::new("camera_forward", VirtualKeyCode::W),
+                    Action::new("camera_backward", VirtualKeyCode::S),
+                ),
+            ),
+            camera_lift: Axis::new(
+                Action::new("camera_up", VirtualKeyCode::Space),
+                Action::new("camera_down", VirtualKeyCode::LShift),
+            ),
+            undo_chord: Chord::new(&[VirtualKeyCode::LControl, VirtualKeyCode::Z]),
+            redo_chord: Chord::new(&[VirtualKeyCode::LControl, VirtualKeyCode::Y]),
         }
     }
 }
 
 impl InputMap for Actions {
     fn actions(&mut self) -> Vec<&mut Action> {
-        vec![
+        let mut actions = vec![
             &mut self.undo,
-            &mut self.camera_forward,
-            &mut self.camera_up,
-            &mut self.camera_down,
-            &mut self.camera_right,
-            &mut self.camera_left,
-            &mut self.camera_backward,
+            &mut self.redo,
             &mut self.debug,
             &mut self.escape,
             &mut self.edit,
-        ]
+        ];
+        actions.extend(self.camera_move.actions());
+        actions.extend(self.camera_lift.actions());
+        actions
+    }
+
+    fn actions_ref(&self) -> Vec<&Action> {
+        let mut actions = vec![
+            &self.undo,
+            &self.redo,
+            &self.debug,
+            &self.escape,
+            &self.edit,
+        ];
+        actions.extend(self.camera_move.actions_ref());
+        actions.extend(self.camera_lift.actions_ref());
+        actions
+    }
+}
+
+pub async fn load_bindings() -> Result<Bindings, IoError> {
+    load_from_toml(resolve_save_path(APP_NAME, BINDINGS_PATH)).await
+}
+
+pub fn save_bindings(bindings: &Bindings) -> oneshot::Receiver<Result<(), IoError>> {
+    save_to_toml(bindings, resolve_save_path(APP_NAME, BINDINGS_PATH))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use excali_io::tokio;
+
+    #[test]
+    fn default_bindings_round_trip() {
+        let path =
+            std::env::temp_dir().join(format!("magibreak_bindings_{}.toml", std::process::id()));
+        let bindings = Actions::default().bindings();
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let loaded: Bindings = runtime.block_on(async {
+            save_to_toml(&bindings, path.clone())
+                .await
+                .unwrap()
+                .unwrap();
+            load_from_toml(&path).await.unwrap()
+        });
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(loaded, bindings);
+        assert_eq!(loaded["camera_up"], VirtualKeyCode::Space);
+
+        let mut actions = Actions::default();
+        actions.apply_bindings(&Bindings::from([(
+            "camera_up".to_string(),
+            VirtualKeyCode::E,
+        )]));
+        assert_eq!(actions.camera_lift.positive.key_code, VirtualKeyCode::E);
+        assert_eq!(
+            actions.camera_lift.negative.key_code,
+            VirtualKeyCode::LShift
+        );
     }
 }
@@ -1,14 +1,21 @@
 use crate::puzzle::*;
 use crate::textures::Textures;
+use crate::APP_NAME;
+use excali_input::{Button, Painter};
 use excali_io::tokio::sync::oneshot;
-use excali_io::{load_from_toml, receive_oneshot_rx, save_to_toml, tokio, OneShotStatus};
-use excali_sprite::{Color, Sprite, SpriteBatch, Transform};
+use excali_io::{
+    load_from_toml, receive_oneshot_rx, resolve_load_path, resolve_save_path, safe_join,
+    save_to_toml, tokio, IoError, OneShotStatus, SafeJoinError,
+};
+use excali_sprite::{Color, Sprite, SpriteBatch, SpriteBlendMode, Transform};
 use excali_ui::egui_winit::egui::{self, Context};
 use excali_ui::Mode;
 use log::error;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
 
-#[derive(Eq, Debug, PartialEq)]
-enum LevelEditorMode {
+#[derive(Eq, Debug, PartialEq, Clone, Copy, Serialize, Deserialize)]
+pub enum LevelEditorMode {
     Clear,
     Cursor,
     Place,
@@ -39,15 +46,77 @@ impl Mode for LevelEditorMode {
             Self::Lines => Self::Clear,
         }
     }
+
+    fn change_back(&self) -> Self {
+        match *self {
+            Self::Clear => Self::Lines,
+            Self::Cursor => Self::Clear,
+            Self::Place => Self::Cursor,
+            Self::Lines => Self::Place,
+        }
+    }
+}
+
+/// where the player's own copy of a level is saved
+fn level_save_path(name: &str) -> Result<PathBuf, SafeJoinError> {
+    safe_join(resolve_save_path(APP_NAME, USER_LEVELS_PATH), name)
 }
 
 async fn load_puzzle(name: String) -> Result<Puzzle, String> {
-    match load_from_toml::<SerialablePuzzle>(format!("{}{}", LEVELS_PATH, name)).await {
+    match (
+        safe_join(USER_LEVELS_PATH, &name),
+        safe_join(LEVELS_PATH, &name),
+    ) {
+        (Ok(saved), Ok(bundled)) => {
+            load_puzzle_file(resolve_load_path(APP_NAME, saved, bundled)).await
+        }
+        (Err(err), _) | (_, Err(err)) => Err(format!("{err}")),
+    }
+}
+
+async fn load_puzzle_file(path: PathBuf) -> Result<Puzzle, String> {
+    match load_from_toml::<SerialablePuzzle>(path).await {
         Ok(serialable_puzzle) => match Puzzle::try_from(serialable_puzzle) {
             Ok(puzzle) => Ok(puzzle),
-            Err(err) => Err(format!("{err:?}")),
+            Err(err) => Err(format!("{err}")),
         },
-        Err(err) => Err(err),
+        Err(err) => Err(err.to_string()),
+    }
+}
+
+const EDITOR_SESSION_PATH: &str = "editor_session.toml";
+
+/// Where the editor was left, so closing the game mid-edit doesn't lose anything
+#[derive(Serialize, Deserialize)]
+pub struct EditorSession {
+    pub file_name: String,
+    pub mode: LevelEditorMode,
+    pub rune: Sigil,
+    /// changes which weren't saved to the level's file, none when the file is up to date
+    pub working_puzzle: Option<SerialablePuzzle>,
+}
+
+impl EditorSession {
+    pub async fn load() -> Result<Self, IoError> {
+        load_from_toml(resolve_save_path(APP_NAME, EDITOR_SESSION_PATH)).await
+    }
+
+    /// written while exiting, so it can't wait on a task
+    pub fn save_blocking(&self) -> Result<(), String> {
+        let path = resolve_save_path(APP_NAME, EDITOR_SESSION_PATH);
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent).map_err(|err| err.to_string())?;
+        }
+        let contents = excali_io::toml::to_string(self).map_err(|err| err.to_string())?;
+        std::fs::write(path, contents).map_err(|err| err.to_string())
+    }
+
+    /// removes a saved session, so a finished one isn't restored next time
+    pub fn clear_blocking() -> Result<(), String> {
+        match std::fs::remove_file(resolve_save_path(APP_NAME, EDITOR_SESSION_PATH)) {
+            Err(err) if err.kind() != std::io::ErrorKind::NotFound => Err(err.to_string()),
+            _ => Ok(()),
+        }
     }
 }
 
@@ -60,14 +129,47 @@ pub struct LevelEditor {
     mode: LevelEditorMode,
     levels: Vec<String>,
     levels_rx: Option<oneshot::Receiver<Result<Vec<String>, String>>>,
-    save_rx: Option<oneshot::Receiver<Result<(), String>>>,
+    save_rx: Option<oneshot::Receiver<Result<(), IoError>>>,
     delete_rx: Option<oneshot::Receiver<Result<(), String>>>,
     load_rx: Option<oneshot::Receiver<Result<Puzzle, String>>>,
     line_start: Option<SigilCoordinate>,
     rune: Sigil,
+    painter: Painter<SigilCoordinate>,
+    /// if loaded_puzzle has changes which haven't been saved
+    unsaved: bool,
 }
 
+/// bundled read-only levels
 const LEVELS_PATH: &str = "./assets/levels/";
+/// levels saved by the player, relative to the data directory
+const USER_LEVELS_PATH: &str = "levels";
+
+async fn level_names(directory: PathBuf) -> Result<Vec<String>, String> {
+    let mut dir = match tokio::fs::read_dir(directory).await {
+        Ok(dir) => dir,
+        // nothing has been saved there yet
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(err) => return Err(err.to_string()),
+    };
+    let mut entries = Vec::<String>::new();
+    loop {
+        match dir.next_entry().await {
+            Err(err) => {
+                return Err(err.to_string());
+            }
+            Ok(entry) => match entry {
+                Some(entry) => {
+                    entries.push(entry.file_name().to_str().unwrap().to_string());
+                }
+                None => {
+                    break;
+                }
+            },
+        }
+    }
+    Ok(entries)
+}
+
 impl LevelEditor {
     pub async fn new(file_name: String) -> Self {
         let mut editor = Self {
@@ -85,6 +187,8 @@ impl LevelEditor {
                 rune: Rune::Alpha,
                 orb: Orb::Circle,
             },
+            painter: Painter::default(),
+            unsaved: false,
         };
         editor.load_levels();
         editor
@@ -99,31 +203,26 @@ impl LevelEditor {
         self.levels_rx = Some(rx);
 
         tokio::spawn(async move {
-            tx.send(match tokio::fs::read_dir(LEVELS_PATH).await {
-                Err(err) => Err(err.to_string()),
-                Ok(mut dir) => {
-                    let mut entries = Vec::<String>::new();
-                    loop {
-                        match dir.next_entry().await {
-                            Err(err) => {
-                                return Err(err.to_string());
+            let mut levels = Vec::<String>::new();
+            for directory in [
+                PathBuf::from(LEVELS_PATH),
+                resolve_save_path(APP_NAME, USER_LEVELS_PATH),
+            ] {
+                match level_names(directory).await {
+                    Ok(names) => {
+                        for name in names {
+                            if !levels.contains(&name) {
+                                levels.push(name);
                             }
-                            Ok(entry) => match entry {
-                                Some(entry) => {
-                                    entries.push(entry.file_name().to_str().unwrap().to_string());
-                                }
-                                None => {
-                                    break;
-                                }
-                            },
                         }
                     }
-                    Ok(entries)
+                    Err(err) => {
+                        tx.send(Err(err)).unwrap();
+                        return;
+                    }
                 }
-            })
-            .unwrap();
-            // no idea what I'm doing here
-            Ok(())
+            }
+            tx.send(Ok(levels)).unwrap();
         });
     }
 
@@ -145,7 +244,13 @@ impl LevelEditor {
             ui.horizontal(|ui| {
                 // saving
                 match receive_oneshot_rx(&mut self.save_rx) {
-                    OneShotStatus::Value(..) => self.load_levels(),
+                    OneShotStatus::Value(result) => {
+                        match result {
+                            Ok(_) => self.unsaved = false,
+                            Err(err) => error!("Can't save {}: {err}", self.file_name),
+                        }
+                        self.load_levels();
+                    }
                     OneShotStatus::None => {
                         if ui.button("Save").clicked() {
                             self.save_level();
@@ -162,6 +267,7 @@ impl LevelEditor {
                     OneShotStatus::Value(new_puzzle) => {
                         match new_puzzle {
                             Ok(new_puzzle) => {
+                                self.unsaved = false;
                                 self.loaded_puzzle = new_puzzle.clone();
                                 puzzle.load_puzzle(new_puzzle);
                             }
@@ -237,22 +343,27 @@ impl LevelEditor {
         self.delete_rx = Some(rx);
 
         tokio::spawn(async move {
-            tx.send(
-                match tokio::fs::remove_file(format!("{}{}", LEVELS_PATH, level)).await {
+            tx.send(match level_save_path(&level) {
+                Ok(path) => match tokio::fs::remove_file(path).await {
                     Ok(_) => Ok(()),
                     Err(err) => Err(err.to_string()),
                 },
-            )
+                Err(err) => Err(err.to_string()),
+            })
             .unwrap();
         });
     }
 
     fn save_level(&mut self) {
-        let file_name = self.file_name.clone();
-        self.save_rx = Some(save_to_toml(
-            &SerialablePuzzle::from(self.loaded_puzzle.clone()),
-            format!("{}{}", LEVELS_PATH, file_name),
-        ));
+        match level_save_path(&self.file_name) {
+            Ok(path) => {
+                self.save_rx = Some(save_to_toml(
+                    &SerialablePuzzle::from(self.loaded_puzzle.clone()),
+                    path,
+                ));
+            }
+            Err(err) => error!("Can't save {}: {err}", self.file_name),
+        }
     }
 
     fn load_level(&mut self) {
@@ -265,6 +376,37 @@ impl LevelEditor {
         });
     }
 
+    /// loads a level file dropped onto the window, saving will write it to the levels directory
+    pub fn load_dropped(&mut self, path: &Path) {
+        if path.extension().and_then(|extension| extension.to_str()) != Some("toml") {
+            error!("Dropped file {} isn't a .toml level", path.display());
+            return;
+        }
+        let Some(file_name) = path.file_name().and_then(|name| name.to_str()) else {
+            error!("Dropped file {} has no file name", path.display());
+            return;
+        };
+        self.file_name = file_name.to_string();
+
+        let (tx, rx) = tokio::sync::oneshot::channel();
+        self.load_rx = Some(rx);
+
+        let path = path.to_path_buf();
+        tokio::spawn(async move {
+            tx.send(load_puzzle_file(path).await).unwrap();
+        });
+    }
+
+    /// returns if the click should be input, placing and clearing paint while held
+    pub fn painted(&mut self, button: &Button, coordinate: SigilCoordinate) -> bool {
+        match self.mode {
+            LevelEditorMode::Clear | LevelEditorMode::Place => {
+                self.painter.paint(button, coordinate)
+            }
+            LevelEditorMode::Cursor | LevelEditorMode::Lines => button.just_pressed(),
+        }
+    }
+
     pub fn input(&mut self, coordinate: SigilCoordinate, puzzle: &mut ActivePuzzle) {
         if !self.enabled {
             return;
@@ -294,6 +436,7 @@ impl LevelEditor {
                         self.loaded_puzzle.lines.push(Line {
                             start,
                             end: coordinate,
+                            orb: None,
                         });
                     }
                 }
@@ -302,9 +445,43 @@ impl LevelEditor {
                 }
             },
         };
+        self.unsaved = true;
         puzzle.load_puzzle(self.loaded_puzzle.clone());
     }
 
+    pub fn session(&self) -> EditorSession {
+        EditorSession {
+            file_name: self.file_name.clone(),
+            mode: self.mode,
+            rune: self.rune,
+            working_puzzle: self
+                .unsaved
+                .then(|| SerialablePuzzle::from(self.loaded_puzzle.clone())),
+        }
+    }
+
+    /// reopens the editor as the session left it, taking the session only if it's for this level
+    pub fn restore(&mut self, session: &mut Option<EditorSession>, puzzle: &mut ActivePuzzle) {
+        let Some(session) = session.take_if(|session| session.file_name == self.file_name) else {
+            return;
+        };
+        self.mode = session.mode;
+        self.rune = session.rune;
+        match session.working_puzzle.map(Puzzle::try_from) {
+            Some(Ok(working_puzzle)) => {
+                self.loaded_puzzle = working_puzzle;
+                self.unsaved = true;
+                // otherwise it's loaded once the editor is toggled on
+                if self.enabled {
+                    puzzle.load_puzzle(self.loaded_puzzle.clone());
+                }
+            }
+            Some(Err(err)) => error!("Can't restore the unsaved {}: {err}", self.file_name),
+            // the level was just loaded from its file
+            None => {}
+        }
+    }
+
     fn toggle(&mut self, puzzle: &mut ActivePuzzle) {
         self.enabled = !self.enabled;
         puzzle.load_puzzle(self.loaded_puzzle.clone());
@@ -315,6 +492,7 @@ impl LevelEditor {
         camera: &Transform,
         mouse_coordinate: SigilCoordinate,
         textures: &'a Textures,
+        spacing: f32,
     ) -> Option<Vec<SpriteBatch<'a>>> {
         if !self.enabled {
             return None;
@@ -322,28 +500,34 @@ impl LevelEditor {
 
         match self.mode {
             LevelEditorMode::Place => {
-                let transform = Transform::from_sigil_coordinate(mouse_coordinate, camera);
+                let transform = Transform::from_sigil_coordinate(mouse_coordinate, camera, spacing);
                 Some(vec![
                     SpriteBatch {
                         sprites: vec![Sprite {
                             transform,
                             texture_coordinate: self.rune.orb.texture_coordinate(false),
                             color: Color::new(1.0, 1.0, 1.0, 0.8),
+                            ..Default::default()
                         }],
                         texture: &textures.orbs,
+                        blend_mode: SpriteBlendMode::Alpha,
+                        clip: None,
                     },
                     SpriteBatch {
                         sprites: vec![Sprite {
                             transform,
                             texture_coordinate: self.rune.rune.texture_coordinate(),
                             color: Color::new(1.0, 1.0, 1.0, 0.8),
+                            ..Default::default()
                         }],
                         texture: &textures.sigils,
+                        blend_mode: SpriteBlendMode::Alpha,
+                        clip: None,
                     },
                 ])
             }
             LevelEditorMode::Clear => {
-                let transform = Transform::from_sigil_coordinate(mouse_coordinate, camera);
+                let transform = Transform::from_sigil_coordinate(mouse_coordinate, camera, spacing);
                 Some(vec![SpriteBatch {
                     sprites: vec![Sprite {
                         transform,
@@ -351,10 +535,12 @@ impl LevelEditor {
                         ..Default::default()
                     }],
                     texture: &textures.border,
+                    blend_mode: SpriteBlendMode::Alpha,
+                    clip: None,
                 }])
             }
             LevelEditorMode::Cursor => {
-                let transform = Transform::from_sigil_coordinate(mouse_coordinate, camera);
+                let transform = Transform::from_sigil_coordinate(mouse_coordinate, camera, spacing);
                 Some(vec![SpriteBatch {
                     sprites: vec![Sprite {
                         transform,
@@ -362,10 +548,12 @@ impl LevelEditor {
                         ..Default::default()
                     }],
                     texture: &textures.cursor,
+                    blend_mode: SpriteBlendMode::Alpha,
+                    clip: None,
                 }])
             }
             LevelEditorMode::Lines => {
-                let transform = Transform::from_sigil_coordinate(mouse_coordinate, camera);
+                let transform = Transform::from_sigil_coordinate(mouse_coordinate, camera, spacing);
                 let mut sprites = vec![Sprite {
                     transform,
                     color: Color::new(1.0, 1.0, 1.0, 0.6),
@@ -374,7 +562,7 @@ impl LevelEditor {
 
                 if let Some(start) = self.line_start {
                     sprites.push(Sprite {
-                        transform: Transform::from_sigil_coordinate(start, camera),
+                        transform: Transform::from_sigil_coordinate(start, camera, spacing),
                         color: Color::new(1.0, 1.0, 1.0, 0.8),
                         ..Default::default()
                     });
@@ -383,8 +571,100 @@ impl LevelEditor {
                 Some(vec![SpriteBatch {
                     sprites,
                     texture: &textures.cursor,
+                    blend_mode: SpriteBlendMode::Alpha,
+                    clip: None,
                 }])
             }
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn mode_changes_back() {
+        let modes = [
+            LevelEditorMode::Clear,
+            LevelEditorMode::Cursor,
+            LevelEditorMode::Place,
+            LevelEditorMode::Lines,
+        ];
+        for mode in modes {
+            assert_eq!(mode.change().change_back(), mode);
+        }
+    }
+
+    fn editor() -> LevelEditor {
+        LevelEditor {
+            enabled: true,
+            loaded_puzzle: Puzzle::default(),
+            file_name: "alpha.toml".to_string(),
+            mode: LevelEditorMode::Place,
+            levels: Vec::new(),
+            levels_rx: None,
+            save_rx: None,
+            delete_rx: None,
+            load_rx: None,
+            line_start: None,
+            rune: Sigil {
+                rune: Rune::Sigma,
+                orb: Orb::Diamond,
+            },
+            painter: Painter::default(),
+            unsaved: false,
+        }
+    }
+
+    #[test]
+    fn session_restores_unsaved_edit() {
+        let mut puzzle = ActivePuzzle::new(Puzzle::default());
+        let mut edited = editor();
+        assert!(edited.session().working_puzzle.is_none());
+        edited.input(SigilCoordinate::new(1, 2), &mut puzzle);
+        edited.mode = LevelEditorMode::Lines;
+
+        let serialized = excali_io::toml::to_string(&edited.session()).unwrap();
+        let session = excali_io::toml::from_str::<EditorSession>(&serialized).unwrap();
+        let mut restored = LevelEditor {
+            rune: Sigil {
+                rune: Rune::Alpha,
+                orb: Orb::Circle,
+            },
+            ..editor()
+        };
+        let mut session = Some(session);
+        restored.restore(&mut session, &mut puzzle);
+
+        assert!(session.is_none());
+        assert_eq!(restored.mode, LevelEditorMode::Lines);
+        assert!(matches!(restored.rune.rune, Rune::Sigma));
+        assert_eq!(restored.rune.orb, Orb::Diamond);
+        assert!(restored.unsaved);
+        assert!(restored
+            .loaded_puzzle
+            .sigils
+            .contains_key(&SigilCoordinate::new(1, 2)));
+    }
+
+    #[test]
+    fn session_for_another_level_is_kept() {
+        let mut puzzle = ActivePuzzle::new(Puzzle::default());
+        let mut edited = editor();
+        edited.input(SigilCoordinate::new(1, 2), &mut puzzle);
+        let mut session = Some(edited.session());
+
+        let mut other = LevelEditor {
+            file_name: "beta.toml".to_string(),
+            mode: LevelEditorMode::Clear,
+            ..editor()
+        };
+        other.restore(&mut session, &mut ActivePuzzle::new(Puzzle::default()));
+
+        assert_eq!(session.unwrap().file_name, "alpha.toml");
+        assert_eq!(other.mode, LevelEditorMode::Clear);
+        assert!(!other.unsaved);
+        assert!(other.loaded_puzzle.sigils.is_empty());
+    }
+}
@@ -1,29 +1,49 @@
+use excali_3d::TranslateGizmo;
+use excali_io::tokio;
 use excali_io::tokio::sync::oneshot;
-use excali_io::{load_file, tokio};
-use excali_io::{receive_oneshot_rx, OneShotStatus};
-use log::error;
-use nalgebra::Vector2;
-use std::time::Instant;
+use excali_io::{receive_oneshot_rx, IoError, OneShotStatus};
+use log::{error, info};
+use nalgebra::{Vector2, Vector3};
+use std::time::Duration;
 
+use crate::game_state::*;
 use crate::level_editor::*;
+use crate::map::{Grid, ZoneState};
 use crate::puzzle::*;
+use crate::save::*;
+use crate::settings::CameraSettings;
 use excali_input::*;
 use excali_render::*;
 use excali_sprite::*;
+use excali_ui::egui_winit::egui;
 use excali_ui::*;
-use winit::event_loop::EventLoop;
+use winit::event::Event;
+use winit::event_loop::{ControlFlow, EventLoop};
 
 use self::input::*;
 use self::textures::*;
 use self::world::World;
 
+mod game_state;
 mod input;
 mod level_editor;
+mod map;
 mod puzzle;
+mod save;
+mod settings;
+mod terrain;
 mod textures;
 mod world;
 
 const STACK_SIZE: usize = 10_000_000;
+/// the order each part of the game is drawn in, from back to front
+const LAYERS: [&str; 4] = ["map", "world", "sprites", "ui"];
+/// names the directory player data is saved to
+pub const APP_NAME: &str = "magibreak";
+/// downwards pull on the map each frame
+const GRAVITY: f32 = 1.0;
+/// seconds to fade out and back in when entering or leaving a puzzle
+const TRANSITION_DURATION: f32 = 0.6;
 
 fn main() {
     let rt = tokio::runtime::Builder::new_multi_thread()
@@ -33,13 +53,12 @@ fn main() {
     rt.block_on(game());
 }
 
-async fn load_sprite_texture(
-    path: &str,
+fn create_sprite_texture(
+    texture: Texture,
     sprite_renderer: &SpriteRenderer,
     renderer: &Renderer,
     sampler: &wgpu::Sampler,
 ) -> SpriteTexture {
-    let texture = renderer.load_texture(&load_file(path).await.unwrap(), path.to_string());
     let bind_group = sprite_renderer.create_bind_group(&renderer.device, sampler, &texture);
     SpriteTexture {
         data: texture,
@@ -52,13 +71,18 @@ async fn load_sprite_texture(
 struct PuzzlePlayer {
     puzzle: ActivePuzzle,
     editor: LevelEditor,
+    line_style: LineStyle,
 }
 
 impl PuzzlePlayer {
     async fn new(level: String) -> Self {
         let editor = LevelEditor::new(level + ".toml").await;
         let puzzle = ActivePuzzle::new(editor.loaded_puzzle.clone());
-        Self { editor, puzzle }
+        Self {
+            editor,
+            puzzle,
+            line_style: LineStyle::default(),
+        }
     }
 
     /// return if solved
@@ -66,35 +90,23 @@ impl PuzzlePlayer {
         &mut self,
         batches: &mut Vec<SpriteBatch<'a>>,
         camera: &Transform,
-        input: &Input<Actions>,
+        input: Option<&Input<Actions>>,
         mouse_coordinate: Option<SigilCoordinate>,
         textures: &'a Textures,
-        time: f32,
+        flow: &LineFlow,
     ) -> bool {
-        let undo_button = &input.input_map.undo.button;
-        let mut solved = false;
-        if !undo_button.consumed && undo_button.state == InputState::JustPressed {
-            self.puzzle.undo();
-        }
-        if !input.left_mouse_click.consumed
-            && input.left_mouse_click.state == InputState::JustPressed
+        let solved = self.handle_input(input, mouse_coordinate);
+        for batch in self
+            .puzzle
+            .sprite_batches(flow, &self.line_style, camera, textures)
+            .drain(..)
         {
-            if let Some(coordinate) = mouse_coordinate {
-                if !self.editor.enabled {
-                    if self.puzzle.input(&coordinate) && self.puzzle.solved() {
-                        solved = true;
-                    }
-                } else {
-                    self.editor.input(coordinate, &mut self.puzzle);
-                }
-            }
-        }
-        for batch in self.puzzle.sprite_batches(time, camera, textures).drain(..) {
             batches.push(batch);
         }
         if let Some(coordinate) = mouse_coordinate {
             if let Some(mut editor_batches) =
-                self.editor.sprite_batches(camera, coordinate, textures)
+                self.editor
+                    .sprite_batches(camera, coordinate, textures, self.puzzle.spacing())
             {
                 for batch in editor_batches.drain(..) {
                     batches.push(batch);
@@ -103,75 +115,319 @@ impl PuzzlePlayer {
         }
         solved
     }
+
+    /// plays or edits the puzzle, ignored without input, returns if the puzzle was solved
+    fn handle_input(
+        &mut self,
+        input: Option<&Input<Actions>>,
+        mouse_coordinate: Option<SigilCoordinate>,
+    ) -> bool {
+        let Some(input) = input else {
+            return false;
+        };
+        let undo_button = &input.input_map.undo.button;
+        let mut solved = false;
+        if (!undo_button.consumed && undo_button.state == InputState::JustPressed)
+            || input.chord_just_pressed(&input.input_map.undo_chord)
+        {
+            self.puzzle.undo();
+        }
+        let redo_button = &input.input_map.redo.button;
+        if (!redo_button.consumed && redo_button.state == InputState::JustPressed)
+            || input.chord_just_pressed(&input.input_map.redo_chord)
+        {
+            self.puzzle.redo();
+        }
+        if let Some(coordinate) = mouse_coordinate {
+            if !self.editor.enabled {
+                if input.left_mouse_click.just_pressed()
+                    && self.puzzle.input(&coordinate)
+                    && self.puzzle.solved()
+                {
+                    solved = true;
+                }
+                if input.right_mouse_click.just_pressed() {
+                    self.puzzle.remove_line_at(coordinate);
+                }
+            } else if self.editor.painted(&input.left_mouse_click, coordinate) {
+                self.editor.input(coordinate, &mut self.puzzle);
+            }
+        }
+        solved
+    }
+}
+
+/// the input the game states react to, none until a transition between them is done
+fn unblocked_input<'a>(
+    transition: Option<&Transition>,
+    input: &'a Input<Actions>,
+) -> Option<&'a Input<Actions>> {
+    transition.is_none().then_some(input)
+}
+
+/// menus for moving between game states
+fn game_state_ui(
+    ctx: &egui::Context,
+    game_state: &GameState,
+    grid: &Grid,
+    slot: &mut String,
+    slots: &mut Vec<String>,
+    camera_settings: &mut CameraSettings,
+    game_events: &mut Vec<GameEvent>,
+) -> bool {
+    let mut settings_changed = false;
+    match game_state {
+        GameState::Menu => {
+            egui::Window::new("Magibreak").show(ctx, |ui| {
+                for name in slots.iter() {
+                    ui.selectable_value(slot, name.clone(), name);
+                }
+                if ui.button("New Slot").clicked() {
+                    let name = format!("slot-{}", slots.len() + 1);
+                    slots.push(name.clone());
+                    *slot = name;
+                }
+                if ui.button("Play").clicked() {
+                    game_events.push(GameEvent::Start);
+                }
+                ui.collapsing("Camera", |ui| settings_changed = camera_settings.ui(ui));
+            });
+        }
+        GameState::Map => {
+            egui::Window::new("Zones").show(ctx, |ui| {
+                for (_, zone) in grid.sorted_zones() {
+                    let button = egui::Button::new(&zone.level_name);
+                    if ui
+                        .add_enabled(zone.state != ZoneState::Locked, button)
+                        .clicked()
+                    {
+                        game_events.push(GameEvent::SelectZone {
+                            level_name: zone.level_name.clone(),
+                        });
+                    }
+                }
+            });
+        }
+        GameState::Paused { .. } => {
+            egui::Window::new("Paused").show(ctx, |ui| {
+                if ui.button("Resume").clicked() {
+                    game_events.push(GameEvent::Escape);
+                }
+                if ui.button("Main Menu").clicked() {
+                    game_events.push(GameEvent::Quit);
+                }
+                ui.collapsing("Camera", |ui| settings_changed = camera_settings.ui(ui));
+            });
+        }
+        GameState::Puzzle { .. } => {}
+    }
+    settings_changed
+}
+
+/// leaves the current state, loading or dropping the puzzle as needed
+fn change_state(
+    game_state: &mut GameState,
+    next_state: GameState,
+    puzzle_player: &mut Option<PuzzlePlayer>,
+    load_puzzle_rx: &mut Option<oneshot::Receiver<PuzzlePlayer>>,
+    input: &mut Input<Actions>,
+    window: &winit::window::Window,
+) {
+    if let GameState::Puzzle { level_name } = &next_state {
+        let (tx, rx) = oneshot::channel();
+        *load_puzzle_rx = Some(rx);
+        let level_name = level_name.clone();
+        tokio::spawn(async move {
+            if tx.send(PuzzlePlayer::new(level_name).await).is_err() {
+                error!("Puzzle was loaded after leaving it");
+            }
+        });
+    } else {
+        *load_puzzle_rx = None;
+        *puzzle_player = None;
+    }
+    input.lock_mouse(next_state == GameState::Map, window);
+    *game_state = next_state;
 }
 
 async fn game() {
     env_logger::init();
 
     let mut event_loop = EventLoop::new();
-    let mut renderer = Renderer::new(&mut event_loop, wgpu::Features::POLYGON_MODE_LINE).await;
-    let mut sprite_renderer = SpriteRenderer::new(
-        &renderer.config,
-        &renderer.device,
-        renderer.config.width as f32,
-        renderer.config.height as f32,
-    );
-
-    let mut puzzle_player: Option<PuzzlePlayer> = Some(PuzzlePlayer::new("draft".into()).await);
+    let mut renderer = Renderer::new(
+        &mut event_loop,
+        RendererConfig {
+            title: "Magibreak".to_string(),
+            initial_size: Some(winit::dpi::PhysicalSize::new(1280, 720)),
+            features: wgpu::Features::POLYGON_MODE_LINE,
+            ..Default::default()
+        },
+    )
+    .await;
+    info!("{}", renderer.report());
+    let mut sprite_renderer = SpriteRenderer::from_renderer(&renderer);
+
+    let mut game_state = GameState::Menu;
+    let map = Grid::load().await.unwrap();
+    let mut grid = map.clone();
+    let mut slot = DEFAULT_SLOT.to_string();
+    let mut slots = SaveGame::slots();
+    if !slots.contains(&slot) {
+        slots.insert(0, slot.clone());
+    }
+    let mut save_game = match SaveGame::load(&slot).await {
+        Ok(save_game) => save_game,
+        Err(err) => {
+            info!("Starting a new game, couldn't load save: {err}");
+            SaveGame::new(&grid)
+        }
+    };
+    save_game.apply(&mut grid);
+    let mut save_rx: Option<oneshot::Receiver<Result<(), IoError>>> = None;
+    let mut load_save_rx: Option<oneshot::Receiver<Result<SaveGame, String>>> = None;
+    let mut puzzle_player: Option<PuzzlePlayer> = None;
     let mut load_puzzle_rx: Option<oneshot::Receiver<PuzzlePlayer>> = None;
+    let mut transition: Option<Transition> = None;
 
     let mut input = Input::new(renderer.window.id(), Actions::default());
-    input.lock_mouse(true, &renderer.window);
-    let mut ui = UI::new(&renderer.device, &event_loop);
+    match load_bindings().await {
+        Ok(bindings) => input.input_map.apply_bindings(&bindings),
+        Err(IoError::Io(err)) if err.kind() == std::io::ErrorKind::NotFound => {
+            info!("Using the default key bindings, none were saved");
+            // written out so players have a file to rebind keys in
+            if let Ok(Err(err)) = save_bindings(&input.input_map.bindings()).await {
+                error!("Couldn't save key bindings: {err}");
+            }
+        }
+        // the player's file is left alone so their edits can be fixed
+        Err(err) => error!("Using the default key bindings, couldn't load them: {err}"),
+    }
+    let mut ui = UI::new(&renderer.device, renderer.config.format, &event_loop);
 
     let sampler = renderer.pixel_art_sampler();
     let line_sampler = renderer.pixel_art_wrap_sampler();
 
-    let start_instant = Instant::now();
+    let mut line_flow = LineFlow::default();
     let camera = Transform::from_scale(Vector2::new(2.0, 2.0));
     let textures = Textures::new(&sprite_renderer, &renderer, &sampler, &line_sampler).await;
     let mut debug = false;
     let mut edit = false;
-    let mut world = World::new(&renderer);
+    // the brush raises and lowers each cell once per drag
+    let mut raise_brush = Painter::default();
+    let mut lower_brush = Painter::default();
+    let mut world = World::new(&renderer, &grid);
+    world.camera_settings = CameraSettings::load().await.unwrap_or_else(|err| {
+        info!("Using the default camera settings, couldn't load them: {err}");
+        CameraSettings::default()
+    });
+    let mut settings_rx: Option<oneshot::Receiver<Result<(), IoError>>> = None;
+    let mut map_rx: Option<oneshot::Receiver<Result<(), IoError>>> = None;
+    // restored when its level is opened
+    let mut editor_session = EditorSession::load()
+        .await
+        .map_err(|err| info!("No editor session to restore: {err}"))
+        .ok();
+    world.set_gravity(Vector3::new(0.0, -GRAVITY, 0.0));
+    world.teleport(grid.world_position(save_game.current_zone));
 
     event_loop.run(move |event, _, control_flow| {
+        if let Event::LoopDestroyed = event {
+            let session = puzzle_player
+                .as_ref()
+                .map(|player| player.editor.session())
+                // unsaved edits to a level which wasn't reopened outweigh a session without any
+                .filter(|session| session.working_puzzle.is_some() || editor_session.is_none());
+            let result = match session {
+                Some(session) => session.save_blocking(),
+                None if editor_session.is_some() => Ok(()),
+                None => EditorSession::clear_blocking(),
+            };
+            if let Err(err) = result {
+                error!("Can't save the editor session: {err}");
+            }
+        }
         input.handle_event(&event, ui.handle_event(&event, renderer.window.id()));
-        if let Err(err) = renderer.handle_event(&event, control_flow, |renderer, view, delta| {
-            // NOTE this shuts up clippy
-            puzzle_player = None;
-            let mouse_coordinate = if let Some(mouse_position) = input.mouse_position {
-                Some(SigilCoordinate::from_position(
-                    camera
-                        .to_object_space(&Transform::from_position(
-                            mouse_position
-                                .world_position(
-                                    &(renderer.config.width, renderer.config.height).into(),
-                                )
-                                .into(),
-                        ))
-                        .position,
-                ))
+        let draw_frame = |renderer: &mut Renderer,
+                          window: winit::window::WindowId,
+                          view: &wgpu::TextureView,
+                          delta: f64,
+                          resumed: bool| {
+            // the game only has the main window
+            if window != renderer.window.id() {
+                return Vec::new();
+            }
+            if resumed {
+                info!("Resumed after losing focus");
+            }
+            let mut game_events = Vec::<GameEvent>::new();
+            let spacing = puzzle_player
+                .as_ref()
+                .map_or(SIGIL_DISTANCE, |player| player.puzzle.spacing());
+            let state_input = unblocked_input(transition.as_ref(), &input);
+            let mouse_coordinate = if let Some(input) = state_input {
+                input.mouse_position.map(|mouse_position| {
+                    SigilCoordinate::from_screen_position(
+                        mouse_position
+                            .world_position(&(renderer.config.width, renderer.config.height).into())
+                            .into(),
+                        &camera,
+                        spacing,
+                    )
+                })
             } else {
                 None
             };
 
-            if input.input_map.debug.button.state == InputState::JustPressed {
+            if let Some(player) = puzzle_player.as_mut() {
+                for path in renderer.frame_events.dropped_files.iter() {
+                    player.editor.load_dropped(path);
+                }
+            }
+
+            if state_input
+                .is_some_and(|input| input.input_map.debug.button.state == InputState::JustPressed)
+            {
                 debug = !debug;
+                // fly around the map while debugging
+                world.set_gravity_enabled(!debug);
             }
 
-            if input.input_map.edit.button.state == InputState::JustPressed {
+            if state_input
+                .is_some_and(|input| input.input_map.edit.button.state == InputState::JustPressed)
+            {
                 edit = !edit;
+                // keeps the map's edits once editing's done
+                if !edit && game_state == GameState::Map {
+                    map_rx = Some(grid.save());
+                }
             }
 
-            let time = renderer
-                .last_frame
-                .duration_since(start_instant)
-                .as_secs_f32();
+            line_flow.update(delta as f32);
 
             let mut batches = Vec::<SpriteBatch>::new();
 
             let ui_output = ui.update(
                 |ctx| {
+                    let previous_slot = slot.clone();
+                    if game_state_ui(
+                        ctx,
+                        &game_state,
+                        &grid,
+                        &mut slot,
+                        &mut slots,
+                        &mut world.camera_settings,
+                        &mut game_events,
+                    ) {
+                        settings_rx = Some(world.camera_settings.save());
+                    }
+                    if slot != previous_slot {
+                        let (tx, rx) = oneshot::channel();
+                        load_save_rx = Some(rx);
+                        let slot = slot.clone();
+                        tokio::spawn(async move {
+                            tx.send(SaveGame::load(&slot).await).ok();
+                        });
+                    }
                     if !edit {
                         return;
                     }
@@ -186,54 +442,248 @@ async fn game() {
                 [renderer.config.width, renderer.config.height],
             );
 
-            // TODO temporary
+            world.gizmo = (game_state == GameState::Map && edit)
+                .then(|| TranslateGizmo::new(grid.world_position(save_game.current_zone).into()));
+            if input.left_mouse_click.just_released() {
+                if let Some((axis, cells)) = world.release_gizmo() {
+                    if cells != 0 {
+                        save_game.current_zone =
+                            grid.nudge_zone(save_game.current_zone, axis, cells);
+                        world.set_zones(&grid);
+                    }
+                }
+            }
+
+            let map_input = state_input.filter(|_| game_state == GameState::Map);
+            if let Some(input) = map_input.filter(|_| edit) {
+                if input.left_mouse_click.just_pressed() {
+                    world.grab_gizmo();
+                }
+                // raise and lower the terrain under the camera like a brush
+                let coordinate = world.coordinate_under_camera();
+                if let Some(height) = grid.height(coordinate) {
+                    if !world.dragging_gizmo()
+                        && raise_brush.paint(&input.left_mouse_click, coordinate)
+                    {
+                        grid.set_height(coordinate, height.saturating_add(1));
+                    }
+                    if lower_brush.paint(&input.right_mouse_click, coordinate) {
+                        grid.set_height(coordinate, height.saturating_sub(1));
+                    }
+                }
+            } else if map_input.is_some_and(|input| {
+                !input.left_mouse_click.consumed
+                    && input.left_mouse_click.state == InputState::JustPressed
+            }) {
+                if let Some(zone) = world
+                    .hovered_zone(&grid)
+                    .and_then(|coordinate| grid.zones.get(&coordinate))
+                {
+                    game_events.push(GameEvent::SelectZone {
+                        level_name: zone.level_name.clone(),
+                    });
+                }
+            }
+
             if input.input_map.escape.button.state == InputState::JustPressed {
-                puzzle_player = None;
+                game_events.push(GameEvent::Escape);
             }
 
             if puzzle_player.is_none() {
                 match receive_oneshot_rx(&mut load_puzzle_rx) {
                     OneShotStatus::Closed => error!("Load level channel closed"),
-                    OneShotStatus::Value(player) => puzzle_player = Some(player),
+                    OneShotStatus::Value(mut player) => {
+                        player
+                            .editor
+                            .restore(&mut editor_session, &mut player.puzzle);
+                        puzzle_player = Some(player);
+                    }
                     _ => (),
                 }
             }
             if let Some(player) = puzzle_player.as_mut() {
-                player.update(
+                if player.update(
                     &mut batches,
                     &camera,
-                    &input,
+                    state_input,
                     mouse_coordinate,
                     &textures,
-                    time,
-                );
+                    &line_flow,
+                ) {
+                    game_events.push(GameEvent::Solved);
+                }
             }
 
-            let mut commands = vec![
-                renderer.clear(
+            if let Some(active) = transition.as_mut() {
+                let covered = active.past_midpoint();
+                let done = active.update(delta as f32);
+                if !covered && active.past_midpoint() {
+                    change_state(
+                        &mut game_state,
+                        active.to.clone(),
+                        &mut puzzle_player,
+                        &mut load_puzzle_rx,
+                        &mut input,
+                        &renderer.window,
+                    );
+                }
+                batches.push(SpriteBatch {
+                    sprites: vec![
+                        active.overlay(renderer.config.width as f32, renderer.config.height as f32)
+                    ],
+                    texture: &textures.white,
+                    blend_mode: SpriteBlendMode::Alpha,
+                    clip: None,
+                });
+                if done {
+                    transition = None;
+                }
+            }
+
+            let mut frame = FrameComposer::new(view, &LAYERS).clear(wgpu::Color {
+                r: 0.4,
+                g: 0.4,
+                b: 0.4,
+                a: 1.0,
+            });
+            frame.add("ui", view, ui_output);
+            let shown_state = transition.as_ref().map_or(&game_state, Transition::shown);
+            if *shown_state == GameState::Map {
+                let viewport = renderer.viewport();
+                // asked again as changing state above borrows the input
+                let state_input = unblocked_input(transition.as_ref(), &input);
+                frame.add(
+                    "world",
                     view,
-                    wgpu::Color {
-                        r: 0.4,
-                        g: 0.4,
-                        b: 0.4,
-                        a: 1.0,
-                    },
-                ),
-                world.update(renderer, view, delta as f32, &input),
-            ];
-            commands.push(sprite_renderer.draw(
-                &batches,
-                &renderer.device,
-                &renderer.queue,
+                    world.update(renderer, view, viewport, delta as f32, state_input),
+                );
+                frame.add(
+                    "map",
+                    view,
+                    world.draw_map(renderer, view, viewport, &grid, save_game.current_zone),
+                );
+            }
+            frame.add(
+                "sprites",
                 view,
-                [renderer.config.width as f32, renderer.config.height as f32],
-            ));
-            commands.push(ui_output);
-
-            input.clear(&renderer.window);
-            commands
-        }) {
-            println!("{err}");
+                sprite_renderer.draw_with(renderer, view, &batches),
+            );
+
+            for game_event in game_events.drain(..) {
+                if transition.is_some() {
+                    break;
+                }
+                match (&game_state, &game_event) {
+                    (GameState::Map, GameEvent::SelectZone { level_name }) => {
+                        if let Some(coordinate) = grid.find_zone(level_name) {
+                            save_game.current_zone = coordinate;
+                        }
+                    }
+                    (GameState::Puzzle { level_name }, GameEvent::Solved) => {
+                        if let Some(coordinate) = grid.find_zone(level_name) {
+                            grid.complete_zone(coordinate);
+                            save_game = SaveGame::from_grid(&grid, coordinate);
+                            match save_game.save(&slot) {
+                                Ok(rx) => save_rx = Some(rx),
+                                Err(err) => error!("Couldn't save game: {err}"),
+                            }
+                        }
+                    }
+                    _ => (),
+                }
+                let next_state = game_state.transition(game_event);
+                if next_state == game_state {
+                    continue;
+                }
+                if game_state.fades_to(&next_state) {
+                    transition = Some(Transition::new(
+                        game_state.clone(),
+                        next_state,
+                        TRANSITION_DURATION,
+                    ));
+                    continue;
+                }
+                change_state(
+                    &mut game_state,
+                    next_state,
+                    &mut puzzle_player,
+                    &mut load_puzzle_rx,
+                    &mut input,
+                    &renderer.window,
+                );
+            }
+
+            match receive_oneshot_rx(&mut load_save_rx) {
+                OneShotStatus::Value(loaded) => {
+                    save_game = loaded.unwrap_or_else(|err| {
+                        info!("Starting a new game, couldn't load save: {err}");
+                        SaveGame::new(&map)
+                    });
+                    grid = map.clone();
+                    save_game.apply(&mut grid);
+                    world.teleport(grid.world_position(save_game.current_zone));
+                }
+                OneShotStatus::Closed => error!("Load save channel closed"),
+                _ => (),
+            }
+            match receive_oneshot_rx(&mut save_rx) {
+                OneShotStatus::Value(Err(err)) => error!("Couldn't save game: {err}"),
+                OneShotStatus::Closed => error!("Save game channel closed"),
+                _ => (),
+            }
+            match receive_oneshot_rx(&mut settings_rx) {
+                OneShotStatus::Value(Err(err)) => {
+                    error!("Couldn't save camera settings: {err}")
+                }
+                OneShotStatus::Closed => error!("Save camera settings channel closed"),
+                _ => (),
+            }
+            match receive_oneshot_rx(&mut map_rx) {
+                OneShotStatus::Value(Err(err)) => error!("Couldn't save the map: {err}"),
+                OneShotStatus::Closed => error!("Save map channel closed"),
+                _ => (),
+            }
+
+            input.clear(&renderer.window, Duration::from_secs_f64(delta));
+            frame.finish(renderer)
+        };
+        match renderer.handle_event(&event, control_flow, draw_frame) {
+            Ok(FrameOutcome::Recovered) => info!("Reconfigured the lost surface"),
+            Ok(_) => (),
+            Err(err) => {
+                error!("Can't render: {err}");
+                *control_flow = ControlFlow::Exit;
+            }
         }
     });
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn keys_pressed_mid_transition_change_nothing() {
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let mut player = runtime.block_on(PuzzlePlayer::new("alpha".to_string()));
+        assert!(player.puzzle.input(&SigilCoordinate::new(1, 1)));
+        // SAFETY: dummy ids are only compared, never passed to the windowing system
+        let window = unsafe { winit::window::WindowId::dummy() };
+        let mut input = Input::new(window, Actions::default());
+        input.input_map.undo.button.state = InputState::JustPressed;
+
+        let level_name = "alpha".to_string();
+        let mut transition = Transition::new(
+            GameState::Map,
+            GameState::Puzzle { level_name },
+            TRANSITION_DURATION,
+        );
+        // the puzzle's shown once the screen's covered
+        transition.update(TRANSITION_DURATION / 2.0);
+        player.handle_input(unblocked_input(Some(&transition), &input), None);
+        assert!(!player.puzzle.redo());
+
+        player.handle_input(unblocked_input(None, &input), None);
+        assert!(player.puzzle.redo());
+    }
+}
@@ -0,0 +1,312 @@
+use excali_3d::Axis;
+use excali_io::tokio::sync::oneshot;
+use excali_io::{
+    load_from_toml, resolve_load_path, resolve_save_path, save_to_toml, FromKeyError, IoError,
+    SerializeKey,
+};
+use nalgebra::{SMatrix, Vector2, Vector3};
+use rapier3d::prelude::{Collider, ColliderBuilder};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+use crate::APP_NAME;
+
+pub const CHUNK_SIZE: usize = 64;
+const MAP_PATH: &str = "./assets/map.toml";
+/// the edited map, relative to the data directory
+const USER_MAP_PATH: &str = "map.toml";
+
+pub type MapCoordinate = Vector2<i32>;
+pub type HeightMap = SMatrix<u16, CHUNK_SIZE, CHUNK_SIZE>;
+/// half the size of the box around each zone which can be clicked
+const ZONE_HALF_EXTENTS: Vector3<f32> = Vector3::new(0.5, 1.0, 0.5);
+
+/// How far the player has gotten with a zone
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ZoneState {
+    #[default]
+    Locked,
+    Unlocked,
+    Solved,
+}
+
+fn map_save_path() -> PathBuf {
+    resolve_save_path(APP_NAME, USER_MAP_PATH)
+}
+
+/// A puzzle placed on the map
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Zone {
+    pub level_name: String,
+    pub next_zones: Vec<MapCoordinate>,
+    #[serde(skip)]
+    pub state: ZoneState,
+}
+
+/// The map's terrain and the zones placed on it
+#[derive(Clone, Debug)]
+pub struct Grid {
+    pub height_map: HeightMap,
+    pub zones: HashMap<MapCoordinate, Zone>,
+    pub starting_zone: MapCoordinate,
+}
+
+impl Grid {
+    /// loads the edited map when there is one, otherwise the bundled map
+    pub async fn load() -> Result<Self, String> {
+        match load_from_toml::<SerialableGrid>(resolve_load_path(APP_NAME, USER_MAP_PATH, MAP_PATH))
+            .await
+        {
+            Ok(serialable_grid) => match Grid::try_from(serialable_grid) {
+                Ok(grid) => Ok(grid),
+                Err(err) => Err(format!("{err}")),
+            },
+            Err(err) => Err(err.to_string()),
+        }
+    }
+
+    /// saves the map to the data directory, leaving the bundled map untouched
+    pub fn save(&self) -> oneshot::Receiver<Result<(), IoError>> {
+        save_to_toml(&SerialableGrid::from(self.clone()), map_save_path())
+    }
+
+    /// solves the zone and unlocks the zones after it
+    pub fn complete_zone(&mut self, coordinate: MapCoordinate) {
+        let Some(zone) = self.zones.get_mut(&coordinate) else {
+            return;
+        };
+        zone.state = ZoneState::Solved;
+        for next in zone.next_zones.clone() {
+            if let Some(next_zone) = self.zones.get_mut(&next) {
+                if next_zone.state == ZoneState::Locked {
+                    next_zone.state = ZoneState::Unlocked;
+                }
+            }
+        }
+    }
+
+    /// moves the zone to an empty coordinate on the map, keeping the zones pointing to it,
+    /// returns if it moved
+    pub fn move_zone(&mut self, from: MapCoordinate, to: MapCoordinate) -> bool {
+        if self.zones.contains_key(&to) || self.height(to).is_none() {
+            return false;
+        }
+        let Some(zone) = self.zones.remove(&from) else {
+            return false;
+        };
+        self.zones.insert(to, zone);
+        for zone in self.zones.values_mut() {
+            for next in zone.next_zones.iter_mut().filter(|next| **next == from) {
+                *next = to;
+            }
+        }
+        if self.starting_zone == from {
+            self.starting_zone = to;
+        }
+        true
+    }
+
+    /// moves a zone by whole cells along an axis, up and down changes the terrain under it,
+    /// returns where the zone ended up
+    pub fn nudge_zone(
+        &mut self,
+        coordinate: MapCoordinate,
+        axis: Axis,
+        cells: i32,
+    ) -> MapCoordinate {
+        let to = match axis {
+            Axis::X => coordinate + MapCoordinate::new(cells, 0),
+            Axis::Z => coordinate + MapCoordinate::new(0, cells),
+            Axis::Y => {
+                if let Some(height) = self.height(coordinate) {
+                    let height = (height as i32 + cells).clamp(0, u16::MAX as i32);
+                    self.set_height(coordinate, height as u16);
+                }
+                return coordinate;
+            }
+        };
+        match self.move_zone(coordinate, to) {
+            true => to,
+            false => coordinate,
+        }
+    }
+
+    /// returns the coordinate of the zone with the level
+    pub fn find_zone(&self, level_name: &str) -> Option<MapCoordinate> {
+        self.zones
+            .iter()
+            .find(|(_, zone)| zone.level_name == level_name)
+            .map(|(coordinate, _)| *coordinate)
+    }
+
+    /// returns none outside of the map
+    pub fn height(&self, coordinate: MapCoordinate) -> Option<u16> {
+        let x = usize::try_from(coordinate.x).ok()?;
+        let y = usize::try_from(coordinate.y).ok()?;
+        self.height_map.get((x, y)).copied()
+    }
+
+    /// changes the terrain's height, ignored outside of the map
+    pub fn set_height(&mut self, coordinate: MapCoordinate, height: u16) {
+        let (Ok(x), Ok(y)) = (usize::try_from(coordinate.x), usize::try_from(coordinate.y)) else {
+            return;
+        };
+        if let Some(cell) = self.height_map.get_mut((x, y)) {
+            *cell = height;
+        }
+    }
+
+    /// the point in the world above a coordinate's terrain
+    pub fn world_position(&self, coordinate: MapCoordinate) -> Vector3<f32> {
+        let height = self.height(coordinate).unwrap_or_default();
+        Vector3::new(
+            coordinate.x as f32,
+            height as f32 + 2.0,
+            coordinate.y as f32,
+        )
+    }
+
+    /// a sensor around each zone, for clicking and world interactions
+    pub fn zone_colliders(&self) -> Vec<(MapCoordinate, Collider)> {
+        self.sorted_zones()
+            .into_iter()
+            .map(|(coordinate, _)| {
+                (
+                    *coordinate,
+                    ColliderBuilder::cuboid(
+                        ZONE_HALF_EXTENTS.x,
+                        ZONE_HALF_EXTENTS.y,
+                        ZONE_HALF_EXTENTS.z,
+                    )
+                    .translation(self.world_position(*coordinate))
+                    .sensor(true)
+                    .build(),
+                )
+            })
+            .collect()
+    }
+
+    /// zones sorted by coordinate so they're listed in a stable order
+    pub fn sorted_zones(&self) -> Vec<(&MapCoordinate, &Zone)> {
+        let mut zones: Vec<(&MapCoordinate, &Zone)> = self.zones.iter().collect();
+        zones.sort_by_key(|(coordinate, _)| (coordinate.x, coordinate.y));
+        zones
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct SerialableGrid {
+    height_map: HeightMap,
+    zones: HashMap<String, Zone>,
+    starting_zone: MapCoordinate,
+}
+
+impl From<Grid> for SerialableGrid {
+    fn from(value: Grid) -> Self {
+        Self {
+            height_map: value.height_map,
+            zones: MapCoordinate::serialize_hash_map(&value.zones),
+            starting_zone: value.starting_zone,
+        }
+    }
+}
+
+impl TryFrom<SerialableGrid> for Grid {
+    type Error = FromKeyError<i32>;
+    fn try_from(value: SerialableGrid) -> Result<Self, Self::Error> {
+        let mut zones = MapCoordinate::deserialize_hash_map(&value.zones)?;
+        if let Some(zone) = zones.get_mut(&value.starting_zone) {
+            zone.state = ZoneState::Unlocked;
+        }
+        Ok(Self {
+            height_map: value.height_map,
+            zones,
+            starting_zone: value.starting_zone,
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn grid_can_serialize() {
+        let grid = Grid {
+            height_map: HeightMap::from_element(3),
+            zones: HashMap::from([(
+                MapCoordinate::new(1, 2),
+                Zone {
+                    level_name: "alpha".to_string(),
+                    next_zones: vec![],
+                    state: ZoneState::Solved,
+                },
+            )]),
+            starting_zone: MapCoordinate::new(1, 2),
+        };
+        let serialized = excali_io::toml::to_string(&SerialableGrid::from(grid)).unwrap();
+        let grid =
+            Grid::try_from(excali_io::toml::from_str::<SerialableGrid>(&serialized).unwrap())
+                .unwrap();
+        assert_eq!(grid.zones[&MapCoordinate::new(1, 2)].level_name, "alpha");
+        assert_eq!(grid.height_map, HeightMap::from_element(3));
+        assert_eq!(
+            grid.zones[&MapCoordinate::new(1, 2)].state,
+            ZoneState::Unlocked
+        );
+    }
+
+    #[test]
+    fn edited_map_is_saved_to_the_data_directory() {
+        let path = map_save_path();
+        assert!(path.starts_with(excali_io::data_dir(APP_NAME)));
+        assert!(!path.starts_with("./assets"));
+    }
+
+    #[test]
+    fn map_asset_loads() {
+        let contents = std::fs::read_to_string(MAP_PATH).unwrap();
+        let grid = Grid::try_from(excali_io::toml::from_str::<SerialableGrid>(&contents).unwrap())
+            .unwrap();
+        assert_eq!(grid.zones[&grid.starting_zone].level_name, "alpha");
+    }
+
+    #[test]
+    fn completing_zone_unlocks_next() {
+        let contents = std::fs::read_to_string(MAP_PATH).unwrap();
+        let mut grid =
+            Grid::try_from(excali_io::toml::from_str::<SerialableGrid>(&contents).unwrap())
+                .unwrap();
+        let coordinate = grid.find_zone("alpha-double").unwrap();
+        grid.complete_zone(coordinate);
+        assert_eq!(grid.zones[&coordinate].state, ZoneState::Solved);
+        for next in grid.zones[&coordinate].next_zones.iter() {
+            assert_eq!(grid.zones[next].state, ZoneState::Unlocked);
+        }
+        assert_eq!(
+            grid.zones[&grid.find_zone("octogon").unwrap()].state,
+            ZoneState::Locked
+        );
+    }
+
+    #[test]
+    fn moved_zone_keeps_links() {
+        let contents = std::fs::read_to_string(MAP_PATH).unwrap();
+        let mut grid =
+            Grid::try_from(excali_io::toml::from_str::<SerialableGrid>(&contents).unwrap())
+                .unwrap();
+        let from = grid.zones[&grid.starting_zone].next_zones[0];
+        let to = from + MapCoordinate::new(0, 1);
+        assert!(!grid.move_zone(from, grid.starting_zone));
+        assert!(!grid.move_zone(from, MapCoordinate::new(-1, 0)));
+        assert!(grid.move_zone(from, to));
+        assert!(!grid.zones.contains_key(&from));
+        assert!(grid.zones[&grid.starting_zone].next_zones.contains(&to));
+
+        let height = grid.height(to).unwrap();
+        assert_eq!(grid.nudge_zone(to, Axis::Y, 2), to);
+        assert_eq!(grid.height(to), Some(height + 2));
+        assert_eq!(grid.nudge_zone(to, Axis::Z, -1), from);
+    }
+}
@@ -1,7 +1,7 @@
 use excali_io::{FromKeyError, SerializeKey};
 use excali_ui::Mode;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{HashMap, VecDeque};
 use std::f32::consts::PI;
 
 use excali_sprite::*;
@@ -10,16 +10,75 @@ use nalgebra::Vector2;
 
 use crate::textures::Textures;
 
-const SIGIL_DISTANCE: f32 = 23.0;
+/// the default distance between neighbouring coordinates, see [Puzzle::spacing]
+pub const SIGIL_DISTANCE: f32 = 23.0;
+/// how many inputs can be undone by default
+const MAX_HISTORY: usize = 100;
+/// the line texture's size, lines this wide draw it unscaled
 const LINE_WIDTH: f32 = 19.0;
 
+/// How puzzle lines are drawn, so themes and difficulties can style them
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct LineStyle {
+    /// thickness in pixels, the texture repeats once per width along a line
+    pub width: f32,
+    /// rounds off each end of a line with a circle
+    pub caps: bool,
+}
+
+impl Default for LineStyle {
+    fn default() -> Self {
+        Self {
+            width: LINE_WIDTH,
+            caps: true,
+        }
+    }
+}
+
+/// Scrolls the lines' texture along them, wrapped so it keeps its precision over long sessions
+#[derive(Clone, Copy, Debug)]
+pub struct LineFlow {
+    /// texture lengths per second
+    pub speed: f32,
+    pub enabled: bool,
+    phase: f32,
+}
+
+impl Default for LineFlow {
+    fn default() -> Self {
+        Self {
+            speed: 1.0,
+            enabled: true,
+            phase: 0.0,
+        }
+    }
+}
+
+impl LineFlow {
+    pub fn update(&mut self, delta: f32) {
+        if self.enabled {
+            self.phase = (self.phase + self.speed * delta).rem_euclid(1.0);
+        }
+    }
+
+    /// how far along the texture has scrolled, from 0 to 1
+    pub fn phase(&self) -> f32 {
+        self.phase
+    }
+}
+
 pub trait FromSigilCoordinate {
-    fn from_sigil_coordinate(coordinate: SigilCoordinate, camera: &Transform) -> Self;
+    fn from_sigil_coordinate(coordinate: SigilCoordinate, camera: &Transform, spacing: f32)
+        -> Self;
 }
 
 impl FromSigilCoordinate for Transform {
-    fn from_sigil_coordinate(coordinate: SigilCoordinate, camera: &Transform) -> Self {
-        camera * &Transform::from_position(coordinate.position())
+    fn from_sigil_coordinate(
+        coordinate: SigilCoordinate,
+        camera: &Transform,
+        spacing: f32,
+    ) -> Self {
+        camera * &Transform::from_position(coordinate.position(spacing))
     }
 }
 
@@ -38,24 +97,36 @@ impl Mode for Orb {
             Self::Octogon => Self::Circle,
         }
     }
-}
 
-impl ToString for Orb {
-    fn to_string(&self) -> String {
-        match self {
-            Self::Circle => "Circle".to_string(),
-            Self::Diamond => "Diamond".to_string(),
-            Self::Octogon => "Octogon".to_string(),
+    fn change_back(&self) -> Self {
+        match *self {
+            Self::Circle => Self::Octogon,
+            Self::Diamond => Self::Circle,
+            Self::Octogon => Self::Diamond,
         }
     }
 }
 
+impl std::fmt::Display for Orb {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                Self::Circle => "Circle",
+                Self::Diamond => "Diamond",
+                Self::Octogon => "Octogon",
+            }
+        )
+    }
+}
+
 impl Orb {
     fn allow_intersections(&self) -> bool {
         *self == Self::Diamond
     }
 
-    // TODO delete colinear lines, maybe
+    /// the extended lines can leave collinear lines behind, see [Puzzle::simplify_lines]
     fn effect(&self, coordinate: SigilCoordinate, to: SigilCoordinate, lines: &mut [Line]) {
         if *self != Orb::Octogon {
             return;
@@ -68,6 +139,15 @@ impl Orb {
         }
     }
 
+    /// tint of the lines drawn from this orb
+    pub fn line_color(&self) -> Color {
+        match self {
+            Self::Circle => Color::new(1.0, 1.0, 1.0, 1.0),
+            Self::Diamond => Color::new(0.4, 0.9, 1.0, 1.0),
+            Self::Octogon => Color::new(1.0, 0.6, 0.9, 1.0),
+        }
+    }
+
     pub fn texture_coordinate(&self, active: bool) -> TextureCoordinate {
         let x = if active { 0.5 } else { 0.0 };
         let y = match self {
@@ -90,16 +170,22 @@ pub enum Rune {
     Sigma,
     Delta,
     Phi,
+    Theta,
 }
 
-impl ToString for Rune {
-    fn to_string(&self) -> String {
-        match self {
-            Self::Alpha => "Alpha".to_string(),
-            Self::Sigma => "Sigma".to_string(),
-            Self::Delta => "Delta".to_string(),
-            Self::Phi => "Phi".to_string(),
-        }
+impl std::fmt::Display for Rune {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                Self::Alpha => "Alpha",
+                Self::Sigma => "Sigma",
+                Self::Delta => "Delta",
+                Self::Phi => "Phi",
+                Self::Theta => "Theta",
+            }
+        )
     }
 }
 
@@ -109,7 +195,18 @@ impl Mode for Rune {
             Self::Alpha => Self::Delta,
             Self::Delta => Self::Phi,
             Self::Phi => Self::Sigma,
-            Self::Sigma => Self::Alpha,
+            Self::Sigma => Self::Theta,
+            Self::Theta => Self::Alpha,
+        }
+    }
+
+    fn change_back(&self) -> Self {
+        match *self {
+            Self::Alpha => Self::Theta,
+            Self::Delta => Self::Alpha,
+            Self::Phi => Self::Delta,
+            Self::Sigma => Self::Phi,
+            Self::Theta => Self::Sigma,
         }
     }
 }
@@ -178,34 +275,34 @@ impl Rune {
             }
             Self::Sigma => {
                 // true on loops
+                lines
+                    .iter()
+                    .any(|line| line.coordinates().contains(&coordinate) && line.in_loop(lines))
+            }
+            Self::Theta => {
+                // true strictly inside a loop, a ray to the right crosses loops an odd number of
+                // times
+                let mut inside = false;
                 for line in lines.iter() {
-                    if !line.coordinates().contains(&coordinate) {
+                    if line.coordinates().contains(&coordinate) {
+                        return false;
+                    }
+                    if !line.in_loop(lines) {
                         continue;
                     }
-
-                    let mut frontier = vec![line.end];
-                    let mut passed = Vec::<SigilCoordinate>::new();
-                    while let Some(frontier_coordinate) = frontier.pop() {
-                        if frontier_coordinate == line.start {
-                            return true;
-                        }
-                        passed.push(frontier_coordinate);
-                        'next: for next_line in lines.iter() {
-                            if next_line.start != frontier_coordinate {
-                                continue;
-                            }
-
-                            for passed_coordinate in passed.iter() {
-                                if *passed_coordinate == next_line.end {
-                                    continue 'next;
-                                }
-                            }
-
-                            frontier.push(next_line.end);
-                        }
+                    let (low, high) = match line.start.y < line.end.y {
+                        true => (line.start, line.end),
+                        false => (line.end, line.start),
+                    };
+                    // half open so a ray through where two lines meet crosses once
+                    if low.y <= coordinate.y
+                        && coordinate.y < high.y
+                        && orientation(low, high, coordinate) == Orientation::CounterClockwise
+                    {
+                        inside = !inside;
                     }
                 }
-                false
+                inside
             }
         }
     }
@@ -213,12 +310,13 @@ impl Rune {
     pub fn texture_coordinate(&self) -> TextureCoordinate {
         let x = match self {
             Self::Alpha => 0.0,
-            Self::Sigma => 0.25,
-            Self::Delta => 0.5,
-            Self::Phi => 0.75,
+            Self::Sigma => 0.2,
+            Self::Delta => 0.4,
+            Self::Phi => 0.6,
+            Self::Theta => 0.8,
         };
         TextureCoordinate {
-            width: 0.25,
+            width: 0.2,
             height: -1.0,
             y: 1.0,
             x,
@@ -236,23 +334,30 @@ pub type CoordinateScalar = i32;
 pub type Position = Vector2<f32>;
 pub type SigilCoordinate = Vector2<CoordinateScalar>;
 
-pub trait Coordinate {
-    fn position(&self) -> Position;
-    fn from_position(position: Position) -> Self;
+/// A point on a grid with spacing between each coordinate
+pub trait Coordinate: Sized {
+    fn position(&self, spacing: f32) -> Position;
+    /// snaps to the nearest coordinate
+    fn from_position(position: Position, spacing: f32) -> Self;
+
+    /// maps a position on screen back through the camera
+    fn from_screen_position(position: Position, camera: &Transform, spacing: f32) -> Self {
+        Self::from_position(
+            (&camera.inverse() * &Transform::from_position(position)).position,
+            spacing,
+        )
+    }
 }
 
 impl Coordinate for SigilCoordinate {
-    fn position(&self) -> Vector2<f32> {
-        Vector2::new(
-            self.x as f32 * SIGIL_DISTANCE,
-            self.y as f32 * SIGIL_DISTANCE,
-        )
+    fn position(&self, spacing: f32) -> Vector2<f32> {
+        Vector2::new(self.x as f32 * spacing, self.y as f32 * spacing)
     }
 
-    fn from_position(position: Vector2<f32>) -> Self {
+    fn from_position(position: Vector2<f32>, spacing: f32) -> Self {
         Vector2::new(
-            (position.x / SIGIL_DISTANCE + 0.5).floor() as CoordinateScalar,
-            (position.y / SIGIL_DISTANCE + 0.5).floor() as CoordinateScalar,
+            (position.x / spacing + 0.5).floor() as CoordinateScalar,
+            (position.y / spacing + 0.5).floor() as CoordinateScalar,
         )
     }
 }
@@ -261,6 +366,9 @@ impl Coordinate for SigilCoordinate {
 pub struct Line {
     pub start: SigilCoordinate,
     pub end: SigilCoordinate,
+    /// the orb the line was drawn from, lines placed in the editor have none
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub orb: Option<Orb>,
 }
 
 impl Line {
@@ -287,9 +395,46 @@ impl Line {
         false
     }
 
-    fn sprite(&self, time: f32, camera: &Transform) -> Sprite {
-        let start = self.start.position();
-        let end = self.end.position();
+    /// returns if following the lines from this one's end leads back to its start
+    fn in_loop(&self, lines: &[Line]) -> bool {
+        let mut frontier = vec![self.end];
+        let mut passed = Vec::<SigilCoordinate>::new();
+        while let Some(frontier_coordinate) = frontier.pop() {
+            if frontier_coordinate == self.start {
+                return true;
+            }
+            passed.push(frontier_coordinate);
+            'next: for next_line in lines.iter() {
+                if next_line.start != frontier_coordinate {
+                    continue;
+                }
+
+                for passed_coordinate in passed.iter() {
+                    if *passed_coordinate == next_line.end {
+                        continue 'next;
+                    }
+                }
+
+                frontier.push(next_line.end);
+            }
+        }
+        false
+    }
+
+    fn color(&self) -> Color {
+        self.orb
+            .map_or(Color::new(1.0, 1.0, 1.0, 1.0), |orb| orb.line_color())
+    }
+
+    fn sprite(
+        &self,
+        flow: &LineFlow,
+        style: &LineStyle,
+        camera: &Transform,
+        spacing: f32,
+    ) -> Sprite {
+        let start = self.start.position(spacing);
+        let end = self.end.position(spacing);
         let position = (start + end) / 2.0;
         let direction = end - start;
         let magnitude = direction.magnitude();
@@ -300,23 +445,48 @@ impl Line {
             (-direction.y / magnitude).asin()
         };
 
+        let scale = style.width / LINE_WIDTH;
         Sprite {
             transform: camera
                 * &Transform {
                     position,
                     rotation,
-                    scale: Vector2::new(1.0, 1.0),
+                    scale: Vector2::new(scale, scale),
                 },
             texture_coordinate: TextureCoordinate {
                 height: 1.0,
-                x: -time,
+                x: -flow.phase(),
                 y: 0.0,
-                width: magnitude / LINE_WIDTH,
+                width: magnitude / style.width,
             },
+            color: self.color(),
             ..Default::default()
         }
     }
 
+    /// circles as wide as the line at both ends, texture_size is the circle texture's width
+    fn caps(
+        &self,
+        style: &LineStyle,
+        camera: &Transform,
+        texture_size: f32,
+        spacing: f32,
+    ) -> [Sprite; 2] {
+        let scale = style.width / texture_size;
+        [self.start, self.end].map(|coordinate| Sprite {
+            transform: camera
+                * &Transform {
+                    position: coordinate.position(spacing),
+                    rotation: 0.0,
+                    scale: Vector2::new(scale, scale),
+                },
+            color: self.color(),
+            ..Default::default()
+        })
+    }
+
+    /// exact on the integer grid so there's no epsilon, lines may touch at an endpoint,
+    /// end on another line or extend one in the same direction but can't cross or overlap
     fn intersects(&self, other: &Self) -> bool {
         // uses https://www.geeksforgeeks.org/check-if-two-given-line-segments-intersect/
         fn on_segment(a: Point, b: Point, c: Point) -> bool {
@@ -422,51 +592,101 @@ impl Line {
 #[derive(Debug)]
 pub struct ActivePuzzle {
     puzzle: Puzzle,
-    history: Vec<Puzzle>,
+    /// the oldest puzzle first
+    history: VecDeque<Puzzle>,
+    /// the oldest puzzles are forgotten past this many, applies from the next input
+    pub max_history: usize,
+    /// undone puzzles, the most recently undone last
+    future: Vec<Puzzle>,
 }
 
 impl ActivePuzzle {
     pub fn new(puzzle: Puzzle) -> Self {
+        Self::with_max_history(puzzle, MAX_HISTORY)
+    }
+
+    pub fn with_max_history(puzzle: Puzzle, max_history: usize) -> Self {
         Self {
             puzzle,
-            history: Vec::new(),
+            history: VecDeque::new(),
+            max_history,
+            future: Vec::new(),
         }
     }
 
     pub fn load_puzzle(&mut self, puzzle: Puzzle) {
         self.puzzle = puzzle;
         self.history.clear();
+        self.future.clear();
     }
 
     pub fn undo(&mut self) -> bool {
-        if let Some(new_puzzle) = self.history.pop() {
-            self.puzzle = new_puzzle;
+        if let Some(new_puzzle) = self.history.pop_back() {
+            self.future
+                .push(std::mem::replace(&mut self.puzzle, new_puzzle));
             return true;
         }
         false
     }
 
-    /// returns if the input was processed
+    /// brings back the last undone puzzle, returns if there was one
+    pub fn redo(&mut self) -> bool {
+        if let Some(new_puzzle) = self.future.pop() {
+            let past = std::mem::replace(&mut self.puzzle, new_puzzle);
+            self.remember(past);
+            return true;
+        }
+        false
+    }
+
+    /// returns if the input was processed, processed input can't be redone past
     pub fn input(&mut self, coordinate: &SigilCoordinate) -> bool {
         let past = self.puzzle.clone();
         if self.puzzle.input(coordinate) {
-            self.history.push(past);
+            self.remember(past);
+            self.future.clear();
+            return true;
+        }
+        false
+    }
+
+    /// pushes onto the history, forgetting the oldest puzzles past the max
+    fn remember(&mut self, past: Puzzle) {
+        self.history.push_back(past);
+        while self.history.len() > self.max_history {
+            self.history.pop_front();
+        }
+    }
+
+    /// see [Puzzle::remove_line_at], the removal can be undone
+    pub fn remove_line_at(&mut self, coordinate: SigilCoordinate) -> bool {
+        let past = self.puzzle.clone();
+        if self.puzzle.remove_line_at(coordinate) {
+            self.remember(past);
+            self.future.clear();
             return true;
         }
         false
     }
 
+    /// see [Puzzle::solved], the map's zone is completed once this is true
     pub fn solved(&self) -> bool {
         self.puzzle.solved()
     }
 
+    /// see [Puzzle::spacing]
+    pub fn spacing(&self) -> f32 {
+        self.puzzle.spacing
+    }
+
     pub fn sprite_batches<'a>(
         &self,
-        time: f32,
+        flow: &LineFlow,
+        style: &LineStyle,
         camera: &Transform,
         textures: &'a Textures,
     ) -> Vec<SpriteBatch<'a>> {
-        self.puzzle.sprite_batches(time, camera, textures)
+        self.puzzle.sprite_batches(flow, style, camera, textures)
     }
 }
 
@@ -475,6 +695,8 @@ pub struct Puzzle {
     pub sigils: HashMap<SigilCoordinate, Sigil>,
     pub lines: Vec<Line>,
     pub cursor: SigilCoordinate,
+    /// how far apart the sigils are drawn
+    pub spacing: f32,
 }
 
 impl Default for Puzzle {
@@ -483,6 +705,7 @@ impl Default for Puzzle {
             sigils: HashMap::new(),
             lines: Vec::new(),
             cursor: Vector2::zeros(),
+            spacing: SIGIL_DISTANCE,
         }
     }
 }
@@ -497,18 +720,20 @@ impl Puzzle {
             let line = Line {
                 start: self.cursor,
                 end: *coordinate,
+                orb: Some(cursor_rune.orb),
             };
 
             if !cursor_rune.orb.allow_intersections() && self.intersects_lines(&line) {
                 return false;
             }
 
-            if self.sigils.get(coordinate).is_some() {
+            if self.sigils.contains_key(coordinate) {
                 cursor_rune
                     .orb
                     .effect(self.cursor, *coordinate, &mut self.lines);
 
                 self.lines.push(line);
+                self.simplify_lines();
                 self.cursor = *coordinate;
                 return true;
             }
@@ -516,7 +741,67 @@ impl Puzzle {
         false
     }
 
+    /// deletes the most recent line touching the coordinate and moves the cursor back to its
+    /// start, returns if a line was deleted. Only the line as it is now is deleted, a line an
+    /// Octogon orb extended isn't shortened back to what it was
+    pub fn remove_line_at(&mut self, coordinate: SigilCoordinate) -> bool {
+        if self.solved() {
+            return false;
+        }
+        let Some(index) = self
+            .lines
+            .iter()
+            .rposition(|line| line.coordinates().contains(&coordinate))
+        else {
+            return false;
+        };
+        self.cursor = self.lines.remove(index).start;
+        true
+    }
+
+    /// merges lines which carry straight on from another line's end into it, as long as there's
+    /// no sigil where they meet, no other line meets there and they're from the same orb
+    pub fn simplify_lines(&mut self) {
+        while let Some((first, second)) = self.mergeable_lines() {
+            self.lines[first].end = self.lines[second].end;
+            self.lines.remove(second);
+        }
+    }
+
+    /// the indices of a line and the line carrying straight on from it
+    fn mergeable_lines(&self) -> Option<(usize, usize)> {
+        for (first, line) in self.lines.iter().enumerate() {
+            let joint = line.end;
+            if self.sigils.contains_key(&joint)
+                || self
+                    .lines
+                    .iter()
+                    .filter(|other| other.start == joint || other.end == joint)
+                    .count()
+                    != 2
+            {
+                continue;
+            }
+            let Some(second) = self.lines.iter().position(|other| other.start == joint) else {
+                continue;
+            };
+            let next = &self.lines[second];
+            if second != first
+                && next.orb == line.orb
+                && orientation(line.start, joint, next.end) == Orientation::Collinear
+                && (joint - line.start).dot(&(next.end - joint)) > 0
+            {
+                return Some((first, second));
+            }
+        }
+        None
+    }
+
+    /// every sigil's rune is active, a puzzle without sigils is never solved
     pub fn solved(&self) -> bool {
+        if self.sigils.is_empty() {
+            return false;
+        }
         for (coordinate, sigil) in self.sigils.iter() {
             if !sigil.rune.active(*coordinate, &self.lines, &self.sigils) {
                 return false;
@@ -536,7 +821,8 @@ impl Puzzle {
 
     pub fn sprite_batches<'a>(
         &self,
-        time: f32,
+        flow: &LineFlow,
+        style: &LineStyle,
         camera: &Transform,
         textures: &'a Textures,
     ) -> Vec<SpriteBatch<'a>> {
@@ -544,7 +830,7 @@ impl Puzzle {
         let mut sigil_sprites = Vec::<Sprite>::new();
 
         for (coordinate, rune) in self.sigils.iter() {
-            let transform = camera * &Transform::from_position(coordinate.position());
+            let transform = camera * &Transform::from_position(coordinate.position(self.spacing));
             let orb_coordinate = rune.orb.texture_coordinate(rune.rune.active(
                 *coordinate,
                 &self.lines,
@@ -568,29 +854,59 @@ impl Puzzle {
             sprites: self
                 .lines
                 .iter()
-                .map(|line| line.sprite(time, camera))
+                .map(|line| line.sprite(flow, style, camera, self.spacing))
                 .collect(),
             texture: &textures.line,
+            blend_mode: SpriteBlendMode::Alpha,
+            clip: None,
+        };
+
+        let caps = SpriteBatch {
+            sprites: match style.caps {
+                true => self
+                    .lines
+                    .iter()
+                    .flat_map(|line| {
+                        line.caps(
+                            style,
+                            camera,
+                            textures.cursor.data.width as f32,
+                            self.spacing,
+                        )
+                    })
+                    .collect(),
+                false => Vec::new(),
+            },
+            texture: &textures.cursor,
+            blend_mode: SpriteBlendMode::Alpha,
+            clip: None,
         };
 
         let cursor = SpriteBatch {
             sprites: vec![Sprite {
-                transform: camera * &Transform::from_position(self.cursor.position()),
+                transform: camera * &Transform::from_position(self.cursor.position(self.spacing)),
                 ..Default::default()
             }],
             texture: &textures.cursor,
+            blend_mode: SpriteBlendMode::Alpha,
+            clip: None,
         };
 
         vec![
             lines,
+            caps,
             cursor,
             SpriteBatch {
                 sprites: orb_sprites,
                 texture: &textures.orbs,
+                blend_mode: SpriteBlendMode::Alpha,
+                clip: None,
             },
             SpriteBatch {
                 sprites: sigil_sprites,
                 texture: &textures.sigils,
+                blend_mode: SpriteBlendMode::Alpha,
+                clip: None,
             },
         ]
     }
@@ -601,6 +917,13 @@ pub struct SerialablePuzzle {
     sigils: HashMap<String, Sigil>,
     lines: Vec<Line>,
     cursor: SigilCoordinate,
+    #[serde(default = "default_spacing")]
+    spacing: f32,
+}
+
+/// puzzles saved before the spacing could change
+fn default_spacing() -> f32 {
+    SIGIL_DISTANCE
 }
 
 impl From<Puzzle> for SerialablePuzzle {
@@ -609,6 +932,7 @@ impl From<Puzzle> for SerialablePuzzle {
             sigils: SigilCoordinate::serialize_hash_map(&value.sigils),
             lines: value.lines,
             cursor: value.cursor,
+            spacing: value.spacing,
         }
     }
 }
@@ -620,6 +944,7 @@ impl TryFrom<SerialablePuzzle> for Puzzle {
             sigils: Vector2::<i32>::deserialize_hash_map(&value.sigils)?,
             lines: value.lines,
             cursor: value.cursor,
+            spacing: value.spacing,
         })
     }
 }
@@ -634,7 +959,7 @@ enum Orientation {
 
 fn orientation(a: Point, b: Point, c: Point) -> Orientation {
     match (b.y - a.y) * (c.x - b.x) - (b.x - a.x) * (c.y - b.y) {
-        val if val == 0 => Orientation::Collinear,
+        0 => Orientation::Collinear,
         val if val > 0 => Orientation::Clockwise,
         _ => Orientation::CounterClockwise,
     }
@@ -651,8 +976,365 @@ mod test {
             sigils: runes,
             lines: vec![],
             cursor: SigilCoordinate::zeros(),
+            spacing: 40.0,
         };
         let serialized = SerialablePuzzle::from(puzzle);
-        Puzzle::try_from(serialized).unwrap();
+        assert_eq!(Puzzle::try_from(serialized).unwrap().spacing, 40.0);
+    }
+
+    #[test]
+    fn clicks_map_through_camera() {
+        let camera = Transform {
+            position: Vector2::new(40.0, -15.0),
+            scale: Vector2::new(2.0, 2.0),
+            ..Default::default()
+        };
+        let coordinate = SigilCoordinate::new(3, -2);
+        let screen_position =
+            Transform::from_sigil_coordinate(coordinate, &camera, SIGIL_DISTANCE).position;
+        assert_eq!(
+            SigilCoordinate::from_screen_position(screen_position, &camera, SIGIL_DISTANCE),
+            coordinate
+        );
+    }
+
+    #[test]
+    fn positions_snap_back_at_any_spacing() {
+        for spacing in [SIGIL_DISTANCE, 10.0, 75.0, 3.5] {
+            for (x, y) in [(0, 0), (3, -2), (-7, 11), (100, 100)] {
+                let coordinate = SigilCoordinate::new(x, y);
+                let position = coordinate.position(spacing);
+                assert_eq!(
+                    SigilCoordinate::from_position(position, spacing),
+                    coordinate
+                );
+                // anywhere closer to the coordinate than its neighbours snaps to it
+                let nudged = position + Vector2::new(0.4, -0.4) * spacing;
+                assert_eq!(SigilCoordinate::from_position(nudged, spacing), coordinate);
+            }
+        }
+    }
+
+    fn alpha_row() -> ActivePuzzle {
+        let mut puzzle = Puzzle::default();
+        for x in [0, 2, 4] {
+            puzzle.sigils.insert(
+                SigilCoordinate::new(x, 0),
+                Sigil {
+                    rune: Rune::Alpha,
+                    orb: Orb::Diamond,
+                },
+            );
+        }
+        ActivePuzzle::new(puzzle)
+    }
+
+    #[test]
+    fn redo_restores_undone_input() {
+        let mut active = alpha_row();
+        assert!(active.input(&SigilCoordinate::new(2, 0)));
+        assert!(active.input(&SigilCoordinate::new(4, 0)));
+        assert!(active.undo());
+        assert!(active.undo());
+        assert!(!active.undo());
+        assert!(active.redo());
+        assert_eq!(active.puzzle.cursor, SigilCoordinate::new(2, 0));
+        assert_eq!(active.puzzle.lines.len(), 1);
+        assert!(active.undo());
+        assert_eq!(active.puzzle.lines.len(), 0);
+    }
+
+    #[test]
+    fn removing_a_line_moves_the_cursor_back() {
+        let mut active = alpha_row();
+        active.input(&SigilCoordinate::new(2, 0));
+        assert!(!active.remove_line_at(SigilCoordinate::new(0, 5)));
+        // the middle of the line touches it too
+        assert!(active.remove_line_at(SigilCoordinate::new(1, 0)));
+        assert_eq!(active.puzzle.lines.len(), 0);
+        assert_eq!(active.puzzle.cursor, SigilCoordinate::new(0, 0));
+        assert!(!active.remove_line_at(SigilCoordinate::new(1, 0)));
+
+        assert!(active.undo());
+        assert_eq!(active.puzzle.lines.len(), 1);
+        assert_eq!(active.puzzle.cursor, SigilCoordinate::new(2, 0));
+    }
+
+    #[test]
+    fn history_forgets_oldest_past_max() {
+        let mut active = ActivePuzzle::with_max_history(Puzzle::default(), 3);
+        // the puzzle is only solved by the last input, when every alpha is touched
+        for x in 0..=5 {
+            active.puzzle.sigils.insert(
+                SigilCoordinate::new(x, 10),
+                Sigil {
+                    rune: Rune::Alpha,
+                    orb: Orb::Circle,
+                },
+            );
+        }
+        active.puzzle.cursor = SigilCoordinate::new(0, 10);
+        for x in 1..=5 {
+            assert!(active.input(&SigilCoordinate::new(x, 10)));
+            assert!(active.history.len() <= 3);
+        }
+        assert_eq!(active.history.len(), 3);
+        assert!(active.undo());
+        assert_eq!(active.puzzle.cursor, SigilCoordinate::new(4, 10));
+        assert!(active.undo());
+        assert!(active.undo());
+        assert_eq!(active.puzzle.cursor, SigilCoordinate::new(2, 10));
+        assert!(!active.undo());
+    }
+
+    #[test]
+    fn connecting_every_alpha_solves() {
+        let mut active = alpha_row();
+        assert!(!active.solved());
+        assert!(active.input(&SigilCoordinate::new(2, 0)));
+        assert!(!active.solved());
+        assert!(active.input(&SigilCoordinate::new(4, 0)));
+        assert!(active.solved());
+        // solved puzzles take no more input
+        assert!(!active.input(&SigilCoordinate::new(0, 0)));
+        assert!(!Puzzle::default().solved());
+    }
+
+    #[test]
+    fn input_after_undo_discards_redo() {
+        let mut active = alpha_row();
+        assert!(active.input(&SigilCoordinate::new(2, 0)));
+        assert!(active.undo());
+        assert!(active.input(&SigilCoordinate::new(4, 0)));
+        assert!(!active.redo());
+        assert_eq!(active.puzzle.cursor, SigilCoordinate::new(4, 0));
+
+        active.undo();
+        active.load_puzzle(Puzzle::default());
+        assert!(!active.redo());
+        assert!(!active.undo());
+    }
+
+    #[test]
+    fn collinear_lines_merge_unless_a_sigil_splits_them() {
+        let line = |start: (i32, i32), end: (i32, i32)| Line {
+            start: SigilCoordinate::new(start.0, start.1),
+            end: SigilCoordinate::new(end.0, end.1),
+            orb: Some(Orb::Octogon),
+        };
+        let mut puzzle = Puzzle {
+            lines: vec![
+                line((2, 2), (4, 4)),
+                line((0, 0), (2, 2)),
+                line((4, 4), (4, 6)),
+            ],
+            ..Default::default()
+        };
+        puzzle.simplify_lines();
+        assert_eq!(puzzle.lines.len(), 2);
+        assert_eq!(puzzle.lines[0].start, SigilCoordinate::new(0, 0));
+        assert_eq!(puzzle.lines[0].end, SigilCoordinate::new(4, 4));
+
+        let mut puzzle = Puzzle {
+            lines: vec![line((0, 0), (2, 0)), line((2, 0), (4, 0))],
+            ..Default::default()
+        };
+        puzzle.sigils.insert(
+            SigilCoordinate::new(2, 0),
+            Sigil {
+                rune: Rune::Alpha,
+                orb: Orb::Circle,
+            },
+        );
+        puzzle.simplify_lines();
+        assert_eq!(puzzle.lines.len(), 2);
+    }
+
+    fn square_loop() -> Vec<Line> {
+        let corners = [(0, 0), (4, 0), (4, 4), (0, 4), (0, 0)];
+        corners
+            .windows(2)
+            .map(|ends| Line {
+                start: SigilCoordinate::new(ends[0].0, ends[0].1),
+                end: SigilCoordinate::new(ends[1].0, ends[1].1),
+                orb: None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn theta_is_active_inside_a_loop() {
+        let lines = square_loop();
+        let sigils = HashMap::<SigilCoordinate, Sigil>::new();
+        let active = |x, y| Rune::Theta.active(SigilCoordinate::new(x, y), &lines, &sigils);
+        assert!(active(2, 2));
+        assert!(active(1, 3));
+        assert!(!active(6, 2));
+        // the ray runs along the bottom line and through both its corners
+        assert!(!active(-1, 0));
+        assert!(!active(-1, 2));
+        // on the loop isn't inside it
+        assert!(!active(2, 0));
+        assert!(!active(4, 4));
+        // lines that don't close a loop don't enclose anything
+        assert!(!Rune::Theta.active(SigilCoordinate::new(2, 2), &lines[..3], &sigils));
+    }
+
+    #[test]
+    fn changing_back_undoes_change() {
+        for orb in [Orb::Circle, Orb::Diamond, Orb::Octogon] {
+            assert_eq!(orb.change().change_back(), orb);
+            assert_eq!(orb.change_back().change(), orb);
+        }
+        let runes = [
+            Rune::Alpha,
+            Rune::Sigma,
+            Rune::Delta,
+            Rune::Phi,
+            Rune::Theta,
+        ];
+        for rune in runes {
+            assert_eq!(rune.change().change_back().to_string(), rune.to_string());
+            assert_eq!(rune.change_back().change().to_string(), rune.to_string());
+        }
+    }
+
+    #[test]
+    fn line_tinted_by_orb() {
+        let mut puzzle = Puzzle::default();
+        for coordinate in [SigilCoordinate::new(0, 0), SigilCoordinate::new(2, 0)] {
+            puzzle.sigils.insert(
+                coordinate,
+                Sigil {
+                    rune: Rune::Alpha,
+                    orb: Orb::Diamond,
+                },
+            );
+        }
+        assert!(puzzle.input(&SigilCoordinate::new(2, 0)));
+        let sprite = puzzle.lines[0].sprite(
+            &LineFlow::default(),
+            &LineStyle::default(),
+            &Transform::default(),
+            SIGIL_DISTANCE,
+        );
+        assert_eq!(sprite.color, Orb::Diamond.line_color());
+
+        let serialized = excali_io::toml::to_string(&SerialablePuzzle::from(puzzle)).unwrap();
+        let puzzle =
+            Puzzle::try_from(excali_io::toml::from_str::<SerialablePuzzle>(&serialized).unwrap())
+                .unwrap();
+        assert_eq!(puzzle.lines[0].orb, Some(Orb::Diamond));
+    }
+
+    #[test]
+    fn legacy_line_has_no_orb() {
+        let puzzle = excali_io::toml::from_str::<SerialablePuzzle>(
+            "cursor = [0, 0]\n[sigils]\n[[lines]]\nstart = [0, 0]\nend = [1, 1]\n",
+        )
+        .unwrap();
+        let puzzle = Puzzle::try_from(puzzle).unwrap();
+        assert_eq!(puzzle.lines[0].orb, None);
+        assert_eq!(puzzle.spacing, SIGIL_DISTANCE);
+        assert_eq!(
+            puzzle.lines[0]
+                .sprite(
+                    &LineFlow::default(),
+                    &LineStyle::default(),
+                    &Transform::default(),
+                    SIGIL_DISTANCE,
+                )
+                .color,
+            Color::new(1.0, 1.0, 1.0, 1.0)
+        );
+    }
+
+    #[test]
+    fn line_flow_wraps_with_delta() {
+        let mut stepped = LineFlow {
+            speed: 0.75,
+            ..Default::default()
+        };
+        let mut once = stepped;
+        for _ in 0..10 {
+            stepped.update(0.1);
+        }
+        once.update(1.0);
+        assert!((stepped.phase() - 0.75).abs() < 1e-5);
+        assert!((once.phase() - stepped.phase()).abs() < 1e-5);
+
+        once.update(1.0);
+        assert!((once.phase() - 0.5).abs() < 1e-5);
+        once.update(1000.0);
+        assert!((0.0..1.0).contains(&once.phase()));
+
+        once.enabled = false;
+        let phase = once.phase();
+        once.update(0.3);
+        assert_eq!(once.phase(), phase);
+    }
+
+    #[test]
+    fn line_width_scales_tiling() {
+        let line = Line {
+            start: SigilCoordinate::new(0, 0),
+            end: SigilCoordinate::new(3, 0),
+            orb: None,
+        };
+        let flow = LineFlow::default();
+        let style = LineStyle::default();
+        let wide = LineStyle {
+            width: style.width * 2.0,
+            ..style
+        };
+        let sprite = line.sprite(&flow, &style, &Transform::default(), SIGIL_DISTANCE);
+        let wide_sprite = line.sprite(&flow, &wide, &Transform::default(), SIGIL_DISTANCE);
+        assert!(
+            (sprite.texture_coordinate.width - wide_sprite.texture_coordinate.width * 2.0).abs()
+                < 1e-5
+        );
+        // the line is just as long but twice as thick
+        assert_eq!(wide_sprite.aabb(19, 19).1.x, sprite.aabb(19, 19).1.x);
+        assert_eq!(wide_sprite.transform.scale, sprite.transform.scale * 2.0);
+    }
+
+    #[test]
+    fn intersections_are_exact() {
+        let line = |start: (i32, i32), end: (i32, i32)| Line {
+            start: Vector2::new(start.0, start.1),
+            end: Vector2::new(end.0, end.1),
+            orb: None,
+        };
+        let cases = [
+            // crossing
+            (line((0, 0), (2, 2)), line((0, 2), (2, 0)), true),
+            (line((0, 0), (4, 0)), line((2, -2), (2, 2)), true),
+            (line((0, 0), (3, 1)), line((1, 2), (2, -1)), true),
+            // touching at an endpoint
+            (line((0, 0), (2, 0)), line((2, 0), (2, 2)), false),
+            (line((0, 0), (2, 2)), line((0, 0), (-2, 1)), false),
+            // ending on the other line
+            (line((0, 0), (4, 0)), line((2, 0), (2, 2)), false),
+            // collinear and overlapping
+            (line((0, 0), (4, 0)), line((2, 0), (6, 0)), true),
+            (line((0, 0), (6, 0)), line((2, 0), (4, 0)), true),
+            (line((0, 0), (0, 4)), line((0, 2), (0, 6)), true),
+            (line((0, 0), (2, 2)), line((1, 1), (3, 3)), true),
+            (line((0, 0), (2, 0)), line((2, 0), (0, 0)), true),
+            // collinear and extending
+            (line((0, 0), (2, 0)), line((2, 0), (4, 0)), false),
+            (line((0, 0), (1, 1)), line((1, 1), (3, 3)), false),
+            // collinear and disjoint
+            (line((0, 0), (1, 0)), line((3, 0), (5, 0)), false),
+            (line((0, 0), (0, 1)), line((0, 3), (0, 5)), false),
+            (line((0, 0), (1, 1)), line((3, 3), (4, 4)), false),
+            // parallel or apart
+            (line((0, 0), (4, 0)), line((0, 1), (4, 1)), false),
+            (line((0, 0), (1, 1)), line((3, 0), (4, -2)), false),
+        ];
+        for (a, b, expected) in cases {
+            assert_eq!(a.intersects(&b), expected, "{a:?} and {b:?}");
+            assert_eq!(b.intersects(&a), expected, "{b:?} and {a:?}");
+        }
     }
 }
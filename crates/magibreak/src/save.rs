@@ -0,0 +1,123 @@
+use excali_io::tokio::sync::oneshot;
+use excali_io::{
+    load_from_toml, resolve_save_path, safe_join, save_to_toml, IoError, SafeJoinError,
+};
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+
+use crate::map::{Grid, MapCoordinate, ZoneState};
+use crate::APP_NAME;
+
+const SAVES_PATH: &str = "saves";
+pub const DEFAULT_SLOT: &str = "default";
+
+/// The player's progress through the map
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct SaveGame {
+    pub solved_zones: Vec<MapCoordinate>,
+    pub current_zone: MapCoordinate,
+}
+
+fn slot_path(slot: &str) -> Result<PathBuf, SafeJoinError> {
+    safe_join(
+        resolve_save_path(APP_NAME, SAVES_PATH),
+        &format!("{slot}.toml"),
+    )
+}
+
+impl SaveGame {
+    /// a fresh save at the start of the map
+    pub fn new(grid: &Grid) -> Self {
+        Self {
+            solved_zones: Vec::new(),
+            current_zone: grid.starting_zone,
+        }
+    }
+
+    /// records the grid's solved zones
+    pub fn from_grid(grid: &Grid, current_zone: MapCoordinate) -> Self {
+        let mut solved_zones: Vec<MapCoordinate> = grid
+            .zones
+            .iter()
+            .filter(|(_, zone)| zone.state == ZoneState::Solved)
+            .map(|(coordinate, _)| *coordinate)
+            .collect();
+        solved_zones.sort_by_key(|coordinate| (coordinate.x, coordinate.y));
+        Self {
+            solved_zones,
+            current_zone,
+        }
+    }
+
+    /// restores the solved zones onto the grid
+    pub fn apply(&self, grid: &mut Grid) {
+        for coordinate in self.solved_zones.iter() {
+            grid.complete_zone(*coordinate);
+        }
+    }
+
+    pub async fn load(slot: &str) -> Result<Self, String> {
+        load_from_toml(slot_path(slot).map_err(|err| err.to_string())?)
+            .await
+            .map_err(|err| err.to_string())
+    }
+
+    pub fn save(&self, slot: &str) -> Result<oneshot::Receiver<Result<(), IoError>>, String> {
+        Ok(save_to_toml(
+            self,
+            slot_path(slot).map_err(|err| err.to_string())?,
+        ))
+    }
+
+    /// returns the names of the saved slots
+    pub fn slots() -> Vec<String> {
+        let Ok(entries) = std::fs::read_dir(resolve_save_path(APP_NAME, SAVES_PATH)) else {
+            return Vec::new();
+        };
+        let mut slots: Vec<String> = entries
+            .filter_map(|entry| {
+                let path = entry.ok()?.path();
+                if path.extension()? != "toml" {
+                    return None;
+                }
+                Some(path.file_stem()?.to_str()?.to_string())
+            })
+            .collect();
+        slots.sort();
+        slots
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::map::SerialableGrid;
+
+    fn load_grid() -> Grid {
+        let contents = std::fs::read_to_string("./assets/map.toml").unwrap();
+        Grid::try_from(excali_io::toml::from_str::<SerialableGrid>(&contents).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn save_restores_zone_states() {
+        let mut grid = load_grid();
+        let coordinate = grid.starting_zone;
+        grid.complete_zone(coordinate);
+        let saved = excali_io::toml::to_string(&SaveGame::from_grid(&grid, coordinate)).unwrap();
+
+        let mut grid = load_grid();
+        let save_game = excali_io::toml::from_str::<SaveGame>(&saved).unwrap();
+        save_game.apply(&mut grid);
+        assert_eq!(save_game.current_zone, coordinate);
+        assert_eq!(grid.zones[&coordinate].state, ZoneState::Solved);
+        for next in grid.zones[&coordinate].next_zones.iter() {
+            assert_eq!(grid.zones[next].state, ZoneState::Unlocked);
+        }
+    }
+
+    #[test]
+    fn slot_names_are_checked() {
+        assert!(slot_path("../escape").is_err());
+        assert!(slot_path(DEFAULT_SLOT).is_ok());
+    }
+}
@@ -0,0 +1,80 @@
+use excali_3d::{Camera, FPSEye};
+use excali_io::tokio::sync::oneshot;
+use excali_io::{load_from_toml, resolve_save_path, save_to_toml, IoError};
+use excali_ui::egui_winit::egui;
+use nalgebra::Vector3;
+use serde::{Deserialize, Serialize};
+
+use crate::APP_NAME;
+
+const CAMERA_SETTINGS_PATH: &str = "camera.toml";
+
+/// How the map's camera moves, saved between sessions
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub struct CameraSettings {
+    /// distance moved each fixed step, the world takes 60 steps a second
+    pub speed: f32,
+    /// radians turned per pixel the mouse moves
+    pub sensitivity: f32,
+    pub invert_y: bool,
+}
+
+impl Default for CameraSettings {
+    fn default() -> Self {
+        Self {
+            speed: 0.5,
+            sensitivity: 0.02,
+            invert_y: false,
+        }
+    }
+}
+
+impl CameraSettings {
+    pub async fn load() -> Result<Self, IoError> {
+        load_from_toml(resolve_save_path(APP_NAME, CAMERA_SETTINGS_PATH)).await
+    }
+
+    pub fn save(&self) -> oneshot::Receiver<Result<(), IoError>> {
+        save_to_toml(self, resolve_save_path(APP_NAME, CAMERA_SETTINGS_PATH))
+    }
+
+    /// the movement for each fixed step of input along the camera's axes
+    pub fn translation(&self, camera: &Camera<FPSEye>, direction: &Vector3<f32>) -> Vector3<f32> {
+        camera.fly_direction(direction) * self.speed
+    }
+
+    /// returns if a change was finished and should be saved
+    pub fn ui(&mut self, ui: &mut egui::Ui) -> bool {
+        let speed = ui.add(egui::Slider::new(&mut self.speed, 0.1..=2.0).text("Speed"));
+        let sensitivity = ui.add(
+            egui::Slider::new(&mut self.sensitivity, 0.002..=0.1)
+                .logarithmic(true)
+                .text("Sensitivity"),
+        );
+        let invert_y = ui.checkbox(&mut self.invert_y, "Invert Y");
+        // sliders are saved once they're let go of
+        [speed, sensitivity]
+            .iter()
+            .any(|slider| slider.drag_released() || (slider.changed() && !slider.dragged()))
+            || invert_y.changed()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn doubled_speed_doubles_translation() {
+        let camera = Camera::<FPSEye>::default();
+        let direction = Vector3::new(1.0, 0.0, 1.0);
+        let settings = CameraSettings::default();
+        let doubled = CameraSettings {
+            speed: settings.speed * 2.0,
+            ..settings
+        };
+        let translation = settings.translation(&camera, &direction);
+        assert!(translation.magnitude() > 0.0);
+        assert!((doubled.translation(&camera, &direction) - translation * 2.0).magnitude() < 1e-6);
+    }
+}
@@ -0,0 +1,203 @@
+use excali_3d::{marching_cell, marching_squares, Model, Vertex, CELL_INDICES, CELL_VERTICES};
+use std::ops::Range;
+
+use crate::map::{HeightMap, CHUNK_SIZE};
+
+const TERRAIN_COLOR: [f32; 3] = [0.3, 0.6, 0.25];
+/// marching cells along each side of the chunk
+const CELLS: usize = CHUNK_SIZE - 1;
+
+/// The terrain's geometry laid out in fixed slots per marching cell
+pub struct TerrainMesh {
+    pub vertices: Vec<Vertex>,
+    pub indices: Vec<u16>,
+}
+
+impl TerrainMesh {
+    pub fn from_marching_squares(height_map: &HeightMap) -> Self {
+        let (vertices, indices) = marching_squares(
+            &|x, z| height_map[(x, z)],
+            CHUNK_SIZE,
+            CHUNK_SIZE,
+            TERRAIN_COLOR,
+        );
+        Self { vertices, indices }
+    }
+
+    /// regenerates the marching cells touching the heights from min to max inclusive,
+    /// returns the ranges of cells which were rewritten
+    pub fn patch(
+        &mut self,
+        height_map: &HeightMap,
+        min: (usize, usize),
+        max: (usize, usize),
+    ) -> Vec<Range<usize>> {
+        let z_cells = min.1.saturating_sub(1)..max.1.min(CELLS - 1) + 1;
+        let mut rewritten = Vec::new();
+        for x in min.0.saturating_sub(1)..max.0.min(CELLS - 1) + 1 {
+            for z in z_cells.clone() {
+                let cell = x * CELLS + z;
+                let (vertices, indices) = marching_cell(
+                    [
+                        height_map[(x, z)],
+                        height_map[(x + 1, z)],
+                        height_map[(x + 1, z + 1)],
+                        height_map[(x, z + 1)],
+                    ],
+                    x,
+                    z,
+                    TERRAIN_COLOR,
+                );
+                let first_vertex = (cell * CELL_VERTICES) as u16;
+                self.vertices[cell * CELL_VERTICES..(cell + 1) * CELL_VERTICES]
+                    .copy_from_slice(&vertices);
+                for (slot, index) in self.indices[cell * CELL_INDICES..(cell + 1) * CELL_INDICES]
+                    .iter_mut()
+                    .zip(indices)
+                {
+                    *slot = first_vertex + index;
+                }
+            }
+            rewritten.push(x * CELLS + z_cells.start..x * CELLS + z_cells.end);
+        }
+        rewritten
+    }
+}
+
+/// The terrain's model, only the cells around changed heights are regenerated
+#[derive(Default)]
+pub struct Terrain {
+    mesh: Option<(TerrainMesh, Model)>,
+    height_map: Option<HeightMap>,
+}
+
+/// the smallest rectangle around the heights which differ
+fn changed_heights(
+    previous: &HeightMap,
+    height_map: &HeightMap,
+) -> Option<((usize, usize), (usize, usize))> {
+    let mut changed: Option<((usize, usize), (usize, usize))> = None;
+    for x in 0..CHUNK_SIZE {
+        for z in 0..CHUNK_SIZE {
+            if previous[(x, z)] == height_map[(x, z)] {
+                continue;
+            }
+            changed = Some(match changed {
+                Some((min, max)) => ((min.0.min(x), min.1.min(z)), (max.0.max(x), max.1.max(z))),
+                None => ((x, z), (x, z)),
+            });
+        }
+    }
+    changed
+}
+
+impl Terrain {
+    /// remembers the height map, returns if it changed since the last call
+    fn needs_rebuild(&mut self, height_map: &HeightMap) -> bool {
+        if self.height_map.as_ref() == Some(height_map) {
+            return false;
+        }
+        self.height_map = Some(*height_map);
+        true
+    }
+
+    /// patches the model where the height map changed, returns if it did
+    pub fn update(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        height_map: &HeightMap,
+    ) -> bool {
+        let previous = self.height_map;
+        if !self.needs_rebuild(height_map) {
+            return false;
+        }
+        let (Some(previous), Some((mesh, model))) = (previous, self.mesh.as_mut()) else {
+            let mesh = TerrainMesh::from_marching_squares(height_map);
+            let model = Model::new(
+                device,
+                mesh.vertices.clone(),
+                mesh.indices.clone(),
+                "Terrain".to_string(),
+            );
+            self.mesh = Some((mesh, model));
+            return true;
+        };
+        if let Some((min, max)) = changed_heights(&previous, height_map) {
+            for cells in mesh.patch(height_map, min, max) {
+                let vertices = cells.start * CELL_VERTICES..cells.end * CELL_VERTICES;
+                let indices = cells.start * CELL_INDICES..cells.end * CELL_INDICES;
+                model.write(
+                    queue,
+                    vertices.start,
+                    &mesh.vertices[vertices],
+                    indices.start,
+                    &mesh.indices[indices],
+                );
+            }
+        }
+        true
+    }
+
+    pub fn model(&self) -> Option<&Model> {
+        self.mesh.as_ref().map(|(_, model)| model)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn same_edit_regenerates_once() {
+        let mut terrain = Terrain::default();
+        let mut height_map = HeightMap::from_element(2);
+        assert!(terrain.needs_rebuild(&height_map));
+
+        let mut regenerations = 0;
+        for _ in 0..2 {
+            height_map[(5, 5)] = 3;
+            if terrain.needs_rebuild(&height_map) {
+                regenerations += 1;
+            }
+        }
+        assert_eq!(regenerations, 1);
+    }
+
+    #[test]
+    fn edit_patches_surrounding_cells() {
+        let mut height_map = HeightMap::from_element(2);
+        let mut mesh = TerrainMesh::from_marching_squares(&height_map);
+        let before_vertices: Vec<u8> = bytemuck::cast_slice(&mesh.vertices).to_vec();
+        let before_indices: Vec<u8> = bytemuck::cast_slice(&mesh.indices).to_vec();
+
+        let previous = height_map;
+        height_map[(5, 5)] = 3;
+        let (min, max) = changed_heights(&previous, &height_map).unwrap();
+        let rewritten = mesh.patch(&height_map, min, max);
+        assert_eq!(
+            rewritten,
+            vec![4 * CELLS + 4..4 * CELLS + 6, 5 * CELLS + 4..5 * CELLS + 6]
+        );
+
+        let patched: Vec<usize> = rewritten.into_iter().flatten().collect();
+        let vertex_bytes = std::mem::size_of::<Vertex>() * CELL_VERTICES;
+        let index_bytes = std::mem::size_of::<u16>() * CELL_INDICES;
+        let after_vertices: &[u8] = bytemuck::cast_slice(&mesh.vertices);
+        let after_indices: &[u8] = bytemuck::cast_slice(&mesh.indices);
+        for cell in 0..CELLS * CELLS {
+            let vertices = cell * vertex_bytes..(cell + 1) * vertex_bytes;
+            let indices = cell * index_bytes..(cell + 1) * index_bytes;
+            let unchanged = before_vertices[vertices.clone()] == after_vertices[vertices]
+                && before_indices[indices.clone()] == after_indices[indices];
+            assert_eq!(unchanged, !patched.contains(&cell), "cell {cell}");
+        }
+
+        let rebuilt = TerrainMesh::from_marching_squares(&height_map);
+        assert_eq!(
+            bytemuck::cast_slice::<Vertex, u8>(&rebuilt.vertices),
+            after_vertices
+        );
+        assert_eq!(rebuilt.indices, mesh.indices);
+    }
+}
@@ -1,7 +1,17 @@
+use excali_io::load_files;
 use excali_render::Renderer;
 use excali_sprite::{SpriteRenderer, SpriteTexture};
+use log::error;
 
-use crate::load_sprite_texture;
+use crate::create_sprite_texture;
+
+const TEXTURE_MANIFEST: [(&str, &str); 5] = [
+    ("orbs", "assets/orbs.png"),
+    ("border", "assets/border.png"),
+    ("sigils", "assets/sigils.png"),
+    ("cursor", "assets/cursor.png"),
+    ("line", "assets/line.png"),
+];
 
 pub struct Textures {
     pub orbs: SpriteTexture,
@@ -9,6 +19,8 @@ pub struct Textures {
     pub sigils: SpriteTexture,
     pub cursor: SpriteTexture,
     pub line: SpriteTexture,
+    /// a single white pixel for solid colored sprites
+    pub white: SpriteTexture,
 }
 
 impl Textures {
@@ -18,16 +30,35 @@ impl Textures {
         sampler: &wgpu::Sampler,
         line_sampler: &wgpu::Sampler,
     ) -> Self {
+        let (files, failures) = load_files(&TEXTURE_MANIFEST).await;
+        for (name, err) in failures.iter() {
+            error!("Failed to load {name} texture: {err}");
+        }
+
+        let (mut textures, failures) = renderer.load_textures(&files);
+        for err in failures.iter() {
+            error!("{err}");
+        }
+        let mut sprite_texture = |name: &str, sampler: &wgpu::Sampler| {
+            // a magenta pixel stands out in place of a missing texture
+            let texture = textures.remove(name).unwrap_or_else(|| {
+                renderer.create_texture(&[255, 0, 255, 255], (1, 1), name.to_string())
+            });
+            create_sprite_texture(texture, sprite_renderer, renderer, sampler)
+        };
+
         Self {
-            orbs: load_sprite_texture("assets/orbs.png", sprite_renderer, renderer, sampler).await,
-            border: load_sprite_texture("assets/border.png", sprite_renderer, renderer, sampler)
-                .await,
-            sigils: load_sprite_texture("assets/sigils.png", sprite_renderer, renderer, sampler)
-                .await,
-            cursor: load_sprite_texture("assets/cursor.png", sprite_renderer, renderer, sampler)
-                .await,
-            line: load_sprite_texture("assets/line.png", sprite_renderer, renderer, line_sampler)
-                .await,
+            orbs: sprite_texture("orbs", sampler),
+            border: sprite_texture("border", sampler),
+            sigils: sprite_texture("sigils", sampler),
+            cursor: sprite_texture("cursor", sampler),
+            line: sprite_texture("line", line_sampler),
+            white: create_sprite_texture(
+                renderer.create_texture(&[255; 4], (1, 1), "white".to_string()),
+                sprite_renderer,
+                renderer,
+                sampler,
+            ),
         }
     }
 }
@@ -1,17 +1,30 @@
-use excali_3d::{Camera, FPSEye, LineRenderer, Renderer3D, Vertex};
+use excali_3d::{
+    Axis, Camera, CameraEye, DepthConfig, FPSEye, LineRenderer, Model, ModelBatch, Renderer3D,
+    TranslateGizmo, Vertex,
+};
 use excali_input::Input;
-use excali_render::Renderer;
+use excali_render::{Renderer, Viewport};
 use log::warn;
-use nalgebra::{Vector2, Vector3};
+use nalgebra::{Matrix4, Vector2, Vector3};
 use rapier3d::control::KinematicCharacterController;
 use rapier3d::prelude::{
     BroadPhase, CCDSolver, ColliderBuilder, ColliderHandle, ColliderSet, DebugRenderBackend,
     DebugRenderObject, DebugRenderPipeline, ImpulseJointSet, IntegrationParameters, IslandManager,
-    MultibodyJointSet, NarrowPhase, PhysicsPipeline, Point, QueryFilter, QueryPipeline, Real,
+    MultibodyJointSet, NarrowPhase, PhysicsPipeline, Point, QueryFilter, QueryPipeline, Ray, Real,
     RigidBodyBuilder, RigidBodyHandle, RigidBodySet,
 };
+use std::collections::HashMap;
 
 use crate::input;
+use crate::map::{Grid, MapCoordinate, ZoneState};
+use crate::settings::CameraSettings;
+use crate::terrain::Terrain;
+
+/// how many seconds the physics moves on each step, whatever the framerate
+const FIXED_DT: f32 = 1.0 / 60.0;
+/// the most steps a frame can run, time past them is dropped so a long stall doesn't cause
+/// even longer frames catching up
+const MAX_STEPS: usize = 5;
 
 struct Character {
     controller: KinematicCharacterController,
@@ -36,20 +49,15 @@ impl Character {
     }
 
     fn position(&self, engine: &PhysicsEngine) -> Option<Vector3<f32>> {
-        Some(
-            engine
-                .bodies
-                .get(self.rigid_body)?
-                .position()
-                .translation
-                .vector,
-        )
+        engine.body_position(self.rigid_body)
     }
 
     fn update(&mut self, translation: Vector3<f32>, engine: &mut PhysicsEngine, delta: f32) {
         if let Some(collider) = engine.colliders.get(self.collider) {
             let mut collisions = Vec::new();
-            let filter = QueryFilter::default().exclude_rigid_body(self.rigid_body);
+            let filter = QueryFilter::default()
+                .exclude_sensors()
+                .exclude_rigid_body(self.rigid_body);
             let movement = self.controller.move_shape(
                 delta,
                 &engine.bodies,
@@ -99,12 +107,19 @@ struct PhysicsEngine {
     multibody_joints: MultibodyJointSet,
     ccd_solver: CCDSolver,
     gravity: Vector3<f32>,
+    gravity_enabled: bool,
+    zones: HashMap<ColliderHandle, MapCoordinate>,
+    /// frame time that hasn't been stepped yet
+    accumulator: f32,
 }
 
 impl Default for PhysicsEngine {
     fn default() -> Self {
         Self {
             gravity: Vector3::new(0.0, -1.0, 0.0),
+            gravity_enabled: true,
+            zones: Default::default(),
+            accumulator: 0.0,
             physics_pipeline: Default::default(),
             query_pipeline: Default::default(),
             colliders: Default::default(),
@@ -127,6 +142,59 @@ pub struct World {
     renderer: Renderer3D,
     line_renderer: LineRenderer,
     camera: Camera<FPSEye>,
+    terrain: Terrain,
+    pins: PinModels,
+    pub camera_settings: CameraSettings,
+    /// shown while editing, dragging its handles moves the selected zone
+    pub gizmo: Option<TranslateGizmo>,
+    /// the grabbed axis and how far it has been dragged
+    drag: Option<(Axis, f32)>,
+}
+
+/// Marks where the zones are, the hovered and selected zones get their own models
+struct PinModels {
+    pin: Model,
+    hovered: Model,
+    selected: Model,
+}
+
+impl PinModels {
+    fn new(device: &wgpu::Device) -> Self {
+        let model = |name: &str, size: Vector3<f32>, color: [f32; 3]| {
+            Model::cube(device, name.to_string(), size, color)
+        };
+        Self {
+            pin: model("Pin", Vector3::new(0.3, 2.0, 0.3), [0.6, 0.6, 0.7]),
+            hovered: model("Hovered Pin", Vector3::new(0.5, 2.2, 0.5), [1.0, 0.9, 0.3]),
+            selected: model("Selected Pin", Vector3::new(0.4, 2.1, 0.4), [0.9, 0.3, 0.3]),
+        }
+    }
+}
+
+/// the matrices for each pin model
+#[derive(Default, Debug, PartialEq)]
+struct PinInstances {
+    pins: Vec<Matrix4<f32>>,
+    hovered: Vec<Matrix4<f32>>,
+    selected: Vec<Matrix4<f32>>,
+}
+
+impl PinInstances {
+    /// the selected zone is drawn as selected even while it's hovered
+    fn new(grid: &Grid, hovered: Option<MapCoordinate>, selected: MapCoordinate) -> Self {
+        let mut instances = Self::default();
+        for (coordinate, _) in grid.sorted_zones() {
+            let matrix = Matrix4::new_translation(&grid.world_position(*coordinate));
+            if *coordinate == selected {
+                instances.selected.push(matrix);
+            } else if Some(*coordinate) == hovered {
+                instances.hovered.push(matrix);
+            } else {
+                instances.pins.push(matrix);
+            }
+        }
+        instances
+    }
 }
 
 #[derive(Default)]
@@ -149,25 +217,79 @@ impl DebugRenderBackend for DebugPhysicsRenderer {
 }
 
 impl PhysicsEngine {
-    fn draw(
-        &self,
-        view: &wgpu::TextureView,
-        line_renderer: &mut LineRenderer,
-        renderer_3d: &Renderer3D,
-        renderer: &Renderer,
-    ) -> wgpu::CommandBuffer {
+    fn debug_vertices(&self) -> Vec<Vertex> {
         let mut debug_renderer = DebugPhysicsRenderer::default();
         DebugRenderPipeline::default().render_colliders(
             &mut debug_renderer,
             &self.bodies,
             &self.colliders,
         );
-        line_renderer.draw(debug_renderer.vertices, renderer, renderer_3d, view)
+        debug_renderer.vertices
+    }
+
+    fn insert_zones(&mut self, grid: &Grid) {
+        for (coordinate, collider) in grid.zone_colliders() {
+            let handle = self.colliders.insert(collider);
+            self.zones.insert(handle, coordinate);
+        }
+    }
+
+    /// replaces the zone colliders with the grid's
+    fn set_zones(&mut self, grid: &Grid) {
+        for (handle, _) in self.zones.drain() {
+            self.colliders
+                .remove(handle, &mut self.islands, &mut self.bodies, false);
+        }
+        self.insert_zones(grid);
+    }
+
+    /// returns the zone the ray hits first, fixed colliders like the ground block it
+    fn zone_under_ray(&self, ray: &Ray, max_distance: f32) -> Option<MapCoordinate> {
+        let (handle, _) = self.query_pipeline.cast_ray(
+            &self.bodies,
+            &self.colliders,
+            ray,
+            max_distance,
+            true,
+            QueryFilter::only_fixed(),
+        )?;
+        self.zones.get(&handle).copied()
+    }
+
+    fn body_position(&self, handle: RigidBodyHandle) -> Option<Vector3<f32>> {
+        Some(self.bodies.get(handle)?.position().translation.vector)
+    }
+
+    /// steps the simulation n times by dt seconds each, without needing a renderer
+    fn step_n(&mut self, n: usize, dt: f32) {
+        self.integration_parameters.dt = dt;
+        for _ in 0..n {
+            self.step();
+        }
+    }
+
+    /// adds the frame's time and returns how many steps of [FIXED_DT] it's due, the remainder
+    /// carries over to the next frame
+    fn fixed_steps(&mut self, delta: f32) -> usize {
+        self.accumulator += delta;
+        // a little slack so float error doesn't leave a whole step for the next frame
+        let steps = ((self.accumulator + f32::EPSILON) / FIXED_DT).floor() as usize;
+        self.accumulator = (self.accumulator - steps as f32 * FIXED_DT).max(0.0);
+        steps.min(MAX_STEPS)
+    }
+
+    /// the gravity applied to bodies, zero while disabled
+    fn active_gravity(&self) -> Vector3<f32> {
+        if self.gravity_enabled {
+            self.gravity
+        } else {
+            Vector3::zeros()
+        }
     }
 
     fn step(&mut self) {
         self.physics_pipeline.step(
-            &self.gravity,
+            &self.active_gravity(),
             &self.integration_parameters,
             &mut self.islands,
             &mut self.broad_phase,
@@ -184,14 +306,28 @@ impl PhysicsEngine {
     }
 }
 
+fn selectable(grid: &Grid, coordinate: MapCoordinate) -> bool {
+    grid.zones
+        .get(&coordinate)
+        .is_some_and(|zone| zone.state != ZoneState::Locked)
+}
+
 impl World {
-    pub fn new(renderer: &Renderer) -> Self {
+    pub fn new(renderer: &Renderer, grid: &Grid) -> Self {
         let mut physics_engine = PhysicsEngine::default();
         physics_engine
             .colliders
             .insert(ColliderBuilder::cuboid(10.0, 1.0, 10.0).build());
+        physics_engine.insert_zones(grid);
 
-        let renderer_3d = Renderer3D::new(&renderer.config, &renderer.device, 10);
+        let renderer_3d = Renderer3D::new(
+            &renderer.config,
+            renderer.adapter(),
+            &renderer.device,
+            10,
+            DepthConfig::default(),
+            1,
+        );
         let camera = Camera {
             position: Vector3::new(0.0, 3.0, -10.0).into(),
             ..Default::default()
@@ -202,65 +338,336 @@ impl World {
             line_renderer: LineRenderer::new(renderer, &renderer_3d, 10),
             camera,
             renderer: renderer_3d,
+            terrain: Terrain::default(),
+            pins: PinModels::new(&renderer.device),
+            camera_settings: CameraSettings::default(),
+            gizmo: None,
+            drag: None,
         }
     }
 
-    pub fn update(
+    /// the map coordinate the camera is above
+    pub fn coordinate_under_camera(&self) -> MapCoordinate {
+        MapCoordinate::new(
+            self.camera.position.x.round() as i32,
+            self.camera.position.z.round() as i32,
+        )
+    }
+
+    /// draws the terrain and zone pins with the camera from the last update,
+    /// patching the terrain where the height map changed
+    /// draws into the viewport [World::update] fit the camera to
+    pub fn draw_map(
         &mut self,
         renderer: &Renderer,
         view: &wgpu::TextureView,
-        delta: f32,
-        input: &Input<input::Actions>,
+        viewport: Viewport,
+        grid: &Grid,
+        selected: MapCoordinate,
     ) -> wgpu::CommandBuffer {
-        const SPEED: f32 = 0.5;
-        const CAMERA_SENSITIVITY: f32 = 0.02;
+        self.terrain
+            .update(&renderer.device, &renderer.queue, &grid.height_map);
+        let instances = PinInstances::new(grid, self.hovered_zone(grid), selected);
+        let mut batches = vec![
+            ModelBatch {
+                model: &self.pins.pin,
+                matrices: instances.pins,
+            },
+            ModelBatch {
+                model: &self.pins.hovered,
+                matrices: instances.hovered,
+            },
+            ModelBatch {
+                model: &self.pins.selected,
+                matrices: instances.selected,
+            },
+        ];
+        if let Some(model) = self.terrain.model() {
+            batches.push(ModelBatch {
+                model,
+                matrices: vec![Matrix4::identity()],
+            });
+        }
+        let (commands, _) = self
+            .renderer
+            .draw(renderer, view, &batches, false, viewport);
+        commands
+    }
 
-        self.camera.aspect = renderer.aspect_ratio();
-        let mut direction = Vector3::<f32>::zeros();
+    /// returns the zone the camera is looking at if it can be selected
+    pub fn hovered_zone(&self, grid: &Grid) -> Option<MapCoordinate> {
+        self.zone_in_view()
+            .filter(|coordinate| selectable(grid, *coordinate))
+    }
 
-        if input.input_map.camera_forward.button.pressed() {
-            direction.z += 1.0;
-        }
-        if input.input_map.camera_backward.button.pressed() {
-            direction.z -= 1.0;
-        }
-        if input.input_map.camera_right.button.pressed() {
-            direction.x -= 1.0;
-        }
-        if input.input_map.camera_left.button.pressed() {
-            direction.x += 1.0;
-        }
-        if input.input_map.camera_down.button.pressed() {
-            direction.y -= 1.0;
+    /// returns the zone the camera is looking at
+    fn zone_in_view(&self) -> Option<MapCoordinate> {
+        const MAX_DISTANCE: f32 = 100.0;
+        let direction = self.camera.eye.target(&self.camera.position) - self.camera.position;
+        self.physics_engine
+            .zone_under_ray(&Ray::new(self.camera.position, direction), MAX_DISTANCE)
+    }
+
+    /// call after zones are moved so they can still be hovered
+    pub fn set_zones(&mut self, grid: &Grid) {
+        self.physics_engine.set_zones(grid);
+    }
+
+    /// starts dragging the gizmo handle the camera is looking at, returns if one was grabbed
+    pub fn grab_gizmo(&mut self) -> bool {
+        let direction = self.camera.eye.target(&self.camera.position) - self.camera.position;
+        self.drag = self
+            .gizmo
+            .and_then(|gizmo| gizmo.hit(&self.camera.position, &direction))
+            .map(|axis| (axis, 0.0));
+        self.drag.is_some()
+    }
+
+    pub fn dragging_gizmo(&self) -> bool {
+        self.drag.is_some()
+    }
+
+    /// stops dragging, returns the axis and how many whole cells it was dragged
+    pub fn release_gizmo(&mut self) -> Option<(Axis, i32)> {
+        let (axis, distance) = self.drag.take()?;
+        Some((axis, distance.round() as i32))
+    }
+
+    fn gizmo_vertices(&self) -> Vec<Vertex> {
+        let Some(mut gizmo) = self.gizmo else {
+            return Vec::new();
+        };
+        if let Some((axis, distance)) = self.drag {
+            gizmo.origin += axis.direction() * distance;
         }
-        if input.input_map.camera_up.button.pressed() {
-            direction.y += 1.0;
+        gizmo.vertices(self.drag.map(|(axis, _)| axis))
+    }
+
+    pub fn set_gravity(&mut self, gravity: Vector3<f32>) {
+        self.physics_engine.gravity = gravity;
+    }
+
+    /// disabling keeps the configured gravity for when it's enabled again
+    pub fn set_gravity_enabled(&mut self, enabled: bool) {
+        self.physics_engine.gravity_enabled = enabled;
+    }
+
+    /// moves the character and camera to the position
+    pub fn teleport(&mut self, position: Vector3<f32>) {
+        if let Some(rigid_body) = self
+            .physics_engine
+            .bodies
+            .get_mut(self.character.rigid_body)
+        {
+            rigid_body.set_translation(position, true);
         }
+        self.camera.position = position.into();
+    }
 
-        if let Some(mouse_delta) = input.mouse_delta {
-            if input.mouse_locked() {
+    /// fits the camera to the viewport the map is drawn into, the character stands still
+    /// without input
+    pub fn update(
+        &mut self,
+        renderer: &Renderer,
+        view: &wgpu::TextureView,
+        viewport: Viewport,
+        delta: f32,
+        input: Option<&Input<input::Actions>>,
+    ) -> wgpu::CommandBuffer {
+        self.camera.aspect = viewport.aspect_ratio();
+        let direction = input.map_or(Vector3::zeros(), |input| {
+            let movement = input.input_map.camera_move.value();
+            Vector3::new(movement.x, input.input_map.camera_lift.value(), movement.y)
+        });
+
+        if let Some((input, mouse_delta)) =
+            input.and_then(|input| Some((input, input.mouse_delta?)))
+        {
+            if let (Some((axis, distance)), Some(gizmo)) = (self.drag.as_mut(), self.gizmo) {
+                // clip space runs from -1.0 to 1.0 with y up
+                let drag = Vector2::new(
+                    mouse_delta.0.x as f32 * 2.0 / viewport.width,
+                    -mouse_delta.0.y as f32 * 2.0 / viewport.height,
+                );
+                *distance += gizmo.drag_distance(&self.camera, *axis, drag);
+            } else if input.mouse_locked() {
+                self.camera.eye.invert_y = self.camera_settings.invert_y;
                 self.camera.rotate(
                     &Vector2::new(mouse_delta.0.x as f32, mouse_delta.0.y as f32),
-                    CAMERA_SENSITIVITY,
+                    self.camera_settings.sensitivity,
                 );
             }
         }
 
-        self.character.update(
-            self.physics_engine.gravity
-                + (self.camera.point_to_world_space(&direction) - self.camera.position.coords)
-                    * SPEED,
-            &mut self.physics_engine,
-            delta,
-        );
-        self.physics_engine.step();
+        let translation = self.camera_settings.translation(&self.camera, &direction);
+        for _ in 0..self.physics_engine.fixed_steps(delta) {
+            self.character.update(
+                self.physics_engine.active_gravity() + translation,
+                &mut self.physics_engine,
+                FIXED_DT,
+            );
+            self.physics_engine.step_n(1, FIXED_DT);
+        }
 
         if let Some(position) = self.character.position(&self.physics_engine) {
             self.camera.position = position.into();
         }
 
         self.renderer.update_camera(&self.camera, renderer);
-        self.physics_engine
-            .draw(view, &mut self.line_renderer, &self.renderer, renderer)
+        let mut vertices = self.physics_engine.debug_vertices();
+        vertices.extend(self.gizmo_vertices());
+        self.line_renderer
+            .draw(vertices, renderer, &self.renderer, view)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use rapier3d::parry::query::RayCast;
+
+    #[test]
+    fn disabled_gravity_stops_falling() {
+        let mut engine = PhysicsEngine {
+            gravity: Vector3::new(0.0, -9.81, 0.0),
+            gravity_enabled: false,
+            ..Default::default()
+        };
+        let body = engine
+            .bodies
+            .insert(RigidBodyBuilder::dynamic().translation(Vector3::new(0.0, 10.0, 0.0)));
+        engine
+            .colliders
+            .insert_with_parent(ColliderBuilder::ball(0.5), body, &mut engine.bodies);
+        let height = |engine: &PhysicsEngine| engine.body_position(body).unwrap().y;
+
+        engine.step_n(10, 1.0 / 60.0);
+        assert_eq!(height(&engine), 10.0);
+
+        engine.gravity_enabled = true;
+        engine.step_n(10, 1.0 / 60.0);
+        assert!(height(&engine) < 10.0);
+    }
+
+    #[test]
+    fn frame_time_runs_fixed_steps() {
+        let mut engine = PhysicsEngine::default();
+        assert_eq!(engine.fixed_steps(0.05), 3);
+        assert_eq!(engine.fixed_steps(0.01), 0);
+        assert_eq!(engine.fixed_steps(0.01), 1);
+        // a stall only catches up a few steps
+        assert_eq!(engine.fixed_steps(2.0), MAX_STEPS);
+        assert!(engine.accumulator < FIXED_DT);
+    }
+
+    #[test]
+    fn ray_hits_zone_collider() {
+        let contents = std::fs::read_to_string("./assets/map.toml").unwrap();
+        let grid = Grid::try_from(
+            excali_io::toml::from_str::<crate::map::SerialableGrid>(&contents).unwrap(),
+        )
+        .unwrap();
+        let mut engine = PhysicsEngine::default();
+        engine.insert_zones(&grid);
+        engine
+            .query_pipeline
+            .update(&engine.bodies, &engine.colliders);
+
+        let coordinate = grid.starting_zone;
+        let target = grid.world_position(coordinate);
+        let ray = Ray::new(
+            (target + Vector3::new(0.0, 10.0, 0.0)).into(),
+            Vector3::new(0.0, -1.0, 0.0),
+        );
+        assert_eq!(engine.zone_under_ray(&ray, 100.0), Some(coordinate));
+        let (_, collider) = grid
+            .zone_colliders()
+            .into_iter()
+            .find(|(zone, _)| *zone == coordinate)
+            .unwrap();
+        assert!(collider.compute_aabb().intersects_local_ray(&ray, 100.0));
+
+        let miss = Ray::new(
+            (target + Vector3::new(0.0, 10.0, 0.7)).into(),
+            Vector3::new(0.0, -1.0, 0.0),
+        );
+        assert_eq!(engine.zone_under_ray(&miss, 100.0), None);
+    }
+
+    #[test]
+    fn hovered_zone_is_highlighted() {
+        let contents = std::fs::read_to_string("./assets/map.toml").unwrap();
+        let grid = Grid::try_from(
+            excali_io::toml::from_str::<crate::map::SerialableGrid>(&contents).unwrap(),
+        )
+        .unwrap();
+        let mut engine = PhysicsEngine::default();
+        engine.insert_zones(&grid);
+        engine
+            .query_pipeline
+            .update(&engine.bodies, &engine.colliders);
+
+        let coordinate = grid.starting_zone;
+        let position = grid.world_position(coordinate);
+        let ray = Ray::new(
+            (position + Vector3::new(0.0, 10.0, 0.0)).into(),
+            Vector3::new(0.0, -1.0, 0.0),
+        );
+        let hovered = engine
+            .zone_under_ray(&ray, 100.0)
+            .filter(|coordinate| selectable(&grid, *coordinate));
+        assert_eq!(hovered, Some(coordinate));
+
+        let selected = grid.zones[&coordinate].next_zones[0];
+        let instances = PinInstances::new(&grid, hovered, selected);
+        assert_eq!(instances.hovered, vec![Matrix4::new_translation(&position)]);
+        assert_eq!(
+            instances.selected,
+            vec![Matrix4::new_translation(&grid.world_position(selected))]
+        );
+        assert_eq!(instances.pins.len(), grid.zones.len() - 2);
+    }
+
+    #[test]
+    fn character_walks_through_zone_sensor() {
+        let mut engine = PhysicsEngine::default();
+        let mut character = Character::new(&mut engine.bodies, &mut engine.colliders);
+        // a zone's box in the character's way
+        engine.colliders.insert(
+            ColliderBuilder::cuboid(0.5, 1.0, 0.5)
+                .translation(Vector3::new(4.0, 100.0, 0.0))
+                .sensor(true)
+                .build(),
+        );
+        engine.step_n(1, FIXED_DT);
+
+        for _ in 0..60 {
+            character.update(Vector3::new(0.1, 0.0, 0.0), &mut engine, FIXED_DT);
+            engine.step_n(1, FIXED_DT);
+        }
+        let position = character.position(&engine).unwrap();
+        assert!(position.x > 5.9, "stopped at {}", position.x);
+    }
+
+    #[test]
+    fn body_rests_on_floor() {
+        let mut engine = PhysicsEngine {
+            gravity: Vector3::new(0.0, -9.81, 0.0),
+            ..Default::default()
+        };
+        // the floor's top is at y = 1
+        engine
+            .colliders
+            .insert(ColliderBuilder::cuboid(10.0, 1.0, 10.0).build());
+        let body = engine
+            .bodies
+            .insert(RigidBodyBuilder::dynamic().translation(Vector3::new(0.0, 3.0, 0.0)));
+        engine
+            .colliders
+            .insert_with_parent(ColliderBuilder::ball(0.5), body, &mut engine.bodies);
+
+        engine.step_n(120, 1.0 / 60.0);
+        let position = engine.body_position(body).unwrap();
+        assert!((position.y - 1.5).abs() < 0.05, "rested at {}", position.y);
     }
 }